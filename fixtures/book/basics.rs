@@ -0,0 +1,12 @@
+// + 变量
+// 变量默认不可变
+// 需要修改时加 mut
+let x = 5;
+
+// * 遮蔽和 mut 不同
+// ? 遮蔽会创建一个新变量
+// ? 类型也可以改变
+// ! 不能给不可变变量赋值
+let x = x + 1;
+
+// *e 不是要点
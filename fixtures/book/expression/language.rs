@@ -0,0 +1,2 @@
+// + match
+fn f() {}
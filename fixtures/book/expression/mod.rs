@@ -0,0 +1,5 @@
+// 表达式
+pub mod language;
+pub fn answer() -> i32 {
+    42
+}
@@ -0,0 +1,4 @@
+// 只有模块声明, 不单独成章
+pub mod basics;
+pub mod expression;
+mod zeta;
@@ -0,0 +1 @@
+// 最后一章
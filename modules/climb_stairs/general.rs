@@ -0,0 +1,348 @@
+// 一般化的爬楼梯
+// climb_stairs 只能一次走 1 或 2 级, 结果是 i32, n 稍大就溢出
+// 这里把它扩展成一个动态规划的例子:
+//   * 允许的步长是任意集合, 例如 {1, 3, 5}
+//   * 可以禁止踩某些台阶(坏掉的台阶)
+//   * 每级台阶有代价时, 求代价最小的走法(LeetCode 746 的一般形式)
+//   * 方案数用 BigUint, 不会溢出
+//   * 用迭代器逐个列出具体的走法
+// ! 台阶的编号: 地面是 0, 顶部是 n, 一次走 s 级就是从 i 到 i + s, 不能越过顶部
+//   所以只允许 {1, 2} 时 ways(n) = F(n+1); 而 climb_stairs(n) 返回的是 F(n), 两者差一项
+use num::bigint::BigUint;
+use num::traits::{One, Zero};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone)]
+pub struct Staircase {
+    // 允许的步长, 从小到大, 不含 0
+    steps: Vec<usize>,
+    forbidden: BTreeSet<usize>,
+}
+
+// 代价最小的一种走法
+#[derive(Debug, Clone, PartialEq)]
+pub struct Climb {
+    pub cost: u64,
+    // 依次踩过的台阶, 从 0 开始, 到顶部结束
+    pub stairs: Vec<usize>,
+}
+
+impl Staircase {
+    // 步长会去重并排序, 0 没有意义(原地不动), 直接去掉
+    pub fn new(steps: &[usize]) -> Staircase {
+        let steps: BTreeSet<usize> = steps.iter().copied().filter(|&s| s > 0).collect();
+        Staircase {
+            steps: steps.into_iter().collect(),
+            forbidden: BTreeSet::new(),
+        }
+    }
+
+    // 禁止踩这些台阶; 禁止 0 或者顶部时没有任何走法
+    pub fn forbid(mut self, stairs: &[usize]) -> Staircase {
+        self.forbidden.extend(stairs);
+        self
+    }
+
+    pub fn steps(&self) -> &[usize] {
+        &self.steps
+    }
+
+    fn allowed(&self, stair: usize) -> bool {
+        !self.forbidden.contains(&stair)
+    }
+
+    // 到达第 n 级的方案数
+    // dp[i] = sum(dp[i - s]), 第 i 级被禁止时 dp[i] = 0
+    pub fn ways(&self, n: usize) -> BigUint {
+        let mut dp: Vec<BigUint> = Vec::with_capacity(n + 1);
+        for i in 0..=n {
+            let count = if !self.allowed(i) {
+                BigUint::zero()
+            } else if i == 0 {
+                BigUint::one()
+            } else {
+                self.steps
+                    .iter()
+                    .take_while(|&&s| s <= i)
+                    .fold(BigUint::zero(), |sum, &s| sum + &dp[i - s])
+            };
+            dp.push(count);
+        }
+        dp.pop().unwrap_or_default()
+    }
+
+    // 代价最小的走法, costs[i] 是踩上第 i 级的代价, 顶部是 costs.len() - 1
+    // ? 地面 costs[0] 也会计入, 不想算地面就把它设成 0; 到不了顶部时返回 None
+    pub fn min_cost(&self, costs: &[u64]) -> Option<Climb> {
+        let n = costs.len().checked_sub(1)?;
+        // best[i] = (到第 i 级的最小代价, 上一级台阶)
+        let mut best: Vec<Option<(u64, usize)>> = vec![None; n + 1];
+        if self.allowed(0) {
+            best[0] = Some((costs[0], 0));
+        }
+        for i in 1..=n {
+            if !self.allowed(i) {
+                continue;
+            }
+            // ? 代价相同时取步长小的那个, 这样结果是确定的
+            best[i] = self
+                .steps
+                .iter()
+                .take_while(|&&s| s <= i)
+                .filter_map(|&s| best[i - s].map(|(cost, _)| (cost, i - s)))
+                .min_by_key(|&(cost, from)| (cost, std::cmp::Reverse(from)))
+                .map(|(cost, from)| (cost + costs[i], from));
+        }
+        let (cost, _) = best[n]?;
+        // 从顶部沿着 "上一级" 倒推回地面
+        let mut stairs = vec![n];
+        let mut stair = n;
+        while stair > 0 {
+            stair = best[stair]?.1;
+            stairs.push(stair);
+        }
+        stairs.reverse();
+        Some(Climb { cost, stairs })
+    }
+
+    // 按步长的字典序逐个列出到达第 n 级的走法, 每种走法是依次走的步长
+    pub fn paths(&self, n: usize) -> Paths<'_> {
+        // finishes[i]: 从第 i 级还能不能走到顶部, 提前算好, 枚举时不会走进死路
+        let mut finishes = vec![false; n + 1];
+        for i in (0..=n).rev() {
+            finishes[i] = self.allowed(i)
+                && (i == n || self.steps.iter().any(|&s| i + s <= n && finishes[i + s]));
+        }
+        Paths {
+            staircase: self,
+            n,
+            stack: if finishes[0] {
+                vec![(0, 0)]
+            } else {
+                Vec::new()
+            },
+            finishes,
+            path: Vec::new(),
+        }
+    }
+}
+
+// 深度优先的枚举, 用显式的栈代替递归, 每次 next 只往前走到下一个完整的走法
+// 走法的数量会随 n 指数增长, 所以是惰性的, 需要多少取多少
+pub struct Paths<'a> {
+    staircase: &'a Staircase,
+    n: usize,
+    finishes: Vec<bool>,
+    // (当前台阶, 下一个要尝试的步长下标)
+    stack: Vec<(usize, usize)>,
+    path: Vec<usize>,
+}
+
+impl Iterator for Paths<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        loop {
+            let (stair, next) = self.stack.last_mut()?;
+            if *stair == self.n {
+                let path = self.path.clone();
+                self.stack.pop();
+                self.path.pop();
+                return Some(path);
+            }
+            match self.staircase.steps.get(*next) {
+                Some(&step) => {
+                    *next += 1;
+                    let to = *stair + step;
+                    if to <= self.n && self.finishes[to] {
+                        self.stack.push((to, 0));
+                        self.path.push(step);
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.path.pop();
+                }
+            }
+        }
+    }
+}
+
+fn parse_list<T: std::str::FromStr>(text: &str) -> Option<Vec<T>> {
+    text.split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+// 子命令入口: stairs <n> [--steps 1,2] [--forbid 3,5] [--costs 0,10,15,...] [--paths [数量]]
+// 给了 --costs 时顶部是 costs 的最后一级, n 可以省略
+pub fn stairs_main(args: &[String]) {
+    let usage =
+        "用法: stairs <n> [--steps 1,2] [--forbid 3,5] [--costs 0,10,15,...] [--paths [数量]]";
+    let mut n: Option<usize> = None;
+    let (mut steps, mut forbid, mut costs) = (vec![1, 2], Vec::new(), None);
+    let mut paths: Option<usize> = None;
+    let mut rest = args.iter().peekable();
+    while let Some(arg) = rest.next() {
+        let parsed = match arg.as_str() {
+            "--steps" => rest.next().and_then(|v| parse_list(v)).map(|v| steps = v),
+            "--forbid" => rest.next().and_then(|v| parse_list(v)).map(|v| forbid = v),
+            "--costs" => rest
+                .next()
+                .and_then(|v| parse_list::<u64>(v))
+                .map(|v| costs = Some(v)),
+            "--paths" => {
+                paths = Some(
+                    rest.next_if(|v| v.parse::<usize>().is_ok())
+                        .map_or(20, |v| v.parse().unwrap_or(20)),
+                );
+                Some(())
+            }
+            other => other.parse().ok().map(|value| n = Some(value)),
+        };
+        if parsed.is_none() {
+            eprintln!("无法解析参数 {:?}\n{}", arg, usage);
+            return;
+        }
+    }
+    let staircase = Staircase::new(&steps).forbid(&forbid);
+    let Some(n) = n.or(costs.as_ref().map(|costs| costs.len().saturating_sub(1))) else {
+        eprintln!("{}", usage);
+        return;
+    };
+    println!(
+        "步长 {:?}, 禁止 {:?}, 到第 {} 级共有 {} 种走法",
+        staircase.steps(),
+        forbid,
+        n,
+        staircase.ways(n)
+    );
+    if let Some(costs) = costs {
+        match staircase.min_cost(&costs) {
+            Some(climb) => println!("最小代价 {}, 依次踩 {:?}", climb.cost, climb.stairs),
+            None => println!("到不了第 {} 级", costs.len().saturating_sub(1)),
+        }
+    }
+    if let Some(limit) = paths {
+        for path in staircase.paths(n).take(limit) {
+            println!("{:?}", path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 依次踩过 path 中的步长, 总代价(包括地面)
+    fn path_cost(path: &[usize], costs: &[u64]) -> u64 {
+        let mut stair = 0;
+        path.iter().fold(costs[0], |cost, step| {
+            stair += step;
+            cost + costs[stair]
+        })
+    }
+
+    #[test]
+    fn steps_are_normalized() {
+        assert_eq!(Staircase::new(&[2, 0, 1, 2]).steps(), [1, 2]);
+    }
+
+    // 只允许 {1, 2} 时 ways(n) = F(n+1) = climb_stairs(n + 1)
+    #[test]
+    fn one_or_two_steps() {
+        let staircase = Staircase::new(&[1, 2]);
+        for n in 0..45 {
+            let expected = crate::modules::climb_stairs::climb_stairs(n as i32 + 1);
+            assert_eq!(
+                staircase.ways(n),
+                BigUint::from(expected as u32),
+                "n = {}",
+                n
+            );
+        }
+        // i32 放不下之后继续用递推比较
+        for n in 2..200 {
+            assert_eq!(
+                staircase.ways(n),
+                staircase.ways(n - 1) + staircase.ways(n - 2)
+            );
+        }
+    }
+
+    // 允许 {1, 2, 3} 时是 Tribonacci
+    #[test]
+    fn up_to_three_steps() {
+        let staircase = Staircase::new(&[1, 2, 3]);
+        let ways: Vec<BigUint> = (0..8).map(|n| staircase.ways(n)).collect();
+        assert_eq!(ways, [1u32, 1, 2, 4, 7, 13, 24, 44].map(BigUint::from));
+    }
+
+    #[test]
+    fn forbidden_stairs() {
+        // 不能踩第 3 级: 先到第 2 级(两种), 然后 2 -> 4 -> 5
+        let staircase = Staircase::new(&[1, 2]).forbid(&[3]);
+        assert_eq!(staircase.ways(5), BigUint::from(2u32));
+        let paths: Vec<Vec<usize>> = staircase.paths(5).collect();
+        assert_eq!(paths, [vec![1, 1, 2, 1], vec![2, 2, 1]]);
+        // 禁止地面或者顶部时没有走法
+        for stair in [0, 5] {
+            let blocked = Staircase::new(&[1, 2]).forbid(&[stair]);
+            assert_eq!(blocked.ways(5), BigUint::zero());
+            assert_eq!(blocked.paths(5).count(), 0);
+            assert_eq!(blocked.min_cost(&[0; 6]), None);
+        }
+    }
+
+    // LeetCode 746 的两个例子, 地面和顶部的代价是 0
+    #[test]
+    fn min_cost_examples() {
+        let staircase = Staircase::new(&[1, 2]);
+        assert_eq!(
+            staircase.min_cost(&[0, 10, 15, 20, 0]),
+            Some(Climb {
+                cost: 15,
+                stairs: vec![0, 2, 4],
+            })
+        );
+        let costs = [0, 1, 100, 1, 1, 1, 100, 1, 1, 100, 1, 0];
+        assert_eq!(staircase.min_cost(&costs).unwrap().cost, 6);
+        assert_eq!(staircase.min_cost(&[]), None);
+    }
+
+    #[test]
+    fn min_cost_unreachable() {
+        // 只能一次走 2 级, 到不了奇数级
+        assert_eq!(Staircase::new(&[2]).min_cost(&[0; 4]), None);
+        assert_eq!(Staircase::new(&[2]).ways(3), BigUint::zero());
+        // 连续两级被禁止, 一次最多走 2 级跨不过去
+        let staircase = Staircase::new(&[1, 2]).forbid(&[2, 3]);
+        assert_eq!(staircase.min_cost(&[1; 6]), None);
+        assert_eq!(staircase.paths(5).count(), 0);
+    }
+
+    // 枚举出的走法数量和 ways 一致, 代价最小的走法和逐个枚举找到的一样
+    #[test]
+    fn paths_agree_with_ways_and_min_cost() {
+        let staircase = Staircase::new(&[1, 3, 4]).forbid(&[5]);
+        for n in 0..20 {
+            let paths: Vec<Vec<usize>> = staircase.paths(n).collect();
+            assert_eq!(BigUint::from(paths.len()), staircase.ways(n), "n = {}", n);
+            let costs: Vec<u64> = (0..=n as u64).map(|i| i * 7 % 11).collect();
+            let brute = paths.iter().map(|path| path_cost(path, &costs)).min();
+            let climb = staircase.min_cost(&costs);
+            assert_eq!(climb.as_ref().map(|climb| climb.cost), brute, "n = {}", n);
+            if let Some(climb) = climb {
+                assert_eq!(climb.stairs.first(), Some(&0));
+                assert_eq!(climb.stairs.last(), Some(&n));
+            }
+        }
+    }
+
+    // 按步长的字典序
+    #[test]
+    fn paths_in_order() {
+        let paths: Vec<Vec<usize>> = Staircase::new(&[1, 2]).paths(3).collect();
+        assert_eq!(paths, [vec![1, 1, 1], vec![1, 2], vec![2, 1]]);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod general;
+
 use std::mem::replace;
 pub fn climb_stairs(n: i32) -> i32 {
     let mut f0 = 0;
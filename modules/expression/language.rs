@@ -1,11 +1,9 @@
 // + 1. 表达式语言
-use super::system_info::{get_cpu_info, Cpu};
-
-#[derive(Debug)]
-enum CpuStatus {
-    Idle,
-    Busy,
-}
+use super::system_info::backend::Backend;
+use super::system_info::background::SampledMetrics;
+use super::system_info::monitor;
+use super::system_info::provider::{LiveProvider, SystemInfoProvider};
+use super::system_info::status::{CpuStatus, StatusConfig};
 
 // + 2. 块与分号
 fn bound() {
@@ -300,6 +298,19 @@ fn type_transform() {
     // &Box<Chessboard> -> &Chessboard
 }
 
+// 测量一次, 用和后台采样相同的 SampledMetrics::update 更新历史、状态和最新快照, 返回当前状态
+// ? provider 换成 ScriptedProvider 就可以不睡眠、不依赖真实机器地验证状态判断
+// ! 测量失败时状态是 Unknown, 不能编造一个全 0 的 Cpu, 那和真正空闲的机器分不清
+pub async fn measure_status<P: SystemInfoProvider>(
+    provider: &mut P,
+    metrics: &mut SampledMetrics,
+) -> CpuStatus {
+    let sample = provider.snapshot(1000).await;
+    // 状态判断用 history 平滑后的值, 单次采样抖动太大
+    metrics.update(sample, provider.now_millis());
+    metrics.classifier.status()
+}
+
 pub async fn expression_language() {
     // Rust虽然看起来和C家族的语言很像, 但是这只是它的一个策略。
     // 在C中, 表达式和语句有着明显的区别
@@ -328,25 +339,35 @@ pub async fn expression_language() {
     */
     // Rust 的 if表达式可以用来初始化变量, 并且配合match产生值
     println!("start");
-    let cpu: Cpu = match get_cpu_info(1000).await {
-        Ok(cpu) => cpu,
-        Err(_) => Cpu {
-            count: 0,
-            usage: 0.0,
-        },
-    };
-    const IDLE_USEAGE_PERCENT: f32 = 10.0;
-    // 利用if初始化变量
-    let status = if cpu.usage <= IDLE_USEAGE_PERCENT {
-        CpuStatus::Idle
-    } else {
-        CpuStatus::Busy
-    };
+    // 阈值、迟滞和最短停留时间都在 StatusConfig 中配置, 这里用默认值
+    let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+    let mut provider = LiveProvider::default();
+    println!("backend: {}", provider.backend().name());
+    let status = measure_status(&mut provider, &mut metrics).await;
     // * 同时 match 表达式可以作为参数传递给宏或者函数
     // 在Rust中, 大多数控制流都是表达式, 几乎没有语句~
-    println!("current cpu status is: {:?}", status);
-    println!("current cpu count is: {:?}", cpu.count);
-    println!("current cpu useage is: {:?}", cpu.usage);
+    println!(
+        "current cpu status is: {}",
+        match &metrics.last_error {
+            Some(err) => format!("{} ({})", status, err),
+            None => status.to_string(),
+        }
+    );
+    // 和 monitor 子命令画的是同一帧, 输出不是终端时是一行 key=value
+    monitor::print_frame(&metrics);
+    // 使用率之外再看温度和频率: 过热降频时使用率很高, 干的活却很少
+    if let Some(snapshot) = &metrics.snapshot {
+        match snapshot.sensors.hottest() {
+            Some(sensor) => println!(
+                "current hottest sensor is: {} {:.1}°C",
+                sensor.label, sensor.celsius
+            ),
+            None => println!("current cpu temperature is: unavailable"),
+        }
+        if let Some(hint) = snapshot.sensors.throttle_hint(status) {
+            println!("thermal throttling hint: {}", hint);
+        }
+    }
 
     println!("--------------------------------循环----------------------------------------");
     loop_fn();
@@ -360,3 +381,41 @@ pub async fn expression_language() {
     if_and_match();
     println!("--------------------------------if和模式匹配----------------------------------------");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::provider::ScriptedProvider;
+    use crate::modules::expression::system_info::SystemInfoError;
+    use std::time::Duration;
+
+    // 测量失败时状态是 Unknown, 恢复后立即按历史平滑后的值重新分级
+    #[tokio::test]
+    async fn measure_status_with_error() {
+        let mut provider = ScriptedProvider::from_usages(&[95.0]);
+        provider.push_error(SystemInfoError::Timeout {
+            operation: "读取CPU时间".to_string(),
+            after: Duration::from_secs(5),
+        });
+        provider.push_usage(20.0);
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+
+        let status = measure_status(&mut provider, &mut metrics).await;
+        assert_eq!(status, CpuStatus::Saturated);
+        assert_eq!(metrics.snapshot.as_ref().unwrap().cpu.usage, 95.0);
+        assert_eq!(metrics.timestamp, 1_000);
+
+        let status = measure_status(&mut provider, &mut metrics).await;
+        assert_eq!(status, CpuStatus::Unknown);
+        assert!(metrics.snapshot.is_none());
+        assert_eq!(metrics.last_error.as_deref(), Some("读取CPU时间 超时(5s)"));
+        assert_eq!(metrics.history.len(), 1);
+
+        // EWMA = 0.3 * 20 + 0.7 * 95 = 72.5
+        let status = measure_status(&mut provider, &mut metrics).await;
+        assert_eq!(metrics.history.ewma(), Some(72.5));
+        assert_eq!(status, CpuStatus::Busy);
+        assert!(metrics.last_error.is_none());
+        assert!(provider.finished());
+    }
+}
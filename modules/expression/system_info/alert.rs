@@ -0,0 +1,587 @@
+// 阈值告警
+// 在状态分级之上定义告警规则, 例如 "usage > 90% 持续 2 分钟" 或 "连续 5 个采样处于 Busy"
+// 每条规则有 pending -> firing -> resolved 的生命周期, 状态变化时通知所有 notifier
+// 处于 firing 的规则遇到采样失败时发出 unavailable 事件, 数据恢复后重新判断, 不会一直停在 firing
+// 规则和 notifier 都从 JSON 文件加载, 格式如下:
+/*
+    {
+        "status": { "min_dwell_ms": 5000 },
+        "rules": [
+            { "name": "cpu-high", "when": { "metric": "cpu_usage", "above": 90 }, "for_ms": 120000 },
+            { "name": "cpu-busy", "when": { "metric": "status", "at_least": "Busy" }, "consecutive": 5 }
+        ],
+        "notifiers": [
+            { "type": "stderr" },
+            { "type": "log_file", "path": "alerts.log" },
+            { "type": "command", "program": "/usr/local/bin/page-me", "args": ["--team", "infra"] }
+        ]
+    }
+*/
+use super::background::{run_sampler, SampledMetrics};
+use super::status::{CpuStatus, StatusConfig};
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::process::Command;
+use std::time::Duration;
+
+// 规则判断的指标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "metric", rename_all = "snake_case")]
+pub enum Condition {
+    // 整体使用率, 平均每个核心的口径(0 ~ 100), 见 Cpu::per_core
+    // ? 8 核机器上 "usage > 90" 指的是 8 个核心平均超过 90%, 而不是一个核心忙
+    CpuUsage { above: f32 },
+    // 最忙核心的使用率
+    MaxCoreUsage { above: f32 },
+    // 内存使用百分比
+    MemoryUsed { above: f32 },
+    // 1 分钟平均负载
+    LoadAverage { above: f32 },
+    // 状态分级不低于某一档
+    Status { at_least: CpuStatus },
+}
+
+impl Condition {
+    // 返回 (是否满足, 当前值), 还没有采样时不满足
+    fn check(&self, metrics: &SampledMetrics) -> Option<(bool, f32)> {
+        let snapshot = metrics.snapshot.as_ref()?;
+        Some(match self {
+            Condition::CpuUsage { above } => {
+                let value = snapshot.cpu.per_core(snapshot.cpu.usage);
+                (value > *above, value)
+            }
+            Condition::MaxCoreUsage { above } => {
+                let value = snapshot.cpu.max_core_usage();
+                (value > *above, value)
+            }
+            Condition::MemoryUsed { above } => {
+                let memory = &snapshot.memory;
+                let value = if memory.total == 0 {
+                    0.0
+                } else {
+                    (memory.used as f64 / memory.total as f64 * 100.0) as f32
+                };
+                (value > *above, value)
+            }
+            Condition::LoadAverage { above } => {
+                let value = snapshot.load_average.one;
+                (value > *above, value)
+            }
+            Condition::Status { at_least } => {
+                let status = metrics.classifier.status();
+                (
+                    status >= *at_least,
+                    snapshot.cpu.per_core(snapshot.cpu.usage),
+                )
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    pub when: Condition,
+    // 条件需要持续满足的时长(毫秒)
+    #[serde(default)]
+    pub for_ms: u64,
+    // 条件需要连续满足的采样次数
+    // ? for_ms 和 consecutive 可以同时设置, 两者都满足才触发; 都不设置时第一次满足就触发
+    #[serde(default)]
+    pub consecutive: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+    // 规则处于 firing 时采样失败, 无法判断条件是否仍然满足
+    Unavailable,
+}
+
+// 告警事件, 发给 notifier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub state: AlertState,
+    pub value: f32,
+    // 毫秒级 unix 时间戳
+    pub timestamp: u64,
+    // 条件开始满足的时间
+    pub since: u64,
+}
+
+impl AlertEvent {
+    pub fn message(&self) -> String {
+        let state = match self.state {
+            AlertState::Firing => "FIRING",
+            AlertState::Resolved => "RESOLVED",
+            AlertState::Unavailable => "UNAVAILABLE",
+        };
+        format!(
+            "[{}] {} value={:.2} since={} at={}",
+            state, self.rule, self.value, self.since, self.timestamp
+        )
+    }
+}
+
+// 单条规则的内部状态
+#[derive(Debug, Clone, Copy)]
+enum RuleState {
+    Inactive,
+    // 条件已满足但还没达到持续时长/次数
+    Pending { since: u64, count: usize },
+    Firing { since: u64 },
+    // 在 firing 期间数据不可用, since 是原来开始满足的时间
+    Unavailable { since: u64 },
+}
+
+pub trait Notifier {
+    fn notify(&self, event: &AlertEvent) -> io::Result<()>;
+}
+
+// 输出到标准错误
+pub struct StderrNotifier;
+
+impl Notifier for StderrNotifier {
+    fn notify(&self, event: &AlertEvent) -> io::Result<()> {
+        writeln!(io::stderr(), "{}", event.message())
+    }
+}
+
+// 追加到日志文件, 每行一个 JSON
+pub struct LogFileNotifier {
+    pub path: String,
+}
+
+impl Notifier for LogFileNotifier {
+    fn notify(&self, event: &AlertEvent) -> io::Result<()> {
+        let line = serde_json::to_string(event)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+// 执行本地命令, 告警信息通过环境变量传入
+// ! 不经过 shell, 参数原样传给程序, 避免告警内容被当成命令解析
+pub struct CommandNotifier {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, event: &AlertEvent) -> io::Result<()> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env("ALERT_RULE", &event.rule)
+            .env(
+                "ALERT_STATE",
+                serde_json::to_string(&event.state)?.trim_matches('"'),
+            )
+            .env("ALERT_VALUE", event.value.to_string())
+            .env("ALERT_TIMESTAMP", event.timestamp.to_string())
+            .env("ALERT_MESSAGE", event.message());
+        let mut child = command.spawn()?;
+        // 在单独的线程里等待子进程退出, 既不阻塞采样, 也不会留下僵尸进程
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    Stderr,
+    LogFile {
+        path: String,
+    },
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl NotifierConfig {
+    pub fn build(&self) -> Box<dyn Notifier + Send> {
+        match self {
+            NotifierConfig::Stderr => Box::new(StderrNotifier),
+            NotifierConfig::LogFile { path } => Box::new(LogFileNotifier { path: path.clone() }),
+            NotifierConfig::Command { program, args } => Box::new(CommandNotifier {
+                program: program.clone(),
+                args: args.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertConfig {
+    // 状态分级的配置, 供 status 规则使用
+    #[serde(default)]
+    pub status: StatusConfig,
+    pub rules: Vec<AlertRule>,
+    // 不配置时默认输出到标准错误
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+}
+
+impl AlertConfig {
+    pub fn load(path: &str) -> io::Result<AlertConfig> {
+        let text = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+pub struct AlertEngine {
+    rules: Vec<(AlertRule, RuleState)>,
+    notifiers: Vec<Box<dyn Notifier + Send>>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>, notifiers: Vec<Box<dyn Notifier + Send>>) -> AlertEngine {
+        AlertEngine {
+            rules: rules
+                .into_iter()
+                .map(|rule| (rule, RuleState::Inactive))
+                .collect(),
+            notifiers,
+        }
+    }
+
+    pub fn from_config(config: &AlertConfig) -> AlertEngine {
+        let mut notifiers: Vec<Box<dyn Notifier + Send>> =
+            config.notifiers.iter().map(NotifierConfig::build).collect();
+        if notifiers.is_empty() {
+            notifiers.push(Box::new(StderrNotifier));
+        }
+        AlertEngine::new(config.rules.clone(), notifiers)
+    }
+
+    // 当前处于 firing 的规则
+    pub fn firing(&self) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|(_, state)| matches!(state, RuleState::Firing { .. }))
+            .map(|(rule, _)| rule.name.as_str())
+            .collect()
+    }
+
+    // 用最新的采样评估所有规则, 返回本次产生的事件并通知 notifier
+    // ? 没有可用的采样时: firing 的规则发出一次 unavailable, pending 的规则重新计数
+    //   数据恢复后, 条件仍然满足就再发一次 firing, 否则发 resolved
+    pub fn evaluate(&mut self, metrics: &SampledMetrics, timestamp: u64) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for (rule, state) in self.rules.iter_mut() {
+            let Some((matched, value)) = rule.when.check(metrics) else {
+                *state = match *state {
+                    RuleState::Firing { since } => {
+                        events.push(AlertEvent {
+                            rule: rule.name.clone(),
+                            state: AlertState::Unavailable,
+                            value: f32::NAN,
+                            timestamp,
+                            since,
+                        });
+                        RuleState::Unavailable { since }
+                    }
+                    RuleState::Pending { .. } => RuleState::Inactive,
+                    unchanged => unchanged,
+                };
+                continue;
+            };
+            let event = |state, since| AlertEvent {
+                rule: rule.name.clone(),
+                state,
+                value,
+                timestamp,
+                since,
+            };
+            *state = match (*state, matched) {
+                (RuleState::Inactive, true) => RuleState::Pending {
+                    since: timestamp,
+                    count: 1,
+                },
+                (RuleState::Pending { since, count }, true) => RuleState::Pending {
+                    since,
+                    count: count + 1,
+                },
+                (RuleState::Firing { since }, false) => {
+                    events.push(event(AlertState::Resolved, since));
+                    RuleState::Inactive
+                }
+                (RuleState::Firing { since }, true) => RuleState::Firing { since },
+                (RuleState::Unavailable { since }, true) => {
+                    events.push(event(AlertState::Firing, since));
+                    RuleState::Firing { since }
+                }
+                (RuleState::Unavailable { since }, false) => {
+                    events.push(event(AlertState::Resolved, since));
+                    RuleState::Inactive
+                }
+                (_, false) => RuleState::Inactive,
+            };
+            // 刚进入或仍在 pending, 检查是否达到持续时长和次数
+            if let RuleState::Pending { since, count } = *state {
+                if timestamp.saturating_sub(since) >= rule.for_ms && count >= rule.consecutive {
+                    events.push(event(AlertState::Firing, since));
+                    *state = RuleState::Firing { since };
+                }
+            }
+        }
+        for event in &events {
+            for notifier in &self.notifiers {
+                if let Err(err) = notifier.notify(event) {
+                    eprintln!("告警通知失败: {}", err);
+                }
+            }
+        }
+        events
+    }
+}
+
+// 子命令入口: alert <规则文件> [采样间隔毫秒]
+pub async fn alert_main(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("用法: alert <规则文件> [采样间隔毫秒]");
+            return;
+        }
+    };
+    let config = match AlertConfig::load(path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("加载告警规则失败: {}", err);
+            return;
+        }
+    };
+    let interval = args.get(1).and_then(|ms| ms.parse().ok()).unwrap_or(1000);
+    let mut engine = AlertEngine::from_config(&config);
+    let mut metrics = SampledMetrics::new(900, config.status.clone());
+    println!("已加载 {} 条告警规则", config.rules.len());
+    run_sampler(Duration::from_millis(interval), |sample, timestamp| {
+        if let Err(err) = &sample {
+            eprintln!("采样失败: {}", err);
+        }
+        metrics.update(sample, timestamp);
+        // 事件已经交给 notifier, 这里在标准输出上汇总仍在 firing 的规则
+        if !engine.evaluate(&metrics, timestamp).is_empty() {
+            let firing = engine.firing();
+            if firing.is_empty() {
+                println!("没有 firing 的规则");
+            } else {
+                println!("firing 中的规则: {}", firing.join(", "));
+            }
+        }
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::background::run_sampler_with;
+    use crate::modules::expression::system_info::provider::{
+        usage_sample, ScriptStep, ScriptedProvider,
+    };
+    use crate::modules::expression::system_info::SystemInfoError;
+
+    fn rule(when: Condition, for_ms: u64, consecutive: usize) -> AlertRule {
+        AlertRule {
+            name: "test".to_string(),
+            when,
+            for_ms,
+            consecutive,
+        }
+    }
+
+    // 按脚本采样, 每次采样后评估一次, 返回 (时间戳, 事件状态)
+    async fn replay(
+        rules: Vec<AlertRule>,
+        mut provider: ScriptedProvider,
+    ) -> Vec<(u64, AlertState)> {
+        let mut engine = AlertEngine::new(rules, Vec::new());
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        let mut states = Vec::new();
+        run_sampler_with(
+            &mut provider,
+            Duration::from_secs(1),
+            |sample, timestamp| {
+                metrics.update(sample, timestamp);
+                for event in engine.evaluate(&metrics, timestamp) {
+                    states.push((event.timestamp, event.state));
+                }
+            },
+        )
+        .await;
+        states
+    }
+
+    fn timeout() -> SystemInfoError {
+        SystemInfoError::Timeout {
+            operation: "读取CPU时间".to_string(),
+            after: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn fires_after_duration_and_resolves() {
+        let provider = ScriptedProvider::from_usages(&[95.0, 95.0, 95.0, 20.0]);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 2_000, 0)];
+        assert_eq!(
+            replay(rules, provider).await,
+            vec![(3_000, AlertState::Firing), (4_000, AlertState::Resolved)]
+        );
+    }
+
+    #[tokio::test]
+    async fn consecutive_count_is_required() {
+        let provider = ScriptedProvider::from_usages(&[95.0, 95.0, 20.0, 95.0, 95.0, 95.0]);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 0, 3)];
+        assert_eq!(
+            replay(rules, provider).await,
+            vec![(6_000, AlertState::Firing)]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_sample_reports_unavailable_then_resolves() {
+        let provider = ScriptedProvider::new([
+            ScriptStep::Sample(usage_sample(95.0)),
+            ScriptStep::Error(timeout()),
+            ScriptStep::Error(timeout()),
+            ScriptStep::Sample(usage_sample(20.0)),
+        ]);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 0, 0)];
+        assert_eq!(
+            replay(rules, provider).await,
+            vec![
+                (1_000, AlertState::Firing),
+                (2_000, AlertState::Unavailable),
+                (4_000, AlertState::Resolved),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_sample_then_still_matching_fires_again() {
+        let provider = ScriptedProvider::new([
+            ScriptStep::Sample(usage_sample(95.0)),
+            ScriptStep::Error(timeout()),
+            ScriptStep::Sample(usage_sample(95.0)),
+        ]);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 0, 0)];
+        assert_eq!(
+            replay(rules, provider).await,
+            vec![
+                (1_000, AlertState::Firing),
+                (2_000, AlertState::Unavailable),
+                (3_000, AlertState::Firing),
+            ]
+        );
+    }
+
+    // 8 核机器上一个核心满载时 usage 是 100, 平均每个核心只有 12.5%, 不应该触发 "> 90"
+    #[tokio::test]
+    async fn cpu_usage_is_per_core() {
+        let eight_cores = |usage: f32| {
+            let mut snapshot = usage_sample(usage);
+            snapshot.cpu.count = 8;
+            ScriptStep::Sample(snapshot)
+        };
+        let provider = ScriptedProvider::new([eight_cores(100.0), eight_cores(100.0)]);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 0, 0)];
+        assert_eq!(replay(rules.clone(), provider).await, Vec::new());
+
+        // 平均每个核心 95%
+        let provider = ScriptedProvider::new([eight_cores(100.0), eight_cores(760.0)]);
+        assert_eq!(
+            replay(rules, provider).await,
+            vec![(2_000, AlertState::Firing)]
+        );
+    }
+
+    #[test]
+    fn firing_rules() {
+        let mut engine = AlertEngine::new(
+            vec![
+                AlertRule {
+                    name: "cpu".to_string(),
+                    ..rule(Condition::CpuUsage { above: 90.0 }, 0, 0)
+                },
+                AlertRule {
+                    name: "memory".to_string(),
+                    ..rule(Condition::MemoryUsed { above: 90.0 }, 0, 0)
+                },
+            ],
+            Vec::new(),
+        );
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        metrics.record(usage_sample(95.0), 1_000);
+        let events = engine.evaluate(&metrics, 1_000);
+        assert_eq!(events.len(), 1);
+        assert_eq!(engine.firing(), ["cpu"]);
+        metrics.record(usage_sample(20.0), 2_000);
+        engine.evaluate(&metrics, 2_000);
+        assert!(engine.firing().is_empty());
+    }
+
+    // pending 期间采样失败, 重新开始计数
+    #[tokio::test]
+    async fn failed_sample_resets_pending() {
+        let mut provider = ScriptedProvider::from_usages(&[95.0, 95.0]);
+        provider.push_error(timeout());
+        provider.push_usage(95.0);
+        provider.push_usage(95.0);
+        let rules = vec![rule(Condition::CpuUsage { above: 90.0 }, 0, 3)];
+        assert_eq!(replay(rules, provider).await, Vec::new());
+    }
+
+    // 连续 3 个采样处于 Busy 以上才告警, 中间的失败让告警进入 unavailable
+    #[tokio::test]
+    async fn alert_on_status() {
+        let mut provider = ScriptedProvider::from_usages(&[95.0, 95.0, 95.0]);
+        provider.push(ScriptStep::Error(timeout()));
+        let rule = AlertRule {
+            name: "cpu-busy".to_string(),
+            when: Condition::Status {
+                at_least: CpuStatus::Busy,
+            },
+            for_ms: 0,
+            consecutive: 3,
+        };
+        let mut engine = AlertEngine::new(vec![rule], Vec::new());
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        let mut events = Vec::new();
+        run_sampler_with(
+            &mut provider,
+            Duration::from_secs(1),
+            |sample, timestamp| {
+                metrics.update(sample, timestamp);
+                events.extend(engine.evaluate(&metrics, timestamp));
+            },
+        )
+        .await;
+        let states: Vec<(u64, AlertState)> = events
+            .iter()
+            .map(|event| (event.timestamp, event.state))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (3_000, AlertState::Firing),
+                (4_000, AlertState::Unavailable)
+            ]
+        );
+        assert_eq!(metrics.classifier.status(), CpuStatus::Unknown);
+        assert!(metrics.last_error.is_some());
+    }
+}
@@ -0,0 +1,196 @@
+// heim 后端, 即最初的实现
+use super::Backend;
+use crate::modules::expression::system_info::process::ProcessTimes;
+use crate::modules::expression::system_info::throughput::{
+    DiskCounters, FilesystemUsage, InterfaceCounters,
+};
+use crate::modules::expression::system_info::{
+    CpuTimeSample, LoadAverage, Memory, Swap, SystemInfoError,
+};
+use futures::{StreamExt, TryStreamExt};
+use heim::{
+    cpu, disk, host, memory, net,
+    process::{Process, ProcessResult},
+    units::{information, ratio, time},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeimBackend;
+
+impl From<&cpu::CpuTime> for CpuTimeSample {
+    fn from(value: &cpu::CpuTime) -> Self {
+        let mut sample = CpuTimeSample {
+            user: value.user().get::<time::second>(),
+            system: value.system().get::<time::second>(),
+            idle: value.idle().get::<time::second>(),
+            ..Default::default()
+        };
+        // iowait 等字段只有 Linux 才有
+        #[cfg(target_os = "linux")]
+        {
+            use heim::cpu::os::linux::CpuTimeExt;
+            sample.nice = value.nice().get::<time::second>();
+            sample.iowait = value.io_wait().get::<time::second>();
+            sample.irq = value.irq().get::<time::second>();
+            sample.softirq = value.soft_irq().get::<time::second>();
+            sample.steal = value.steal().get::<time::second>();
+        }
+        sample
+    }
+}
+
+// heim 没有提供进程的所属用户, Linux 上用 /proc/<pid> 目录的属主代替
+fn process_uid(pid: u32) -> Option<u32> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(format!("/proc/{}", pid))
+            .ok()
+            .map(|meta| meta.uid())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+async fn read_process(process: Process) -> ProcessResult<ProcessTimes> {
+    let (name, command, cpu_time, memory, create_time) = futures::try_join!(
+        process.name(),
+        process.command(),
+        process.cpu_time(),
+        process.memory(),
+        process.create_time(),
+    )?;
+    let pid = process.pid() as u32;
+    Ok(ProcessTimes {
+        pid,
+        name,
+        cmdline: command.to_os_string().to_string_lossy().trim().to_string(),
+        uid: process_uid(pid),
+        // heim 的 create_time 已经是 Unix 时间戳, 和 ProcBackend 一致
+        start_time: create_time.get::<time::second>(),
+        cpu_time: (cpu_time.user() + cpu_time.system()).get::<time::second>(),
+        rss: memory.rss().get::<information::byte>(),
+    })
+}
+
+impl Backend for HeimBackend {
+    fn name(&self) -> &'static str {
+        "heim"
+    }
+
+    async fn cpu_times(&self) -> Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError> {
+        let (total, cores) = futures::try_join!(cpu::time(), async {
+            cpu::times().await?.try_collect::<Vec<_>>().await
+        })?;
+        Ok((
+            CpuTimeSample::from(&total),
+            cores.iter().map(CpuTimeSample::from).collect(),
+        ))
+    }
+
+    async fn logical_count(&self) -> Result<u64, SystemInfoError> {
+        Ok(cpu::logical_count().await?)
+    }
+
+    async fn memory(&self) -> Result<Memory, SystemInfoError> {
+        let memory = memory::memory().await?;
+        let total = memory.total().get::<information::byte>();
+        let available = memory.available().get::<information::byte>();
+        Ok(Memory {
+            total,
+            used: total.saturating_sub(available),
+            available,
+            // 容器的上限由上层补充
+            container: None,
+        })
+    }
+
+    async fn swap(&self) -> Result<Swap, SystemInfoError> {
+        let swap = memory::swap().await?;
+        Ok(Swap {
+            total: swap.total().get::<information::byte>(),
+            used: swap.used().get::<information::byte>(),
+            free: swap.free().get::<information::byte>(),
+        })
+    }
+
+    async fn load_average(&self) -> Result<LoadAverage, SystemInfoError> {
+        let (one, five, fifteen) = cpu::os::unix::loadavg().await?;
+        Ok(LoadAverage {
+            one: one.get::<ratio::ratio>(),
+            five: five.get::<ratio::ratio>(),
+            fifteen: fifteen.get::<ratio::ratio>(),
+        })
+    }
+
+    async fn uptime(&self) -> Result<f64, SystemInfoError> {
+        Ok(host::uptime().await?.get::<time::second>())
+    }
+
+    async fn processes(&self) -> Result<Vec<ProcessTimes>, SystemInfoError> {
+        let processes = heim::process::processes().await?;
+        Ok(processes
+            .filter_map(|process| async move { read_process(process.ok()?).await.ok() })
+            .collect()
+            .await)
+    }
+
+    async fn disk_counters(&self) -> Result<Vec<DiskCounters>, SystemInfoError> {
+        let counters = disk::io_counters().await?.try_collect::<Vec<_>>().await?;
+        Ok(counters
+            .iter()
+            .map(|counter| DiskCounters {
+                device: counter.device_name().to_string_lossy().to_string(),
+                reads: counter.read_count(),
+                writes: counter.write_count(),
+                read_bytes: counter.read_bytes().get::<information::byte>(),
+                write_bytes: counter.write_bytes().get::<information::byte>(),
+            })
+            // 和 proc 后端一样, 跳过从来没有读写过的设备
+            .filter(|disk| disk.reads > 0 || disk.writes > 0)
+            .collect())
+    }
+
+    async fn network_counters(&self) -> Result<Vec<InterfaceCounters>, SystemInfoError> {
+        let counters = net::io_counters().await?.try_collect::<Vec<_>>().await?;
+        Ok(counters
+            .iter()
+            .map(|counter| InterfaceCounters {
+                interface: counter.interface().to_string(),
+                rx_bytes: counter.bytes_recv().get::<information::byte>(),
+                tx_bytes: counter.bytes_sent().get::<information::byte>(),
+                rx_packets: counter.packets_recv(),
+                tx_packets: counter.packets_sent(),
+            })
+            .collect())
+    }
+
+    async fn filesystems(&self) -> Result<Vec<FilesystemUsage>, SystemInfoError> {
+        let partitions = disk::partitions_physical()
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut filesystems = Vec::new();
+        for partition in partitions {
+            // 挂载点无法访问时跳过这一个
+            let usage = match partition.usage().await {
+                Ok(usage) => usage,
+                Err(_) => continue,
+            };
+            filesystems.push(FilesystemUsage {
+                device: partition
+                    .device()
+                    .map_or(String::new(), |device| device.to_string_lossy().to_string()),
+                mount_point: partition.mount_point().display().to_string(),
+                fs_type: partition.file_system().as_str().to_string(),
+                total: usage.total().get::<information::byte>(),
+                used: usage.used().get::<information::byte>(),
+                free: usage.free().get::<information::byte>(),
+            });
+        }
+        Ok(filesystems)
+    }
+}
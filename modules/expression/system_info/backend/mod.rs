@@ -0,0 +1,200 @@
+// 数据来源(后端)
+// heim 是一个停更的 rc 版本, 依赖树很大, 而我们只需要几个读数
+// 这里把读数抽象成 Backend trait, 提供两个实现:
+//   * procfs: 直接解析 Linux 的 /proc 文件, 不依赖 heim
+//   * heim: 原来的实现, 跨平台, 只有开启 heim 特性时才编译(cargo build --features heim)
+// 默认后端: Linux 上是 proc; 其他平台开启了 heim 特性时是 heim
+// 通过环境变量 SYSTEM_INFO_BACKEND=proc|heim 覆盖默认值
+// 使用 proc 后端时, 还可以用 SYSTEM_INFO_PROC_ROOT 指定 procfs 目录(例如 fixtures/proc)
+use super::process::ProcessTimes;
+use super::throughput::{DiskCounters, FilesystemUsage, InterfaceCounters};
+use super::SystemInfoError;
+use super::{CpuTimeSample, LoadAverage, Memory, Swap};
+use std::future::Future;
+use std::sync::Once;
+
+#[cfg(feature = "heim")]
+pub mod heim_backend;
+pub mod procfs;
+
+#[cfg(feature = "heim")]
+pub use heim_backend::HeimBackend;
+pub use procfs::ProcBackend;
+
+// ? trait 中的 async 方法写成 `impl Future + Send`, 这样返回的 Future 可以交给 tokio::spawn
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    // 整体累计CPU时间 + 每个逻辑核心的累计CPU时间
+    fn cpu_times(
+        &self,
+    ) -> impl Future<Output = Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError>> + Send;
+
+    fn logical_count(&self) -> impl Future<Output = Result<u64, SystemInfoError>> + Send;
+
+    fn memory(&self) -> impl Future<Output = Result<Memory, SystemInfoError>> + Send;
+
+    fn swap(&self) -> impl Future<Output = Result<Swap, SystemInfoError>> + Send;
+
+    fn load_average(&self) -> impl Future<Output = Result<LoadAverage, SystemInfoError>> + Send;
+
+    // 开机时长, 单位秒
+    fn uptime(&self) -> impl Future<Output = Result<f64, SystemInfoError>> + Send;
+
+    // 所有进程的累计 CPU 时间和内存, 读取过程中退出或无权访问的进程直接跳过
+    fn processes(&self) -> impl Future<Output = Result<Vec<ProcessTimes>, SystemInfoError>> + Send;
+
+    // 每个块设备的累计读写次数和字节数
+    fn disk_counters(
+        &self,
+    ) -> impl Future<Output = Result<Vec<DiskCounters>, SystemInfoError>> + Send;
+
+    // 每个网卡的累计收发字节数和包数
+    fn network_counters(
+        &self,
+    ) -> impl Future<Output = Result<Vec<InterfaceCounters>, SystemInfoError>> + Send;
+
+    // 物理文件系统(不含 proc、tmpfs 等虚拟文件系统)的用量
+    fn filesystems(
+        &self,
+    ) -> impl Future<Output = Result<Vec<FilesystemUsage>, SystemInfoError>> + Send;
+}
+
+// 运行时可选的后端
+#[derive(Debug, Clone)]
+pub enum AnyBackend {
+    #[cfg(feature = "heim")]
+    Heim(HeimBackend),
+    Proc(ProcBackend),
+}
+
+impl AnyBackend {
+    // 按名字选择后端, 不认识或者没有编译进来的后端返回 None
+    pub fn from_name(name: &str) -> Option<AnyBackend> {
+        match name {
+            #[cfg(feature = "heim")]
+            "heim" => Some(AnyBackend::Heim(HeimBackend)),
+            "proc" | "procfs" => {
+                let root = std::env::var("SYSTEM_INFO_PROC_ROOT").unwrap_or("/proc".to_string());
+                Some(AnyBackend::Proc(ProcBackend::new(root)))
+            }
+            _ => None,
+        }
+    }
+
+    // 当前平台的默认后端
+    #[cfg(all(feature = "heim", not(target_os = "linux")))]
+    fn platform_default() -> AnyBackend {
+        AnyBackend::Heim(HeimBackend)
+    }
+
+    // ? 非 Linux 平台没有 /proc, 没有开启 heim 特性时 proc 后端的每次读取都会失败
+    #[cfg(not(all(feature = "heim", not(target_os = "linux"))))]
+    fn platform_default() -> AnyBackend {
+        AnyBackend::from_name("proc").unwrap()
+    }
+}
+
+// 读取 SYSTEM_INFO_BACKEND 环境变量, 没有设置时使用当前平台的默认后端
+// ! 设置了不认识的名字时同样使用默认后端, 并且只提示一次, 否则每次测量都会打印
+pub fn default_backend() -> AnyBackend {
+    static UNKNOWN: Once = Once::new();
+    let Ok(name) = std::env::var("SYSTEM_INFO_BACKEND") else {
+        return AnyBackend::platform_default();
+    };
+    AnyBackend::from_name(&name).unwrap_or_else(|| {
+        let backend = AnyBackend::platform_default();
+        UNKNOWN.call_once(|| {
+            eprintln!(
+                "SYSTEM_INFO_BACKEND={:?} 不可用, 改用 {} 后端",
+                name,
+                backend.name()
+            )
+        });
+        backend
+    })
+}
+
+// 把调用分发给具体的后端
+macro_rules! dispatch {
+    ($self:ident, $method:ident) => {
+        match $self {
+            #[cfg(feature = "heim")]
+            AnyBackend::Heim(backend) => backend.$method().await,
+            AnyBackend::Proc(backend) => backend.$method().await,
+        }
+    };
+}
+
+impl Backend for AnyBackend {
+    fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "heim")]
+            AnyBackend::Heim(backend) => backend.name(),
+            AnyBackend::Proc(backend) => backend.name(),
+        }
+    }
+
+    async fn cpu_times(&self) -> Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError> {
+        dispatch!(self, cpu_times)
+    }
+
+    async fn logical_count(&self) -> Result<u64, SystemInfoError> {
+        dispatch!(self, logical_count)
+    }
+
+    async fn memory(&self) -> Result<Memory, SystemInfoError> {
+        dispatch!(self, memory)
+    }
+
+    async fn swap(&self) -> Result<Swap, SystemInfoError> {
+        dispatch!(self, swap)
+    }
+
+    async fn load_average(&self) -> Result<LoadAverage, SystemInfoError> {
+        dispatch!(self, load_average)
+    }
+
+    async fn uptime(&self) -> Result<f64, SystemInfoError> {
+        dispatch!(self, uptime)
+    }
+
+    async fn processes(&self) -> Result<Vec<ProcessTimes>, SystemInfoError> {
+        dispatch!(self, processes)
+    }
+
+    async fn disk_counters(&self) -> Result<Vec<DiskCounters>, SystemInfoError> {
+        dispatch!(self, disk_counters)
+    }
+
+    async fn network_counters(&self) -> Result<Vec<InterfaceCounters>, SystemInfoError> {
+        dispatch!(self, network_counters)
+    }
+
+    async fn filesystems(&self) -> Result<Vec<FilesystemUsage>, SystemInfoError> {
+        dispatch!(self, filesystems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_names() {
+        assert_eq!(AnyBackend::from_name("proc").unwrap().name(), "proc");
+        assert_eq!(AnyBackend::from_name("procfs").unwrap().name(), "proc");
+        assert!(AnyBackend::from_name("sysctl").is_none());
+        // 没有开启 heim 特性时 heim 也是不可用的名字
+        assert_eq!(
+            AnyBackend::from_name("heim").is_some(),
+            cfg!(feature = "heim")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_defaults_to_proc() {
+        assert_eq!(AnyBackend::platform_default().name(), "proc");
+    }
+}
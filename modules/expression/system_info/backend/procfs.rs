@@ -0,0 +1,756 @@
+// /proc 后端, 直接解析 Linux 的 procfs 文件
+// 解析函数都是纯函数(输入文件内容, 输出读数), 可以直接用 fixtures/proc 下的样例文件验证
+// ProcBackend 的根目录也可以指向 fixtures/proc, 这样整个后端都能在任意机器上回放
+// 文件读取都放在阻塞线程池里(error::blocking), 这样 READ_TIMEOUT 对卡住的 /proc 也有效
+use super::Backend;
+use crate::modules::expression::system_info::error::{blocking, with_timeout};
+use crate::modules::expression::system_info::process::ProcessTimes;
+use crate::modules::expression::system_info::throughput::{
+    statvfs, DiskCounters, FilesystemUsage, InterfaceCounters,
+};
+use crate::modules::expression::system_info::{
+    CpuTimeSample, LoadAverage, Memory, Swap, SystemInfoError,
+};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// 单个挂载点的 statvfs 最多等待的时间
+// ? 本地文件系统在毫秒级返回, 超时的挂载点之后不再查询
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(1);
+
+// 网络文件系统, 服务器无响应时 statvfs 会一直阻塞
+// ? 它们在 /proc/filesystems 中通常带 nodev 标记, 已经不算物理文件系统, 这里再明确排除一次
+const REMOTE_FS: [&str; 10] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ceph",
+    "glusterfs",
+    "9p",
+    "afs",
+    "lustre",
+];
+
+#[derive(Debug, Clone)]
+pub struct ProcBackend {
+    // procfs 挂载点, 正常情况下是 /proc
+    pub root: PathBuf,
+    // /proc/stat 中的时间单位是 clock tick, 每秒的 tick 数
+    pub clock_ticks: f64,
+    // statvfs 超时过的挂载点; 阻塞的线程不会被取消, 再查询只会堆积更多卡住的线程
+    stalled: Arc<Mutex<Vec<String>>>,
+}
+
+impl Default for ProcBackend {
+    fn default() -> Self {
+        ProcBackend::new("/proc")
+    }
+}
+
+impl ProcBackend {
+    pub fn new(root: impl Into<PathBuf>) -> ProcBackend {
+        ProcBackend {
+            root: root.into(),
+            clock_ticks: clock_ticks(),
+            stalled: Arc::default(),
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<String, SystemInfoError> {
+        let path = self.root.join(name);
+        fs::read_to_string(&path).map_err(|err| {
+            // 非 Linux 系统上没有 procfs
+            if cfg!(not(target_os = "linux")) && err.kind() == std::io::ErrorKind::NotFound {
+                SystemInfoError::unsupported(format!("procfs({})", path.display()))
+            } else {
+                SystemInfoError::from_io(&path, err)
+            }
+        })
+    }
+
+    // 在阻塞线程池里读取一个文件
+    async fn read_blocking(&self, name: &str) -> Result<String, SystemInfoError> {
+        let backend = self.clone();
+        let name = name.to_string();
+        let resource = self.root.join(&name).display().to_string();
+        blocking(&resource, move || backend.read(&name)).await
+    }
+
+    // 遍历 /proc 下的数字目录, 读取过程中退出或无权访问的进程直接跳过
+    fn scan_processes(&self) -> Result<Vec<ProcessTimes>, SystemInfoError> {
+        let boot_time = parse_boot_time(&self.read("stat")?)?;
+        let entries =
+            fs::read_dir(&self.root).map_err(|err| SystemInfoError::from_io(&self.root, err))?;
+        Ok(entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter_map(|pid| self.read_process(pid, boot_time).ok())
+            .collect())
+    }
+
+    // 读取单个进程, /proc/<pid>/ 下的 stat、status 和 cmdline
+    // boot_time 是 /proc/stat 中的 btime, 用来把启动时间换算成和 heim 一样的 Unix 时间戳
+    fn read_process(&self, pid: u32, boot_time: f64) -> Result<ProcessTimes, SystemInfoError> {
+        let dir = pid.to_string();
+        let stat = self.read(&format!("{}/stat", dir))?;
+        let (name, cpu_time, since_boot) = parse_process_stat(&stat, self.clock_ticks)?;
+        let (uid, rss) = parse_process_status(&self.read(&format!("{}/status", dir))?)?;
+        // 命令行参数之间用 NUL 分隔
+        let cmdline = self
+            .read(&format!("{}/cmdline", dir))
+            .unwrap_or_default()
+            .split('\0')
+            .filter(|arg| !arg.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(ProcessTimes {
+            pid,
+            name,
+            cmdline,
+            uid,
+            start_time: boot_time + since_boot,
+            cpu_time,
+            rss,
+        })
+    }
+}
+
+// 每秒的 clock tick 数, 几乎所有 Linux 上都是 100
+fn clock_ticks() -> f64 {
+    // ? sysconf 是一个简单的 libc 调用, 没有需要维护的不变量
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+// 解析 /proc/stat 中的一行 "cpu0 317865 456 71065 3101075 8645 14938 10567 0 0 0"
+// 字段依次是 user nice system idle iowait irq softirq steal guest guest_nice
+// ! 老内核的字段可能不全, 缺少的字段按 0 处理; guest 已经计入 user, 这里不再读取
+fn parse_cpu_line(line: &str, clock_ticks: f64) -> Result<CpuTimeSample, SystemInfoError> {
+    let mut values = [0.0; 8];
+    for (slot, field) in values.iter_mut().zip(line.split_whitespace().skip(1)) {
+        let ticks = field
+            .parse::<u64>()
+            .map_err(|err| SystemInfoError::parse_with("/proc/stat", line, err))?;
+        *slot = ticks as f64 / clock_ticks;
+    }
+    let [user, nice, system, idle, iowait, irq, softirq, steal] = values;
+    Ok(CpuTimeSample {
+        user,
+        nice,
+        system,
+        idle,
+        iowait,
+        irq,
+        softirq,
+        steal,
+    })
+}
+
+// 解析 /proc/stat, 返回整体累计时间和每个核心的累计时间
+pub fn parse_stat(
+    text: &str,
+    clock_ticks: f64,
+) -> Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError> {
+    let mut total = None;
+    let mut cores = Vec::new();
+    for line in text.lines() {
+        let label = line.split_whitespace().next().unwrap_or("");
+        if label == "cpu" {
+            total = Some(parse_cpu_line(line, clock_ticks)?);
+        } else if label.starts_with("cpu") {
+            cores.push(parse_cpu_line(line, clock_ticks)?);
+        }
+    }
+    let total = total.ok_or_else(|| SystemInfoError::parse("/proc/stat", "没有 cpu 行"))?;
+    Ok((total, cores))
+}
+
+// 解析 /proc/stat 中的 btime 行, 即系统启动时的 Unix 时间戳(秒)
+pub fn parse_boot_time(text: &str) -> Result<f64, SystemInfoError> {
+    let line = text
+        .lines()
+        .find(|line| line.starts_with("btime "))
+        .ok_or_else(|| SystemInfoError::parse("/proc/stat", "没有 btime 行"))?;
+    let value = line.split_whitespace().nth(1).unwrap_or("");
+    value
+        .parse::<u64>()
+        .map(|btime| btime as f64)
+        .map_err(|err| SystemInfoError::parse_with("/proc/stat", line, err))
+}
+
+// 在 /proc/meminfo 中查找一个键, 值的单位是 kB, 返回字节
+fn meminfo_value(text: &str, key: &str) -> Result<u64, SystemInfoError> {
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix(key).and_then(|r| r.strip_prefix(':')) {
+            let kb = rest
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .parse::<u64>()
+                .map_err(|err| SystemInfoError::parse_with("/proc/meminfo", line, err))?;
+            return Ok(kb * 1024);
+        }
+    }
+    Err(SystemInfoError::parse(
+        "/proc/meminfo",
+        format!("没有 {}", key),
+    ))
+}
+
+// 解析 /proc/meminfo
+// ? MemAvailable 从 3.14 内核开始才有, 更老的内核退化为 MemFree + Buffers + Cached
+pub fn parse_meminfo(text: &str) -> Result<(Memory, Swap), SystemInfoError> {
+    let total = meminfo_value(text, "MemTotal")?;
+    let available = match meminfo_value(text, "MemAvailable") {
+        Ok(available) => available,
+        Err(_) => {
+            meminfo_value(text, "MemFree")?
+                + meminfo_value(text, "Buffers").unwrap_or(0)
+                + meminfo_value(text, "Cached").unwrap_or(0)
+        }
+    };
+    let swap_total = meminfo_value(text, "SwapTotal")?;
+    let swap_free = meminfo_value(text, "SwapFree")?;
+    Ok((
+        Memory {
+            total,
+            used: total.saturating_sub(available),
+            available,
+            // 容器的上限由上层补充
+            container: None,
+        },
+        Swap {
+            total: swap_total,
+            used: swap_total.saturating_sub(swap_free),
+            free: swap_free,
+        },
+    ))
+}
+
+// 解析 /proc/loadavg, 例如 "0.52 0.58 0.59 2/1024 12345"
+pub fn parse_loadavg(text: &str) -> Result<LoadAverage, SystemInfoError> {
+    let mut fields = text.split_whitespace();
+    let mut next = || -> Result<f32, SystemInfoError> {
+        let field = fields
+            .next()
+            .ok_or_else(|| SystemInfoError::parse("/proc/loadavg", "字段不足"))?;
+        field
+            .parse::<f32>()
+            .map_err(|err| SystemInfoError::parse_with("/proc/loadavg", text.trim(), err))
+    };
+    Ok(LoadAverage {
+        one: next()?,
+        five: next()?,
+        fifteen: next()?,
+    })
+}
+
+// 解析 /proc/uptime, 第一个字段是开机时长(秒), 第二个是所有核心累计的空闲时长
+pub fn parse_uptime(text: &str) -> Result<f64, SystemInfoError> {
+    let field = text
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| SystemInfoError::parse("/proc/uptime", "内容为空"))?;
+    field
+        .parse::<f64>()
+        .map_err(|err| SystemInfoError::parse_with("/proc/uptime", text.trim(), err))
+}
+
+// 解析 /proc/<pid>/stat, 例如 "1 (bash) S 0 1 1 0 -1 4194560 ... "
+// 返回 (进程名, 累计 user + system 时间(秒), 开机后多久启动(秒))
+// ! 进程名里可以有空格和括号, 所以从最后一个 ')' 之后再按空格切分
+pub fn parse_process_stat(
+    text: &str,
+    clock_ticks: f64,
+) -> Result<(String, f64, f64), SystemInfoError> {
+    let resource = "/proc/<pid>/stat";
+    let (open, close) = match (text.find('('), text.rfind(')')) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return Err(SystemInfoError::parse(resource, "没有进程名")),
+    };
+    let name = text[open + 1..close].to_string();
+    // 第 0 个字段是状态(原始的第 3 个字段), utime/stime/starttime 是原始的第 14/15/22 个字段
+    let fields: Vec<&str> = text[close + 1..].split_whitespace().collect();
+    let field = |index: usize| -> Result<u64, SystemInfoError> {
+        let value = fields
+            .get(index)
+            .ok_or_else(|| SystemInfoError::parse(resource, "字段不足"))?;
+        value
+            .parse::<u64>()
+            .map_err(|err| SystemInfoError::parse_with(resource, *value, err))
+    };
+    let cpu_time = (field(11)? + field(12)?) as f64 / clock_ticks;
+    Ok((name, cpu_time, field(19)? as f64 / clock_ticks))
+}
+
+// 解析 /proc/<pid>/status, 返回 (真实 uid, 常驻内存字节数)
+// ? 内核线程没有 VmRSS 这一行, 按 0 处理
+pub fn parse_process_status(text: &str) -> Result<(Option<u32>, u64), SystemInfoError> {
+    let resource = "/proc/<pid>/status";
+    let mut uid = None;
+    let mut rss = 0;
+    for line in text.lines() {
+        let value = |rest: &str| rest.split_whitespace().next().unwrap_or("").to_string();
+        if let Some(rest) = line.strip_prefix("Uid:") {
+            let value = value(rest);
+            let parsed = value
+                .parse::<u32>()
+                .map_err(|err| SystemInfoError::parse_with(resource, line, err))?;
+            uid = Some(parsed);
+        } else if let Some(rest) = line.strip_prefix("VmRSS:") {
+            let value = value(rest);
+            let kb = value
+                .parse::<u64>()
+                .map_err(|err| SystemInfoError::parse_with(resource, line, err))?;
+            rss = kb * 1024;
+        }
+    }
+    Ok((uid, rss))
+}
+
+// /proc/diskstats 中的扇区固定是 512 字节, 和设备真实的扇区大小无关
+const SECTOR_SIZE: u64 = 512;
+
+// 解析 /proc/diskstats, 每行 "major minor 设备名 读完成 读合并 读扇区 读耗时 写完成 写合并 写扇区 ..."
+// ? 从来没有读写过的设备(大量空闲的 loop、ram 设备)直接跳过
+pub fn parse_diskstats(text: &str) -> Result<Vec<DiskCounters>, SystemInfoError> {
+    let mut disks = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            return Err(SystemInfoError::parse("/proc/diskstats", line));
+        }
+        let field = |index: usize| {
+            fields[index]
+                .parse::<u64>()
+                .map_err(|err| SystemInfoError::parse_with("/proc/diskstats", line, err))
+        };
+        let disk = DiskCounters {
+            device: fields[2].to_string(),
+            reads: field(3)?,
+            writes: field(7)?,
+            read_bytes: field(5)? * SECTOR_SIZE,
+            write_bytes: field(9)? * SECTOR_SIZE,
+        };
+        if disk.reads > 0 || disk.writes > 0 {
+            disks.push(disk);
+        }
+    }
+    Ok(disks)
+}
+
+// 解析 /proc/net/dev, 前两行是表头, 之后每行 "网卡: 接收的 8 列 发送的 8 列"
+// 接收和发送的前两列都是字节数和包数
+pub fn parse_net_dev(text: &str) -> Result<Vec<InterfaceCounters>, SystemInfoError> {
+    text.lines()
+        .skip(2)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (interface, counters) = line
+                .split_once(':')
+                .ok_or_else(|| SystemInfoError::parse("/proc/net/dev", line))?;
+            let values = counters
+                .split_whitespace()
+                .map(|value| value.parse::<u64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| SystemInfoError::parse_with("/proc/net/dev", line, err))?;
+            if values.len() < 10 {
+                return Err(SystemInfoError::parse("/proc/net/dev", line));
+            }
+            Ok(InterfaceCounters {
+                interface: interface.trim().to_string(),
+                rx_bytes: values[0],
+                rx_packets: values[1],
+                tx_bytes: values[8],
+                tx_packets: values[9],
+            })
+        })
+        .collect()
+}
+
+// 解析 /proc/filesystems, 返回物理文件系统类型(没有 nodev 标记的那些)
+pub fn parse_filesystems(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|line| !line.starts_with("nodev"))
+        .map(|line| line.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// 解析 /proc/self/mounts, 返回物理文件系统的 (设备, 挂载点, 类型)
+// 网络文件系统和 fuse.sshfs 这样的 FUSE 文件系统即使在 physical 中也跳过
+// ! 挂载点中的空格、制表符等会被转义成 "\040" 这样的八进制, 需要还原
+// ? 同一个设备可以挂载多次(bind mount), 只保留第一次出现的挂载点
+pub fn parse_mounts(text: &str, physical: &[String]) -> Vec<(String, String, String)> {
+    let mut devices = Vec::new();
+    let mut mounts = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || !physical.iter().any(|fs| fs == fields[2]) || is_remote(fields[2]) {
+            continue;
+        }
+        if devices.contains(&fields[0]) {
+            continue;
+        }
+        devices.push(fields[0]);
+        mounts.push((
+            fields[0].to_string(),
+            unescape_mount(fields[1]),
+            fields[2].to_string(),
+        ));
+    }
+    mounts
+}
+
+fn is_remote(fs_type: &str) -> bool {
+    REMOTE_FS.contains(&fs_type) || fs_type.starts_with("fuse.")
+}
+
+fn unescape_mount(path: &str) -> String {
+    let mut out = String::new();
+    let mut rest = path;
+    while let Some(index) = rest.find('\\') {
+        out.push_str(&rest[..index]);
+        let escaped = rest.get(index + 1..index + 4);
+        match escaped.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+impl Backend for ProcBackend {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+
+    async fn cpu_times(&self) -> Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError> {
+        parse_stat(&self.read_blocking("stat").await?, self.clock_ticks)
+    }
+
+    // 在线的逻辑核心数, 即 /proc/stat 中 cpuN 行的数量
+    async fn logical_count(&self) -> Result<u64, SystemInfoError> {
+        let (_, cores) = parse_stat(&self.read_blocking("stat").await?, self.clock_ticks)?;
+        Ok(cores.len() as u64)
+    }
+
+    async fn memory(&self) -> Result<Memory, SystemInfoError> {
+        Ok(parse_meminfo(&self.read_blocking("meminfo").await?)?.0)
+    }
+
+    async fn swap(&self) -> Result<Swap, SystemInfoError> {
+        Ok(parse_meminfo(&self.read_blocking("meminfo").await?)?.1)
+    }
+
+    async fn load_average(&self) -> Result<LoadAverage, SystemInfoError> {
+        parse_loadavg(&self.read_blocking("loadavg").await?)
+    }
+
+    async fn uptime(&self) -> Result<f64, SystemInfoError> {
+        parse_uptime(&self.read_blocking("uptime").await?)
+    }
+
+    async fn processes(&self) -> Result<Vec<ProcessTimes>, SystemInfoError> {
+        let backend = self.clone();
+        let resource = self.root.display().to_string();
+        blocking(&resource, move || backend.scan_processes()).await
+    }
+
+    async fn disk_counters(&self) -> Result<Vec<DiskCounters>, SystemInfoError> {
+        parse_diskstats(&self.read_blocking("diskstats").await?)
+    }
+
+    async fn network_counters(&self) -> Result<Vec<InterfaceCounters>, SystemInfoError> {
+        parse_net_dev(&self.read_blocking("net/dev").await?)
+    }
+
+    // 挂载表来自 procfs, 用量用 statvfs 读取
+    // ? 挂载点无法访问(例如权限不足)时跳过这一个, 不影响其余的文件系统
+    // ! statvfs 同样是阻塞调用, 每个挂载点单独放到阻塞线程里并限时, 一个卡住的挂载点只影响它自己
+    async fn filesystems(&self) -> Result<Vec<FilesystemUsage>, SystemInfoError> {
+        let physical = parse_filesystems(&self.read_blocking("filesystems").await?);
+        let mounts = parse_mounts(&self.read_blocking("self/mounts").await?, &physical);
+        let stalled = self.stalled.lock().unwrap().clone();
+        let usages = mounts
+            .into_iter()
+            .filter(|(_, mount_point, _)| !stalled.contains(mount_point))
+            .map(|(device, mount_point, fs_type)| async move {
+                let path = mount_point.clone();
+                let read = blocking(&mount_point, move || statvfs(&path));
+                match with_timeout("statvfs", STATVFS_TIMEOUT, read).await {
+                    Ok((total, used, free)) => Some(FilesystemUsage {
+                        device,
+                        mount_point,
+                        fs_type,
+                        total,
+                        used,
+                        free,
+                    }),
+                    Err(SystemInfoError::Timeout { .. }) => {
+                        self.stalled.lock().unwrap().push(mount_point);
+                        None
+                    }
+                    Err(_) => None,
+                }
+            });
+        Ok(futures::future::join_all(usages)
+            .await
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/proc")
+            .join(name);
+        fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    }
+
+    fn is_parse_error<T: std::fmt::Debug>(result: Result<T, SystemInfoError>) -> bool {
+        matches!(result, Err(SystemInfoError::Parse { .. }))
+    }
+
+    #[test]
+    fn stat_fixture() {
+        let (total, cores) = parse_stat(&fixture("stat"), 100.0).unwrap();
+        assert_eq!(total.user, 4705.0 / 100.0);
+        assert_eq!(total.nice, 356.0 / 100.0);
+        assert_eq!(total.system, 584.0 / 100.0);
+        assert_eq!(total.idle, 3699176.0 / 100.0);
+        assert_eq!(total.iowait, 23060.0 / 100.0);
+        assert_eq!(total.softirq, 277.0 / 100.0);
+        assert_eq!(total.steal, 0.0);
+        assert_eq!(cores.len(), 4);
+        assert_eq!(cores[0].user, 1393.0 / 100.0);
+        assert_eq!(cores[3].idle, 924463.0 / 100.0);
+        assert_eq!(parse_boot_time(&fixture("stat")).unwrap(), 1792360000.0);
+        assert!(is_parse_error(parse_boot_time("cpu  1 2 3 4")));
+        assert!(is_parse_error(parse_boot_time("btime soon")));
+    }
+
+    // 老内核只有前 4 个字段, 缺少的按 0 处理
+    #[test]
+    fn stat_short_line() {
+        let (total, cores) = parse_stat("cpu 10 0 20 300\n", 100.0).unwrap();
+        assert_eq!((total.user, total.system, total.idle), (0.1, 0.2, 3.0));
+        assert_eq!(total.iowait, 0.0);
+        assert!(cores.is_empty());
+    }
+
+    #[test]
+    fn stat_malformed() {
+        assert!(is_parse_error(parse_stat("cpu 10 x 20 300\n", 100.0)));
+        assert!(is_parse_error(parse_stat(
+            "cpu 10 0 20 300\ncpu0 1 -2\n",
+            100.0
+        )));
+        // 截断到只剩核心的行, 没有整体的 cpu 行
+        assert!(is_parse_error(parse_stat("cpu0 1 2 3 4\n", 100.0)));
+        assert!(is_parse_error(parse_stat("", 100.0)));
+    }
+
+    #[test]
+    fn meminfo_fixture() {
+        let (memory, swap) = parse_meminfo(&fixture("meminfo")).unwrap();
+        assert_eq!(memory.total, 8052212 * 1024);
+        assert_eq!(memory.available, 4721036 * 1024);
+        assert_eq!(memory.used, (8052212 - 4721036) * 1024);
+        assert!(memory.container.is_none());
+        assert_eq!(swap.total, 2097148 * 1024);
+        assert_eq!(swap.free, 1834996 * 1024);
+        assert_eq!(swap.used, (2097148 - 1834996) * 1024);
+    }
+
+    // 没有 MemAvailable 时用 MemFree + Buffers + Cached
+    #[test]
+    fn meminfo_without_available() {
+        let text: String = fixture("meminfo")
+            .lines()
+            .filter(|line| !line.starts_with("MemAvailable"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let (memory, _) = parse_meminfo(&text).unwrap();
+        assert_eq!(memory.available, (532148 + 326876 + 3872212) * 1024);
+    }
+
+    #[test]
+    fn meminfo_malformed() {
+        let text = fixture("meminfo");
+        // 截断在 SwapTotal 之前
+        let truncated = &text[..text.find("SwapTotal").unwrap()];
+        assert!(is_parse_error(parse_meminfo(truncated)));
+        assert!(is_parse_error(parse_meminfo(
+            &text.replace("MemTotal:        8052212 kB", "MemTotal: lots kB")
+        )));
+        assert!(is_parse_error(parse_meminfo("")));
+    }
+
+    #[test]
+    fn loadavg_fixture() {
+        let load = parse_loadavg(&fixture("loadavg")).unwrap();
+        assert_eq!((load.one, load.five, load.fifteen), (0.52, 0.58, 0.59));
+    }
+
+    #[test]
+    fn loadavg_malformed() {
+        assert!(is_parse_error(parse_loadavg("0.52 0.58")));
+        assert!(is_parse_error(parse_loadavg("0.52 high 0.59 2/1024 12345")));
+        assert!(is_parse_error(parse_loadavg("")));
+    }
+
+    #[test]
+    fn uptime_fixture() {
+        assert_eq!(parse_uptime(&fixture("uptime")).unwrap(), 35842.66);
+    }
+
+    #[test]
+    fn uptime_malformed() {
+        assert!(is_parse_error(parse_uptime("")));
+        assert!(is_parse_error(parse_uptime("  \n")));
+        assert!(is_parse_error(parse_uptime("35842,66 140123.05")));
+    }
+
+    #[test]
+    fn process_fixtures() {
+        let (name, cpu_time, start) = parse_process_stat(&fixture("4242/stat"), 100.0).unwrap();
+        assert_eq!(name, "python3 (worker)");
+        assert_eq!(cpu_time, (183422 + 5121) as f64 / 100.0);
+        assert_eq!(start, 35128.4);
+        assert_eq!(
+            parse_process_status(&fixture("4242/status")).unwrap(),
+            (Some(1000), 393216 * 1024)
+        );
+        // 内核线程没有 VmRSS
+        assert_eq!(
+            parse_process_status(&fixture("17/status")).unwrap(),
+            (Some(0), 0)
+        );
+        assert!(is_parse_error(parse_process_stat(
+            "4242 python3 R 1",
+            100.0
+        )));
+        assert!(is_parse_error(parse_process_stat(
+            "4242 (python3) R 1 2",
+            100.0
+        )));
+    }
+
+    #[test]
+    fn diskstats_and_net_dev_fixtures() {
+        let disks = parse_diskstats(&fixture("diskstats")).unwrap();
+        let devices: Vec<&str> = disks.iter().map(|disk| disk.device.as_str()).collect();
+        assert_eq!(devices, ["nvme0n1", "nvme0n1p1", "nvme0n1p2"]);
+        assert_eq!(disks[0].read_bytes, 10563210 * SECTOR_SIZE);
+        assert!(is_parse_error(parse_diskstats("259 0 nvme0n1 1 2 3\n")));
+
+        let interfaces = parse_net_dev(&fixture("net/dev")).unwrap();
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[1].interface, "eth0");
+        assert_eq!(
+            (interfaces[1].rx_bytes, interfaces[1].tx_bytes),
+            (9552168, 85663)
+        );
+    }
+
+    #[test]
+    fn mounts_fixture() {
+        let physical = parse_filesystems(&fixture("filesystems"));
+        assert_eq!(physical, ["ext4", "vfat"]);
+        let mounts = parse_mounts(&fixture("self/mounts"), &physical);
+        let points: Vec<&str> = mounts.iter().map(|(_, point, _)| point.as_str()).collect();
+        // bind mount 的 /var/lib/docker 只保留第一次出现, 转义的空格被还原
+        assert_eq!(points, ["/", "/boot/efi", "/mnt/backup disk"]);
+    }
+
+    // 网络和 FUSE 文件系统即使被当作物理文件系统也不查询
+    #[test]
+    fn remote_mounts_skipped() {
+        let physical = ["ext4", "nfs4", "cifs", "fuse.sshfs"].map(String::from);
+        let mounts = parse_mounts(
+            "/dev/sda1 / ext4 rw 0 0\n\
+             server:/export /mnt/nfs nfs4 rw 0 0\n\
+             //server/share /mnt/smb cifs rw 0 0\n\
+             user@host:/ /mnt/ssh fuse.sshfs rw 0 0\n",
+            &physical,
+        );
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].1, "/");
+    }
+
+    // 超时过的挂载点不再调用 statvfs
+    #[tokio::test]
+    async fn stalled_mount_skipped() {
+        let backend =
+            ProcBackend::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/proc"));
+        let points = |filesystems: Vec<FilesystemUsage>| {
+            filesystems
+                .into_iter()
+                .map(|filesystem| filesystem.mount_point)
+                .collect::<Vec<_>>()
+        };
+        assert!(points(backend.filesystems().await.unwrap()).contains(&"/".to_string()));
+        backend.stalled.lock().unwrap().push("/".to_string());
+        assert!(!points(backend.filesystems().await.unwrap()).contains(&"/".to_string()));
+    }
+
+    // 整个后端指向 fixtures/proc 回放
+    #[tokio::test]
+    async fn backend_over_fixtures() {
+        let backend =
+            ProcBackend::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/proc"));
+        assert_eq!(backend.logical_count().await.unwrap(), 4);
+        assert_eq!(backend.uptime().await.unwrap(), 35842.66);
+        let processes = backend.processes().await.unwrap();
+        let mut pids: Vec<u32> = processes.iter().map(|process| process.pid).collect();
+        pids.sort_unstable();
+        assert_eq!(pids, [1, 17, 4242]);
+        // 启动时间换算成 Unix 时间戳: btime + starttime / clock tick
+        let worker = processes
+            .iter()
+            .find(|process| process.pid == 4242)
+            .unwrap();
+        assert_eq!(
+            worker.start_time,
+            1792360000.0 + 3512840.0 / backend.clock_ticks
+        );
+    }
+
+    // uptime 是一个没有写端的 FIFO, 打开时会一直阻塞, 读取应该在超时后返回
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn blocked_read_times_out() {
+        let root = std::env::temp_dir().join(format!("procfs-fifo-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let fifo = root.join("uptime");
+        let path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        let backend = ProcBackend::new(root.clone());
+        let result =
+            with_timeout("读取开机时长", Duration::from_millis(100), backend.uptime()).await;
+        assert!(matches!(result, Err(SystemInfoError::Timeout { .. })));
+        // 打开写端, 让阻塞线程里的读取结束, 否则测试的运行时退出时会一直等它
+        fs::write(&fifo, "1.0 2.0\n").unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+// 后台采样
+// 在 tokio 任务里通过 LiveProvider 持续消费 sampler::cpu_stream, 把最新快照、历史和状态放在共享状态里
+// 指标接口、告警等只读共享状态, 不需要各自再做一次 1 秒的阻塞测量
+use super::history::{CpuHistory, CpuSample, WINDOW_1M};
+use super::provider::{LiveProvider, SystemInfoProvider};
+use super::status::{StatusClassifier, StatusConfig};
+use super::{SystemInfoError, SystemSnapshot};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+pub struct SampledMetrics {
+    // 最新一次完整快照, 第一个采样到来之前以及最近一次采样失败时为 None
+    pub snapshot: Option<SystemSnapshot>,
+    // 最近一次采样失败的原因, 采样恢复后清空
+    pub last_error: Option<String>,
+    // 最新快照的毫秒级时间戳
+    pub timestamp: u64,
+    pub history: CpuHistory,
+    pub classifier: StatusClassifier,
+}
+
+impl SampledMetrics {
+    pub fn new(capacity: usize, config: StatusConfig) -> SampledMetrics {
+        SampledMetrics {
+            snapshot: None,
+            last_error: None,
+            timestamp: 0,
+            history: CpuHistory::new(capacity, 0.3),
+            classifier: StatusClassifier::new(config),
+        }
+    }
+
+    // 记录一次快照: 写入历史, 并用平滑后的值更新状态
+    pub fn record(&mut self, snapshot: SystemSnapshot, timestamp: u64) {
+        self.history.push_sample(CpuSample {
+            timestamp,
+            cpu: snapshot.cpu.clone(),
+        });
+        let usage = snapshot
+            .cpu
+            .per_core(self.history.ewma().unwrap_or(snapshot.cpu.usage));
+        let max_core_usage = self
+            .history
+            .max_core_stats(WINDOW_1M)
+            .map_or(snapshot.cpu.max_core_usage(), |stats| stats.mean);
+        self.classifier.observe(usage, max_core_usage, timestamp);
+        self.snapshot = Some(snapshot);
+        self.last_error = None;
+        self.timestamp = timestamp;
+    }
+
+    // 记录一次失败的采样: 丢弃旧快照, 状态变为 Unknown
+    // ! 不能保留旧快照, 否则指标接口会一直输出失败之前的读数
+    // ? 这里只记录原因, 要不要输出、输出到哪里由调用方决定(monitor 会把它画在面板上)
+    pub fn record_error(&mut self, err: &SystemInfoError, timestamp: u64) {
+        self.classifier.mark_unknown(timestamp);
+        self.snapshot = None;
+        self.last_error = Some(err.to_string());
+        self.timestamp = timestamp;
+    }
+
+    // 按采样结果选择 record 或 record_error
+    pub fn update(&mut self, sample: Result<SystemSnapshot, SystemInfoError>, timestamp: u64) {
+        match sample {
+            Ok(snapshot) => self.record(snapshot, timestamp),
+            Err(err) => self.record_error(&err, timestamp),
+        }
+    }
+}
+
+// ? 锁只在读写内存数据时短暂持有, 不会跨 await, 所以用标准库的 Mutex 就够了
+pub type SharedMetrics = Arc<Mutex<SampledMetrics>>;
+
+// 持续采样, 每次采样(成功或失败)都回调一次 on_sample
+// ? 后台任务和需要自己持有状态的调用方(例如告警、monitor 的重绘)共用这一个循环
+pub async fn run_sampler<F>(interval: Duration, on_sample: F)
+where
+    F: FnMut(Result<SystemSnapshot, SystemInfoError>, u64),
+{
+    run_sampler_with(&mut LiveProvider::default(), interval, on_sample).await
+}
+
+// 使用指定 provider 的采样循环, provider 没有更多采样时返回
+// ? 换成 ScriptedProvider 时整个循环不会等待, 时间戳也来自脚本的时钟
+pub async fn run_sampler_with<P, F>(provider: &mut P, interval: Duration, mut on_sample: F)
+where
+    P: SystemInfoProvider,
+    F: FnMut(Result<SystemSnapshot, SystemInfoError>, u64),
+{
+    let time = interval.as_millis() as u64;
+    while !provider.finished() {
+        let sample = provider.snapshot(time).await;
+        on_sample(sample, provider.now_millis());
+    }
+}
+
+// 启动后台采样任务, abort 返回的 JoinHandle 即可停止
+pub fn spawn_sampler(interval: Duration, metrics: SharedMetrics) -> JoinHandle<()> {
+    tokio::spawn(run_sampler(interval, move |sample, timestamp| {
+        metrics.lock().unwrap().update(sample, timestamp)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::provider::{usage_sample, ScriptedProvider};
+    use crate::modules::expression::system_info::status::CpuStatus;
+
+    // 8 核机器上 usage = 120 是平均每个核心 15%, 应该是 Normal 而不是 Saturated
+    #[test]
+    fn record_classifies_per_core_usage() {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        let mut snapshot = usage_sample(120.0);
+        snapshot.cpu.count = 8;
+        metrics.record(snapshot, 1_000);
+        assert_eq!(metrics.classifier.status(), CpuStatus::Normal);
+    }
+
+    #[test]
+    fn record_single_core_is_unchanged() {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        metrics.record(usage_sample(95.0), 1_000);
+        assert_eq!(metrics.classifier.status(), CpuStatus::Saturated);
+    }
+
+    // 连续 3 个 95% 的采样之后进入 Saturated
+    #[tokio::test]
+    async fn saturated_after_three_samples() {
+        let mut provider = ScriptedProvider::from_usages(&[95.0, 95.0, 95.0]);
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        run_sampler_with(
+            &mut provider,
+            Duration::from_secs(1),
+            |sample, timestamp| metrics.update(sample, timestamp),
+        )
+        .await;
+        assert_eq!(metrics.classifier.status(), CpuStatus::Saturated);
+        assert_eq!(metrics.history.len(), 3);
+        assert_eq!(metrics.timestamp, 3_000);
+    }
+}
@@ -0,0 +1,405 @@
+// 容器限制
+// 容器里 /proc/stat 和 cpu::logical_count() 看到的都是宿主机, 64 核的宿主机上只分到 2 核的容器
+// 看起来永远是 "Idle", 内存也是宿主机的总量
+// 这里读取 cgroup 的 CPU 配额和内存上限, 与宿主机的原始读数一起输出:
+//   * v2: cpu.max ("max 100000" 或 "150000 100000"), cpu.stat 的 usage_usec, memory.max, memory.current
+//   * v1: cpu.cfs_quota_us / cpu.cfs_period_us, cpuacct.usage(纳秒), memory.limit_in_bytes, memory.usage_in_bytes
+// 默认根据 /proc/self/cgroup 在 /sys/fs/cgroup 下定位当前进程的 cgroup
+// 设置 SYSTEM_INFO_CGROUP_ROOT 时直接把它当成当前 cgroup 的目录(例如 fixtures/cgroup/v2)
+use super::error::{blocking, SystemInfoError};
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ? v1 的 "无限制" 是一个接近 i64::MAX 且按页对齐的数, 超过这个值都当作没有限制
+const V1_UNLIMITED: u64 = 1 << 62;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+// 当前进程所在的 cgroup
+// ! 只有设置了配额/上限时才会出现在 Cpu.container 和 Memory.container 中, 没有限制时宿主机的读数就是准确的
+#[derive(Debug, Clone)]
+pub struct Cgroup {
+    pub version: CgroupVersion,
+    // v2 中三者是同一个目录
+    cpu_dir: PathBuf,
+    cpuacct_dir: PathBuf,
+    memory_dir: PathBuf,
+}
+
+// 一次 CPU 读数, 两次之差就是容器的 CPU 使用率
+#[derive(Debug, Clone, Copy)]
+pub struct CgroupCpuSample {
+    // 累计使用的 CPU 时间, 单位秒
+    pub usage: f64,
+    // 配额, 单位是 CPU 个数, 例如 1.5; 没有限制时为 None
+    pub quota: Option<f64>,
+}
+
+// 容器视角的 CPU
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerCpu {
+    pub version: Option<CgroupVersion>,
+    // 配额(CPU 个数), 没有限制时为 None
+    pub quota: Option<f64>,
+    // 实际可用的 CPU 个数 = min(配额, 宿主机逻辑核心数)
+    pub effective_cpus: f64,
+    // 容器自己的使用率, 和 Cpu.usage 口径一致: 占满一个核心为 100
+    pub usage: f32,
+    // 相对配额的使用率(0 ~ 100), 用满配额就是 100
+    pub quota_usage: f32,
+}
+
+// 容器视角的内存, 单位字节
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerMemory {
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl ContainerMemory {
+    // 相对上限的使用百分比
+    pub fn used_percent(&self) -> f32 {
+        if self.limit == 0 {
+            0.0
+        } else {
+            (self.used as f64 / self.limit as f64 * 100.0) as f32
+        }
+    }
+}
+
+impl Cgroup {
+    // 定位当前进程的 cgroup, 不在任何 cgroup 文件系统中时返回 None
+    pub fn detect() -> Option<Cgroup> {
+        if let Ok(root) = std::env::var("SYSTEM_INFO_CGROUP_ROOT") {
+            return Cgroup::at(Path::new(&root), "");
+        }
+        let self_cgroup = fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+        Cgroup::at(Path::new("/sys/fs/cgroup"), &self_cgroup)
+    }
+
+    // 在阻塞线程池里定位 cgroup 并读取 CPU 采样, 只有设置了配额时才返回
+    // ? detect 和读取都是同步的文件操作, 直接在 async fn 里调用会占住执行器线程
+    pub async fn cpu_sample_blocking(
+    ) -> Result<Option<(CgroupVersion, CgroupCpuSample)>, SystemInfoError> {
+        blocking("cgroup", || {
+            Ok(Cgroup::detect().and_then(|cgroup| {
+                let sample = cgroup.cpu_sample()?;
+                sample.quota.map(|_| (cgroup.version, sample))
+            }))
+        })
+        .await
+    }
+
+    // 同上, 读取容器的内存上限
+    pub async fn memory_blocking() -> Result<Option<ContainerMemory>, SystemInfoError> {
+        blocking("cgroup", || {
+            Ok(Cgroup::detect().and_then(|cgroup| cgroup.memory()))
+        })
+        .await
+    }
+
+    // root 是 cgroup 文件系统的挂载点, self_cgroup 是 /proc/self/cgroup 的内容
+    // ? 容器有自己的 cgroup 命名空间时, /proc/self/cgroup 中的路径可能在挂载点下不存在, 这时退回挂载点本身
+    pub fn at(root: &Path, self_cgroup: &str) -> Option<Cgroup> {
+        if !root.exists() {
+            return None;
+        }
+        let entries = parse_self_cgroup(self_cgroup);
+        let path_of = |controller: &str| {
+            entries
+                .iter()
+                .find(|(controllers, _)| controllers.iter().any(|c| c == controller))
+                .map_or("/", |(_, path)| path.as_str())
+        };
+        // 根目录下有 cgroup.controllers 说明是纯 v2 (混合模式下它在 unified 子目录里)
+        if root.join("cgroup.controllers").exists() {
+            let dir = existing(
+                &[root.join(relative(path_of(""))), root.to_path_buf()],
+                "cpu.stat",
+            )?;
+            return Some(Cgroup {
+                version: CgroupVersion::V2,
+                cpu_dir: dir.clone(),
+                cpuacct_dir: dir.clone(),
+                memory_dir: dir,
+            });
+        }
+        // v1 每个控制器单独挂载, cpu 和 cpuacct 经常挂在同一个目录 "cpu,cpuacct"
+        // 某个控制器没有挂载时仍然返回一个目录, 之后读取这个控制器的文件会得到 None
+        let controller_dir = |controller: &str, mounts: &[&str], file: &str| {
+            let candidates: Vec<PathBuf> = mounts
+                .iter()
+                .flat_map(|mount| {
+                    let base = root.join(mount);
+                    [base.join(relative(path_of(controller))), base]
+                })
+                .collect();
+            existing(&candidates, file).unwrap_or_else(|| root.join(mounts[0]))
+        };
+        let cpu_mounts = ["cpu", "cpu,cpuacct", "cpuacct,cpu"];
+        let cpuacct_mounts = ["cpuacct", "cpu,cpuacct", "cpuacct,cpu"];
+        Some(Cgroup {
+            version: CgroupVersion::V1,
+            cpu_dir: controller_dir("cpu", &cpu_mounts, "cpu.cfs_quota_us"),
+            cpuacct_dir: controller_dir("cpuacct", &cpuacct_mounts, "cpuacct.usage"),
+            memory_dir: controller_dir("memory", &["memory"], "memory.limit_in_bytes"),
+        })
+    }
+
+    fn read(dir: &Path, name: &str) -> Option<String> {
+        fs::read_to_string(dir.join(name)).ok()
+    }
+
+    // CPU 配额(CPU 个数), 没有限制时为 None
+    pub fn cpu_quota(&self) -> Option<f64> {
+        match self.version {
+            CgroupVersion::V2 => parse_cpu_max(&Cgroup::read(&self.cpu_dir, "cpu.max")?),
+            CgroupVersion::V1 => parse_cfs_quota(
+                &Cgroup::read(&self.cpu_dir, "cpu.cfs_quota_us")?,
+                &Cgroup::read(&self.cpu_dir, "cpu.cfs_period_us")?,
+            ),
+        }
+    }
+
+    // 累计 CPU 时间(秒)和配额
+    pub fn cpu_sample(&self) -> Option<CgroupCpuSample> {
+        let usage = match self.version {
+            CgroupVersion::V2 => {
+                parse_cpu_stat_usage(&Cgroup::read(&self.cpuacct_dir, "cpu.stat")?)?
+            }
+            CgroupVersion::V1 => {
+                let nanos = Cgroup::read(&self.cpuacct_dir, "cpuacct.usage")?;
+                nanos.trim().parse::<u64>().ok()? as f64 / 1e9
+            }
+        };
+        Some(CgroupCpuSample {
+            usage,
+            quota: self.cpu_quota(),
+        })
+    }
+
+    // 内存上限和当前用量, 没有上限时为 None
+    pub fn memory(&self) -> Option<ContainerMemory> {
+        let (limit, used) = match self.version {
+            CgroupVersion::V2 => ("memory.max", "memory.current"),
+            CgroupVersion::V1 => ("memory.limit_in_bytes", "memory.usage_in_bytes"),
+        };
+        let limit = parse_memory_limit(&Cgroup::read(&self.memory_dir, limit)?)?;
+        let used = Cgroup::read(&self.memory_dir, used)?.trim().parse().ok()?;
+        Some(ContainerMemory { limit, used })
+    }
+}
+
+// "/docker/abc" -> "docker/abc", 用于拼接在挂载点后面
+fn relative(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+// 第一个包含 file 的目录
+fn existing(candidates: &[PathBuf], file: &str) -> Option<PathBuf> {
+    candidates
+        .iter()
+        .find(|dir| dir.join(file).exists())
+        .cloned()
+}
+
+// 解析 /proc/self/cgroup, 每行 "编号:控制器列表:路径"
+// v2 的那一行控制器列表为空, 返回 ([""], 路径)
+pub fn parse_self_cgroup(text: &str) -> Vec<(Vec<String>, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let _id = fields.next()?;
+            let controllers = fields.next()?.split(',').map(str::to_string).collect();
+            Some((controllers, fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+// v2 cpu.max: "max 100000" 表示不限制, "150000 100000" 表示每 100ms 最多用 150ms, 即 1.5 个 CPU
+pub fn parse_cpu_max(text: &str) -> Option<f64> {
+    let mut fields = text.split_whitespace();
+    let quota = fields.next()?;
+    let period: f64 = fields.next().map_or(Some(100_000.0), |p| p.parse().ok())?;
+    if quota == "max" || period <= 0.0 {
+        return None;
+    }
+    Some(quota.parse::<f64>().ok()? / period)
+}
+
+// v1: cfs_quota_us 为 -1 表示不限制
+pub fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: i64 = period.trim().parse().ok()?;
+    if quota <= 0 || period <= 0 {
+        return None;
+    }
+    Some(quota as f64 / period as f64)
+}
+
+// v2 memory.max 为 "max" 表示不限制; v1 用一个很大的数表示不限制
+pub fn parse_memory_limit(text: &str) -> Option<u64> {
+    let limit: u64 = text.trim().parse().ok()?;
+    (limit < V1_UNLIMITED).then_some(limit)
+}
+
+// v2 cpu.stat 中的 "usage_usec 374751690", 返回秒
+pub fn parse_cpu_stat_usage(text: &str) -> Option<f64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|usec| usec as f64 / 1e6)
+}
+
+// 由两次读数计算容器视角的 CPU
+// ? 没有配额时 effective_cpus 就是宿主机的核心数, 使用率仍然只统计容器自己的进程
+pub fn container_cpu(
+    version: CgroupVersion,
+    earlier: &CgroupCpuSample,
+    later: &CgroupCpuSample,
+    elapsed: f64,
+    host_count: u64,
+) -> ContainerCpu {
+    let usage = if elapsed > 0.0 {
+        ((later.usage - earlier.usage).max(0.0) / elapsed * 100.0) as f32
+    } else {
+        0.0
+    };
+    let effective_cpus = later
+        .quota
+        .map_or(host_count as f64, |quota| quota.min(host_count as f64));
+    let quota_usage = if effective_cpus > 0.0 {
+        usage / effective_cpus as f32
+    } else {
+        0.0
+    };
+    ContainerCpu {
+        version: Some(version),
+        quota: later.quota,
+        effective_cpus,
+        usage,
+        quota_usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(usage: f64, quota: Option<f64>) -> CgroupCpuSample {
+        CgroupCpuSample { usage, quota }
+    }
+
+    // 把 fixtures/cgroup/v2 复制到临时目录, 改写其中的文件
+    fn v2_with(name: &str, overrides: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cgroup-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for entry in fs::read_dir("fixtures/cgroup/v2").unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+        for (file, content) in overrides {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn v2_fixture() {
+        let cgroup = Cgroup::at(Path::new("fixtures/cgroup/v2"), "").unwrap();
+        assert_eq!(cgroup.version, CgroupVersion::V2);
+        assert_eq!(cgroup.cpu_quota(), Some(1.5));
+        let cpu = cgroup.cpu_sample().unwrap();
+        assert_eq!(cpu.usage, 8734120311.0 / 1e6);
+        let memory = cgroup.memory().unwrap();
+        assert_eq!((memory.limit, memory.used), (536870912, 402653184));
+        assert_eq!(memory.used_percent(), 75.0);
+    }
+
+    // 容器有自己的 cgroup 命名空间时, /proc/self/cgroup 里的路径在挂载点下不存在, 退回挂载点
+    #[test]
+    fn v2_missing_self_path_falls_back_to_root() {
+        let cgroup = Cgroup::at(Path::new("fixtures/cgroup/v2"), "0::/docker/abc\n").unwrap();
+        assert_eq!(cgroup.cpu_quota(), Some(1.5));
+    }
+
+    #[test]
+    fn v1_fixture() {
+        let cgroup = Cgroup::at(Path::new("fixtures/cgroup/v1"), "").unwrap();
+        assert_eq!(cgroup.version, CgroupVersion::V1);
+        assert_eq!(cgroup.cpu_quota(), Some(2.0));
+        assert_eq!(cgroup.cpu_sample().unwrap().usage, 5312097734521.0 / 1e9);
+        let memory = cgroup.memory().unwrap();
+        assert_eq!((memory.limit, memory.used), (2147483648, 1395864371));
+    }
+
+    #[test]
+    fn missing_root() {
+        assert!(Cgroup::at(Path::new("fixtures/cgroup/none"), "").is_none());
+    }
+
+    // 配额和宿主机核心数取较小的那个
+    #[test]
+    fn effective_cpus() {
+        let quota = Cgroup::at(Path::new("fixtures/cgroup/v2"), "")
+            .unwrap()
+            .cpu_quota();
+        let cpu = container_cpu(
+            CgroupVersion::V2,
+            &sample(10.0, quota),
+            &sample(11.5, quota),
+            1.0,
+            8,
+        );
+        assert_eq!(cpu.effective_cpus, 1.5);
+        assert_eq!(cpu.usage, 150.0);
+        assert_eq!(cpu.quota_usage, 100.0);
+        // 配额比宿主机的核心还多时, 只能用到宿主机的核心数
+        let cpu = container_cpu(
+            CgroupVersion::V1,
+            &sample(0.0, Some(4.0)),
+            &sample(1.0, Some(4.0)),
+            1.0,
+            2,
+        );
+        assert_eq!(cpu.effective_cpus, 2.0);
+        assert_eq!(cpu.quota_usage, 50.0);
+    }
+
+    #[test]
+    fn unlimited() {
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("max"), None);
+        assert_eq!(parse_cpu_max("50000"), Some(0.5));
+        assert_eq!(parse_cpu_max("50000 0"), None);
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(parse_cfs_quota("50000\n", "100000\n"), Some(0.5));
+        assert_eq!(parse_memory_limit("max\n"), None);
+        // v1 的 "无限制" 是 i64::MAX 按页向下对齐
+        assert_eq!(parse_memory_limit("9223372036854771712\n"), None);
+        assert_eq!(parse_memory_limit("536870912\n"), Some(536870912));
+    }
+
+    // 没有配额的 v2 目录: 不出现在 Cpu.container 里, effective_cpus 是宿主机的核心数
+    #[test]
+    fn v2_without_limits() {
+        let dir = v2_with(
+            "unlimited",
+            &[("cpu.max", "max 100000\n"), ("memory.max", "max\n")],
+        );
+        let cgroup = Cgroup::at(&dir, "").unwrap();
+        assert_eq!(cgroup.cpu_quota(), None);
+        assert!(cgroup.memory().is_none());
+        let cpu = cgroup.cpu_sample().unwrap();
+        assert_eq!(cpu.quota, None);
+        let container = container_cpu(CgroupVersion::V2, &cpu, &cpu, 1.0, 8);
+        assert_eq!(container.effective_cpus, 8.0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+// 常驻采样进程
+// 机器上很多短命的脚本都要看一眼 CPU, 每个都调用 get_cpu_info(1000) 就要各自等 1 秒
+// 这里让采样常驻后台, 历史保存在内存中, 通过 Unix domain socket 回答查询:
+//   * current      最新一次完整快照
+//   * history 5m   最近一段时间的采样和统计, 时长格式见 history::parse_duration
+//   * status       当前状态、平滑后的使用率、采样是否正常以及分级用的阈值
+// 协议是按行的: 客户端每发送一行命令, 服务端回复一行 JSON, 一个连接可以连续查询
+// 例如 `echo status | socat - UNIX-CONNECT:/tmp/system_info.sock`, 或者直接用 query 子命令
+// ! 只在 Unix 上编译(见 mod.rs), 其他平台上 daemon 和 query 子命令只打印不支持
+use super::backend::{default_backend, Backend};
+use super::background::{spawn_sampler, SampledMetrics, SharedMetrics};
+use super::history::{self, parse_duration, CpuHistory, CpuSample, UsageStats, WINDOW_1M};
+use super::status::{CpuStatus, StatusConfig};
+use super::{get_system_snapshot, SystemSnapshot};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+
+// 最多保留的采样数, 1 秒一次时是 15 分钟
+const CAPACITY: usize = 900;
+
+// 默认的 socket 路径: SYSTEM_INFO_SOCKET, 其次是 $XDG_RUNTIME_DIR/system_info.sock, 最后是 /tmp
+pub fn default_socket() -> PathBuf {
+    if let Ok(path) = std::env::var("SYSTEM_INFO_SOCKET") {
+        return PathBuf::from(path);
+    }
+    std::env::var("XDG_RUNTIME_DIR")
+        .map_or(PathBuf::from("/tmp"), PathBuf::from)
+        .join("system_info.sock")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Current,
+    History(Duration),
+    Status,
+}
+
+impl Query {
+    pub fn parse(line: &str) -> Result<Query, String> {
+        let mut words = line.split_whitespace();
+        let query = match (words.next(), words.next()) {
+            (Some("current"), None) => Query::Current,
+            (Some("status"), None) => Query::Status,
+            // 不带时长时返回最近 1 分钟
+            (Some("history"), None) => Query::History(WINDOW_1M),
+            (Some("history"), Some(window)) => Query::History(
+                parse_duration(window).ok_or_else(|| format!("无法解析时长 {:?}", window))?,
+            ),
+            _ => {
+                return Err(format!(
+                    "未知命令 {:?}, 可用的命令: current, history <时长>, status",
+                    line.trim()
+                ))
+            }
+        };
+        match words.next() {
+            Some(extra) => Err(format!("多余的参数 {:?}", extra)),
+            None => Ok(query),
+        }
+    }
+}
+
+// 回复, 用 kind 字段区分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Response {
+    Current {
+        timestamp: u64,
+        snapshot: SystemSnapshot,
+    },
+    History {
+        window_ms: u64,
+        samples: Vec<CpuSample>,
+        // 窗口内没有采样时为 None
+        stats: Option<UsageStats>,
+        max_core_stats: Option<UsageStats>,
+    },
+    Status {
+        status: CpuStatus,
+        // 最近一次状态切换的时间
+        since: Option<u64>,
+        ewma: Option<f32>,
+        // 最近一次采样是否成功, 失败时 error 是原因
+        sampler_up: bool,
+        error: Option<String>,
+        // 最新采样的时间戳和内存中的采样数
+        timestamp: u64,
+        samples: usize,
+        // 分级用的阈值和最短停留时间, 方便客户端解释 status
+        thresholds: StatusConfig,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Response {
+    // 只读取共享状态, 不会触发测量
+    pub fn answer(query: &Query, metrics: &SampledMetrics) -> Response {
+        match query {
+            Query::Current => match &metrics.snapshot {
+                Some(snapshot) => Response::Current {
+                    timestamp: metrics.timestamp,
+                    snapshot: snapshot.clone(),
+                },
+                None => Response::Error {
+                    message: metrics
+                        .last_error
+                        .clone()
+                        .unwrap_or("还没有采样".to_string()),
+                },
+            },
+            Query::History(window) => Response::History {
+                window_ms: window.as_millis() as u64,
+                samples: metrics.history.window(*window).cloned().collect(),
+                stats: metrics.history.stats(*window),
+                max_core_stats: metrics.history.max_core_stats(*window),
+            },
+            Query::Status => Response::Status {
+                status: metrics.classifier.status(),
+                since: metrics.classifier.last_change(),
+                ewma: metrics.history.ewma(),
+                sampler_up: metrics.snapshot.is_some(),
+                error: metrics.last_error.clone(),
+                timestamp: metrics.timestamp,
+                samples: metrics.history.len(),
+                thresholds: metrics.classifier.config().clone(),
+            },
+        }
+    }
+}
+
+// 一个连接: 逐行读取命令, 逐行回复, 直到客户端关闭
+async fn handle(stream: UnixStream, metrics: SharedMetrics) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match Query::parse(&line) {
+            Ok(query) => Response::answer(&query, &metrics.lock().unwrap()),
+            Err(message) => Response::Error { message },
+        };
+        let mut text = serde_json::to_string(&response)?;
+        text.push('\n');
+        writer.write_all(text.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+// 绑定 socket, 旧的 socket 文件还在时先确认它没有被别的进程使用
+// ! 上一次运行被 kill -9 时 socket 文件会留下来, bind 会失败, 需要先删除
+async fn bind(path: &Path) -> io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} 已经有进程在监听", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    UnixListener::bind(path)
+}
+
+// 启动后台采样并在 path 上回答查询, 收到 SIGINT 或 SIGTERM 时退出
+pub async fn serve(path: &Path, interval: Duration) -> io::Result<()> {
+    let metrics: SharedMetrics = Arc::new(Mutex::new(SampledMetrics::new(
+        CAPACITY,
+        StatusConfig::default(),
+    )));
+    let listener = bind(path).await?;
+    // ? 信号流在循环外创建, 两次 accept 之间到达的信号也不会丢
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    // 在采样开始前订阅, 第一次状态切换也能记录到日志里
+    let mut transitions = metrics.lock().unwrap().classifier.subscribe();
+    let sampler = spawn_sampler(interval, metrics.clone());
+    println!(
+        "采样进程已启动: {}, 后端 {}",
+        path.display(),
+        default_backend().name()
+    );
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => break Err(err),
+                };
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle(stream, metrics).await {
+                        eprintln!("查询处理失败: {}", err);
+                    }
+                });
+            }
+            Some(transition) = transitions.next() => {
+                println!(
+                    "{} 状态切换 {} -> {}",
+                    transition.timestamp, transition.from, transition.to
+                );
+            }
+            _ = interrupt.recv() => break Ok(()),
+            _ = terminate.recv() => break Ok(()),
+        }
+    };
+    // 停止采样并删除 socket 文件, 之后的连接会直接失败, 而不是连上一个没有人应答的 socket
+    sampler.abort();
+    let _ = std::fs::remove_file(path);
+    println!("采样进程已退出");
+    result
+}
+
+// 发送一条命令并返回一行 JSON 回复
+pub async fn query(path: &Path, command: &str) -> io::Result<String> {
+    let stream = UnixStream::connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+    writer.shutdown().await?;
+    let mut lines = BufReader::new(reader).lines();
+    lines
+        .next_line()
+        .await?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "没有回复"))
+}
+
+// 子命令入口: daemon [socket 路径] [采样间隔毫秒]
+pub async fn daemon_main(args: &[String]) {
+    let path = args.first().map_or_else(default_socket, PathBuf::from);
+    let interval = args.get(1).and_then(|ms| ms.parse().ok()).unwrap_or(1000);
+    if let Err(err) = serve(&path, Duration::from_millis(interval)).await {
+        eprintln!("采样进程启动失败: {}", err);
+    }
+}
+
+// 子命令入口: query <命令...>, socket 路径见 default_socket
+// ? 采样进程没有运行时, current 和 history 退回到直接测量, 调用方拿到的仍然是一行 JSON, 只是要多等 1 秒
+pub async fn query_main(args: &[String]) {
+    let command = if args.is_empty() {
+        "status".to_string()
+    } else {
+        args.join(" ")
+    };
+    match query(&default_socket(), &command).await {
+        Ok(line) => println!("{}", line),
+        Err(err) => match Query::parse(&command) {
+            Ok(query @ (Query::Current | Query::History(_))) => {
+                eprintln!("连接采样进程失败({}), 改为直接测量", err);
+                match serde_json::to_string(&measure_directly(&query).await) {
+                    Ok(text) => println!("{}", text),
+                    Err(err) => eprintln!("序列化失败: {}", err),
+                }
+            }
+            _ => eprintln!("连接采样进程失败: {}", err),
+        },
+    }
+}
+
+// 直接测量一次, 按采样进程的格式回复; history 里只有这一个采样
+async fn measure_directly(query: &Query) -> Response {
+    let snapshot = match get_system_snapshot(1000).await {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            return Response::Error {
+                message: err.to_string(),
+            }
+        }
+    };
+    match query {
+        Query::History(window) => {
+            let mut history = CpuHistory::new(1, 1.0);
+            history.push(snapshot.cpu);
+            Response::History {
+                window_ms: window.as_millis() as u64,
+                samples: history.iter().cloned().collect(),
+                stats: history.stats(*window),
+                max_core_stats: history.max_core_stats(*window),
+            }
+        }
+        _ => Response::Current {
+            timestamp: history::now_millis(),
+            snapshot,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::{Cpu, SystemInfoError};
+
+    fn snapshot(usage: f32) -> SystemSnapshot {
+        SystemSnapshot {
+            cpu: Cpu {
+                count: 1,
+                usage,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Query::parse("current"), Ok(Query::Current));
+        assert_eq!(Query::parse("  status \n"), Ok(Query::Status));
+        assert_eq!(Query::parse("history"), Ok(Query::History(WINDOW_1M)));
+        assert_eq!(
+            Query::parse("history 5m"),
+            Ok(Query::History(Duration::from_secs(300)))
+        );
+        assert_eq!(
+            Query::parse("history 90"),
+            Ok(Query::History(Duration::from_secs(90)))
+        );
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let error = |line: &str| Query::parse(line).unwrap_err();
+        assert!(error("").starts_with("未知命令"));
+        assert!(error("stats").starts_with("未知命令"));
+        // 命令区分大小写
+        assert!(error("STATUS").starts_with("未知命令"));
+        assert!(error("current now").starts_with("未知命令"));
+        assert_eq!(error("history 5y"), "无法解析时长 \"5y\"");
+        assert_eq!(error("history m"), "无法解析时长 \"m\"");
+        assert_eq!(error("history 5m 1h"), "多余的参数 \"1h\"");
+    }
+
+    #[test]
+    fn answer_before_first_sample() {
+        let mut metrics = SampledMetrics::new(10, StatusConfig::default());
+        assert!(matches!(
+            Response::answer(&Query::Current, &metrics),
+            Response::Error { ref message } if message == "还没有采样"
+        ));
+        metrics.record_error(&SystemInfoError::unsupported("procfs"), 1_000);
+        assert!(matches!(
+            Response::answer(&Query::Current, &metrics),
+            Response::Error { ref message } if message == "当前平台不支持 procfs"
+        ));
+        match Response::answer(&Query::Status, &metrics) {
+            Response::Status {
+                status,
+                sampler_up,
+                error,
+                samples,
+                ..
+            } => {
+                assert_eq!(status, CpuStatus::Unknown);
+                assert!(!sampler_up);
+                assert_eq!(error.as_deref(), Some("当前平台不支持 procfs"));
+                assert_eq!(samples, 0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match Response::answer(&Query::History(WINDOW_1M), &metrics) {
+            Response::History { samples, stats, .. } => {
+                assert!(samples.is_empty());
+                assert!(stats.is_none());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn answer_with_samples() {
+        let mut metrics = SampledMetrics::new(10, StatusConfig::default());
+        for (index, usage) in [10.0, 20.0, 30.0].into_iter().enumerate() {
+            metrics.record(snapshot(usage), 1_000_000 + index as u64 * 30_000);
+        }
+        match Response::answer(&Query::Current, &metrics) {
+            Response::Current {
+                timestamp,
+                snapshot,
+            } => {
+                assert_eq!(timestamp, 1_060_000);
+                assert_eq!(snapshot.cpu.usage, 30.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // 窗口按最新采样的时间往前算, 45 秒内只有后两个采样
+        match Response::answer(&Query::History(Duration::from_secs(45)), &metrics) {
+            Response::History {
+                window_ms,
+                samples,
+                stats,
+                ..
+            } => {
+                assert_eq!(window_ms, 45_000);
+                assert_eq!(samples.len(), 2);
+                assert_eq!(stats.unwrap().mean, 25.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match Response::answer(&Query::Status, &metrics) {
+            Response::Status {
+                sampler_up,
+                error,
+                timestamp,
+                samples,
+                ewma,
+                thresholds,
+                ..
+            } => {
+                assert!(sampler_up);
+                assert_eq!(error, None);
+                assert_eq!(timestamp, 1_060_000);
+                assert_eq!(samples, 3);
+                assert!(ewma.is_some());
+                let defaults = StatusConfig::default();
+                assert_eq!(thresholds.hot_core, defaults.hot_core);
+                assert_eq!(thresholds.min_dwell_ms, defaults.min_dwell_ms);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn response_json() {
+        // 客户端按 kind 字段区分回复
+        let text = serde_json::to_string(&Response::Error {
+            message: "x".to_string(),
+        })
+        .unwrap();
+        assert_eq!(text, r#"{"kind":"error","message":"x"}"#);
+    }
+}
@@ -0,0 +1,336 @@
+// 系统信息的错误类型
+// 原来直接返回 heim::Error, 调用方只能把任何错误都当成 "CPU 为 0", 和真正空闲的机器分不清
+// 这里按调用方关心的原因分类, 底层错误通过 source() 保留下来
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+// 底层错误, 只用于 source 链
+pub type Source = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum SystemInfoError {
+    // 没有权限读取, 例如容器里 /proc 被限制
+    PermissionDenied {
+        resource: String,
+        source: Source,
+    },
+    // 当前平台不支持这个读数
+    UnsupportedPlatform {
+        feature: String,
+    },
+    // 读到了数据但格式不对
+    Parse {
+        resource: String,
+        message: String,
+        source: Option<Source>,
+    },
+    // 在限定时间内没有完成
+    Timeout {
+        operation: String,
+        after: Duration,
+    },
+    // 其他 I/O 或后端错误
+    Backend {
+        resource: String,
+        source: Source,
+    },
+}
+
+impl SystemInfoError {
+    pub fn parse(resource: impl Into<String>, message: impl Into<String>) -> SystemInfoError {
+        SystemInfoError::Parse {
+            resource: resource.into(),
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    // 带上底层解析错误, 例如 ParseIntError
+    pub fn parse_with<E>(resource: impl Into<String>, message: impl Into<String>, source: E) -> Self
+    where
+        E: Error + Send + Sync + 'static,
+    {
+        SystemInfoError::Parse {
+            resource: resource.into(),
+            message: message.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    pub fn unsupported(feature: impl Into<String>) -> SystemInfoError {
+        SystemInfoError::UnsupportedPlatform {
+            feature: feature.into(),
+        }
+    }
+
+    // 按 io::ErrorKind 分类读取文件时的错误
+    pub fn from_io(path: &Path, err: io::Error) -> SystemInfoError {
+        let resource = path.display().to_string();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => SystemInfoError::PermissionDenied {
+                resource,
+                source: Box::new(err),
+            },
+            io::ErrorKind::Unsupported => {
+                SystemInfoError::UnsupportedPlatform { feature: resource }
+            }
+            io::ErrorKind::TimedOut => SystemInfoError::Timeout {
+                operation: resource,
+                after: Duration::ZERO,
+            },
+            io::ErrorKind::InvalidData => SystemInfoError::Parse {
+                resource,
+                message: err.to_string(),
+                source: Some(Box::new(err)),
+            },
+            _ => SystemInfoError::Backend {
+                resource,
+                source: Box::new(err),
+            },
+        }
+    }
+}
+
+impl fmt::Display for SystemInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SystemInfoError::PermissionDenied { resource, .. } => {
+                write!(f, "没有权限读取 {}", resource)
+            }
+            SystemInfoError::UnsupportedPlatform { feature } => {
+                write!(f, "当前平台不支持 {}", feature)
+            }
+            SystemInfoError::Parse {
+                resource, message, ..
+            } => write!(f, "解析 {} 失败: {}", resource, message),
+            SystemInfoError::Timeout { operation, after } => {
+                write!(f, "{} 超时({:?})", operation, after)
+            }
+            SystemInfoError::Backend { resource, .. } => write!(f, "读取 {} 失败", resource),
+        }
+    }
+}
+
+impl Error for SystemInfoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SystemInfoError::PermissionDenied { source, .. }
+            | SystemInfoError::Backend { source, .. } => Some(source.as_ref()),
+            SystemInfoError::Parse { source, .. } => source.as_deref().map(|s| s as &dyn Error),
+            SystemInfoError::UnsupportedPlatform { .. } | SystemInfoError::Timeout { .. } => None,
+        }
+    }
+}
+
+// heim 的错误本身就是 io::Error 加上下文, 按 kind 分类
+#[cfg(feature = "heim")]
+impl From<heim::Error> for SystemInfoError {
+    fn from(err: heim::Error) -> Self {
+        let resource = "heim".to_string();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => SystemInfoError::PermissionDenied {
+                resource,
+                source: Box::new(err),
+            },
+            io::ErrorKind::Unsupported => SystemInfoError::UnsupportedPlatform {
+                feature: err.to_string(),
+            },
+            io::ErrorKind::InvalidData => SystemInfoError::Parse {
+                resource,
+                message: err.to_string(),
+                source: Some(Box::new(err)),
+            },
+            _ => SystemInfoError::Backend {
+                resource,
+                source: Box::new(err),
+            },
+        }
+    }
+}
+
+// 给一次读取加上时间限制
+// ? 读取 /proc 或调用 heim 正常都在毫秒级完成, 卡住通常意味着文件系统或内核出了问题
+// ! 只有 future 会让出执行权时超时才有效; 同步的文件读取要先用 blocking 放到阻塞线程池里
+pub async fn with_timeout<F, T>(
+    operation: &str,
+    after: Duration,
+    future: F,
+) -> Result<T, SystemInfoError>
+where
+    F: Future<Output = Result<T, SystemInfoError>>,
+{
+    use futures::future::{self, Either};
+    let delay = futures_timer::Delay::new(after);
+    futures::pin_mut!(future);
+    match future::select(future, delay).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(SystemInfoError::Timeout {
+            operation: operation.to_string(),
+            after,
+        }),
+    }
+}
+
+// 在 tokio 的阻塞线程池里执行同步读取(read_to_string、statvfs 等)
+// 直接在 async fn 里读文件时, 读取卡住会连同整个 future 一起卡住, with_timeout 里的 Delay 永远等不到被轮询
+// ? 超时后阻塞线程本身不会被取消, 只是调用方不再等它
+pub async fn blocking<F, T>(resource: &str, read: F) -> Result<T, SystemInfoError>
+where
+    F: FnOnce() -> Result<T, SystemInfoError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(read)
+        .await
+        .unwrap_or_else(|err| {
+            Err(SystemInfoError::Backend {
+                resource: resource.to_string(),
+                source: Box::new(err),
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let io = || io::Error::other("boom");
+        let cases = [
+            (
+                SystemInfoError::from_io(
+                    Path::new("/proc/stat"),
+                    io::Error::from(io::ErrorKind::PermissionDenied),
+                ),
+                "没有权限读取 /proc/stat",
+            ),
+            (
+                SystemInfoError::unsupported("温度传感器"),
+                "当前平台不支持 温度传感器",
+            ),
+            (
+                SystemInfoError::parse("/proc/loadavg", "字段不足"),
+                "解析 /proc/loadavg 失败: 字段不足",
+            ),
+            (
+                SystemInfoError::Timeout {
+                    operation: "读取CPU时间".to_string(),
+                    after: Duration::from_millis(500),
+                },
+                "读取CPU时间 超时(500ms)",
+            ),
+            (
+                SystemInfoError::from_io(Path::new("/proc/meminfo"), io()),
+                "读取 /proc/meminfo 失败",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn from_io_kinds() {
+        let path = Path::new("/proc/stat");
+        let kind = |kind| SystemInfoError::from_io(path, io::Error::from(kind));
+        assert!(matches!(
+            kind(io::ErrorKind::Unsupported),
+            SystemInfoError::UnsupportedPlatform { .. }
+        ));
+        assert!(matches!(
+            kind(io::ErrorKind::TimedOut),
+            SystemInfoError::Timeout { .. }
+        ));
+        assert!(matches!(
+            kind(io::ErrorKind::InvalidData),
+            SystemInfoError::Parse {
+                source: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            kind(io::ErrorKind::NotFound),
+            SystemInfoError::Backend { .. }
+        ));
+    }
+
+    #[test]
+    fn source_chain() {
+        // 底层错误要能通过 source() 取回原始类型
+        let err = SystemInfoError::from_io(
+            Path::new("/proc/stat"),
+            io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+        );
+        let source = err.source().expect("PermissionDenied 带有 source");
+        let io = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(io.to_string(), "denied");
+
+        let int = "x".parse::<u64>().unwrap_err();
+        let err = SystemInfoError::parse_with("/proc/uptime", "不是数字", int.clone());
+        let source = err.source().unwrap();
+        assert_eq!(source.downcast_ref::<std::num::ParseIntError>(), Some(&int));
+
+        // 作为别的错误的 source 时, 链条可以一直走到最底层
+        let mut current: &(dyn Error + 'static) = &err;
+        let mut depth = 1;
+        while let Some(next) = current.source() {
+            current = next;
+            depth += 1;
+        }
+        assert_eq!(depth, 2);
+        assert!(current.is::<std::num::ParseIntError>());
+
+        assert!(SystemInfoError::parse("/proc/uptime", "空")
+            .source()
+            .is_none());
+        assert!(SystemInfoError::unsupported("sensors").source().is_none());
+        let timeout = SystemInfoError::Timeout {
+            operation: "读取".to_string(),
+            after: Duration::ZERO,
+        };
+        assert!(timeout.source().is_none());
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let slow = async {
+            futures_timer::Delay::new(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        let err = with_timeout("慢读取", Duration::from_millis(10), slow)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SystemInfoError::Timeout { ref operation, after }
+                if operation == "慢读取" && after == Duration::from_millis(10)
+        ));
+
+        let fast = async { Ok(7) };
+        let value = with_timeout("快读取", Duration::from_secs(5), fast).await;
+        assert_eq!(value.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn blocking_results() {
+        assert_eq!(blocking("test", || Ok(3)).await.unwrap(), 3);
+        let err = blocking::<_, ()>("test", || Err(SystemInfoError::unsupported("x")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SystemInfoError::UnsupportedPlatform { .. }));
+        // 读取线程 panic 时转换成 Backend 错误, 而不是让调用方一起 panic
+        let err = blocking::<_, ()>("panicking", || panic!("读取失败"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SystemInfoError::Backend { ref resource, .. } if resource == "panicking"
+        ));
+        assert!(err.source().is_some());
+    }
+}
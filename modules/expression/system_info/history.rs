@@ -0,0 +1,306 @@
+// 采样历史
+// 单次 1 秒的采样抖动很大, 用它判断 Idle/Busy 会来回跳
+// 这里用一个有容量上限的环形缓冲保存最近的采样, 并提供窗口统计和指数加权移动平均(EWMA)
+use super::Cpu;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 常用的统计窗口, 和 load average 的 1/5/15 分钟对应
+pub const WINDOW_1M: Duration = Duration::from_secs(60);
+pub const WINDOW_5M: Duration = Duration::from_secs(5 * 60);
+pub const WINDOW_15M: Duration = Duration::from_secs(15 * 60);
+
+// 当前时间, 毫秒级 unix 时间戳
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// 解析 "90s"、"5m"、"2h"、"7d" 这样的时长, 不带单位时按秒
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: u64 = value.parse().ok()?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(value * seconds))
+}
+
+// 带时间戳的采样
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuSample {
+    // 毫秒级 unix 时间戳
+    pub timestamp: u64,
+    pub cpu: Cpu,
+}
+
+// 一个窗口内 usage 的统计值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl UsageStats {
+    // 空切片没有统计值
+    pub fn from_values(values: &[f32]) -> Option<UsageStats> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let count = sorted.len();
+        Some(UsageStats {
+            count,
+            min: sorted[0],
+            max: sorted[count - 1],
+            mean: sorted.iter().sum::<f32>() / count as f32,
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+        })
+    }
+}
+
+// 最近秩法(nearest-rank)求百分位, sorted 必须已经升序且非空
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub struct CpuHistory {
+    samples: VecDeque<CpuSample>,
+    capacity: usize,
+    // EWMA 的平滑系数, 越大越跟手, 越小越平滑
+    alpha: f32,
+    ewma: Option<f32>,
+}
+
+impl CpuHistory {
+    // ! capacity 至少为 1, alpha 取值范围 (0, 1]
+    pub fn new(capacity: usize, alpha: f32) -> CpuHistory {
+        CpuHistory {
+            samples: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+            alpha: alpha.clamp(f32::EPSILON, 1.0),
+            ewma: None,
+        }
+    }
+
+    // 记录一个采样, 时间戳取当前时间
+    pub fn push(&mut self, cpu: Cpu) {
+        self.push_sample(CpuSample {
+            timestamp: now_millis(),
+            cpu,
+        });
+    }
+
+    // 记录一个带时间戳的采样, 超出容量时丢弃最旧的
+    pub fn push_sample(&mut self, sample: CpuSample) {
+        let usage = sample.cpu.usage;
+        self.ewma = Some(match self.ewma {
+            Some(previous) => self.alpha * usage + (1.0 - self.alpha) * previous,
+            None => usage,
+        });
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&CpuSample> {
+        self.samples.back()
+    }
+
+    // 从旧到新遍历
+    pub fn iter(&self) -> impl Iterator<Item = &CpuSample> {
+        self.samples.iter()
+    }
+
+    // 平滑后的 usage, 状态判断应该优先用这个而不是单次采样
+    pub fn ewma(&self) -> Option<f32> {
+        self.ewma
+    }
+
+    // 最近 window 时长内的采样, 以最新一个采样的时间为准(而不是当前时间)
+    // ? 这样回放历史数据时统计结果也是确定的
+    pub fn window(&self, window: Duration) -> impl Iterator<Item = &CpuSample> {
+        let since = self.latest().map_or(0, |latest| {
+            latest.timestamp.saturating_sub(window.as_millis() as u64)
+        });
+        self.samples
+            .iter()
+            .filter(move |sample| sample.timestamp >= since)
+    }
+
+    pub fn stats(&self, window: Duration) -> Option<UsageStats> {
+        let values: Vec<f32> = self.window(window).map(|sample| sample.cpu.usage).collect();
+        UsageStats::from_values(&values)
+    }
+
+    // 窗口内每个采样中最忙核心的统计, 用来发现长期被打满的单核
+    pub fn max_core_stats(&self, window: Duration) -> Option<UsageStats> {
+        let values: Vec<f32> = self
+            .window(window)
+            .map(|sample| sample.cpu.max_core_usage())
+            .collect();
+        UsageStats::from_values(&values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::provider::{ScriptedProvider, SystemInfoProvider};
+
+    fn sample(timestamp: u64, usage: f32) -> CpuSample {
+        CpuSample {
+            timestamp,
+            cpu: Cpu {
+                count: 1,
+                usage,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn percentiles() {
+        let values: Vec<f32> = (1..=100).rev().map(|v| v as f32).collect();
+        let stats = UsageStats::from_values(&values).unwrap();
+        assert_eq!(stats.count, 100);
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 100.0, 50.5));
+        assert_eq!((stats.p50, stats.p95, stats.p99), (50.0, 95.0, 99.0));
+
+        // 20 个值: p95 是第 19 个, p99 向上取整到第 20 个
+        let values: Vec<f32> = (1..=20).map(|v| v as f32).collect();
+        let stats = UsageStats::from_values(&values).unwrap();
+        assert_eq!((stats.p50, stats.p95, stats.p99), (10.0, 19.0, 20.0));
+
+        let stats = UsageStats::from_values(&[42.0]).unwrap();
+        assert_eq!((stats.p50, stats.p95, stats.p99), (42.0, 42.0, 42.0));
+    }
+
+    #[test]
+    fn evicts_oldest_at_capacity() {
+        let mut history = CpuHistory::new(3, 0.5);
+        for timestamp in 1..=5 {
+            history.push_sample(sample(timestamp * 1000, timestamp as f32));
+        }
+        assert_eq!(history.len(), 3);
+        let timestamps: Vec<u64> = history.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, [3000, 4000, 5000]);
+        assert_eq!(history.latest().unwrap().timestamp, 5000);
+        // 被淘汰的采样仍然计入了 EWMA
+        assert_eq!(history.ewma(), Some(4.0625));
+
+        // 容量 0 按 1 处理
+        let mut history = CpuHistory::new(0, 0.5);
+        history.push_sample(sample(1000, 10.0));
+        history.push_sample(sample(2000, 20.0));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.latest().unwrap().cpu.usage, 20.0);
+    }
+
+    #[test]
+    fn ewma_alpha_is_clamped() {
+        // alpha 大于 1 时按 1 处理, EWMA 就是最新的值
+        let mut history = CpuHistory::new(10, 2.0);
+        for usage in [10.0, 90.0, 30.0] {
+            history.push_sample(sample(0, usage));
+        }
+        assert_eq!(history.ewma(), Some(30.0));
+
+        // alpha 不大于 0 时按 f32::EPSILON 处理, EWMA 几乎停在第一个值
+        for alpha in [0.0, -1.0] {
+            let mut history = CpuHistory::new(10, alpha);
+            for usage in [10.0, 90.0, 30.0] {
+                history.push_sample(sample(0, usage));
+            }
+            let ewma = history.ewma().unwrap();
+            assert!((ewma - 10.0).abs() < 1e-3, "alpha {}: {}", alpha, ewma);
+        }
+    }
+
+    #[test]
+    fn empty_window() {
+        let history = CpuHistory::new(10, 0.3);
+        assert!(history.is_empty());
+        assert!(history.latest().is_none());
+        assert_eq!(history.ewma(), None);
+        assert_eq!(history.window(WINDOW_15M).count(), 0);
+        assert_eq!(history.stats(WINDOW_1M), None);
+        assert_eq!(history.max_core_stats(WINDOW_1M), None);
+        assert_eq!(UsageStats::from_values(&[]), None);
+
+        // 窗口以最新采样为准, 长度为 0 的窗口只包含最新的采样
+        let mut history = CpuHistory::new(10, 0.3);
+        history.push_sample(sample(1000, 10.0));
+        history.push_sample(sample(2000, 20.0));
+        let stats = history.stats(Duration::ZERO).unwrap();
+        assert_eq!((stats.count, stats.mean), (1, 20.0));
+        assert_eq!(history.stats(WINDOW_5M).unwrap().count, 2);
+    }
+
+    #[test]
+    fn push_uses_current_time() {
+        let before = now_millis();
+        let mut history = CpuHistory::new(10, 0.3);
+        history.push(sample(0, 50.0).cpu);
+        let timestamp = history.latest().unwrap().timestamp;
+        assert!(before <= timestamp && timestamp <= now_millis());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Some(WINDOW_5M));
+        assert_eq!(parse_duration(" 2h "), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("7d"), Some(Duration::from_secs(7 * 86400)));
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("m"), None);
+    }
+
+    #[tokio::test]
+    async fn history_windows_follow_script_clock() {
+        let usages: Vec<f32> = (0..90).map(|i| i as f32).collect();
+        let mut provider = ScriptedProvider::from_usages(&usages).starting_at(1_000_000);
+        let mut history = CpuHistory::new(120, 0.3);
+        while !provider.finished() {
+            let cpu = provider.cpu_info(1000).await.unwrap();
+            history.push_sample(CpuSample {
+                timestamp: provider.now_millis(),
+                cpu,
+            });
+        }
+        let stats = history.stats(WINDOW_1M).unwrap();
+        // 最新的采样在 1_090_000, 窗口内是 1_030_000 之后的 61 个采样, 即 29 ~ 89
+        assert_eq!(stats.count, 61);
+        assert_eq!((stats.min, stats.max), (29.0, 89.0));
+        assert_eq!(stats.p50, 59.0);
+    }
+}
@@ -0,0 +1,556 @@
+// Prometheus 指标接口
+// 以 Prometheus 文本格式(text exposition format 0.0.4)暴露后台采样的数据
+// 每次抓取只读取共享状态, 不会触发测量
+use super::background::{spawn_sampler, SampledMetrics, SharedMetrics};
+use super::history::{WINDOW_15M, WINDOW_1M, WINDOW_5M};
+use super::status::{CpuStatus, StatusConfig};
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+// 请求头的长度上限, 超过时不再继续读取
+const MAX_REQUEST: usize = 8 * 1024;
+
+// 一组同名指标: HELP/TYPE 只输出一次, 后面跟若干带标签的样本
+struct Family<'a> {
+    out: &'a mut String,
+}
+
+impl<'a> Family<'a> {
+    fn gauge(out: &'a mut String, name: &str, help: &str) -> Family<'a> {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        Family { out }
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+        self
+    }
+}
+
+// 标签值中的反斜杠、双引号和换行需要转义
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    Family::gauge(out, name, help).sample(name, &[], value);
+}
+
+// 状态用 "一个状态一个时间序列, 当前状态为 1" 的惯用写法
+fn render_status(out: &mut String, metrics: &SampledMetrics) {
+    let current = metrics.classifier.status();
+    let mut family = Family::gauge(
+        out,
+        "cpu_status",
+        "Current CPU status level, 1 for the active one.",
+    );
+    for status in [
+        CpuStatus::Unknown,
+        CpuStatus::Idle,
+        CpuStatus::Normal,
+        CpuStatus::Busy,
+        CpuStatus::Saturated,
+    ] {
+        let value = if status == current { 1.0 } else { 0.0 };
+        family.sample("cpu_status", &[("status", &status.to_string())], value);
+    }
+}
+
+// 把共享状态渲染为 Prometheus 文本
+// ! 第一个采样到来之前或最近一次采样失败时, 只输出 sampler_up 0 和 unknown 状态, 而不是输出一堆 0 值
+pub fn render(metrics: &SampledMetrics) -> String {
+    let mut out = String::new();
+    let snapshot = match &metrics.snapshot {
+        Some(snapshot) => snapshot,
+        None => {
+            gauge(
+                &mut out,
+                "sampler_up",
+                "Whether the latest background sample succeeded.",
+                0.0,
+            );
+            render_status(&mut out, metrics);
+            return out;
+        }
+    };
+    gauge(
+        &mut out,
+        "sampler_up",
+        "Whether the latest background sample succeeded.",
+        1.0,
+    );
+    gauge(
+        &mut out,
+        "sample_timestamp_seconds",
+        "Unix time of the latest sample.",
+        metrics.timestamp as f64 / 1000.0,
+    );
+
+    let cpu = &snapshot.cpu;
+    gauge(
+        &mut out,
+        "cpu_logical_count",
+        "Number of logical CPUs.",
+        cpu.count as f64,
+    );
+    gauge(
+        &mut out,
+        "cpu_usage_percent",
+        "CPU usage over the last sampling interval, 100 per fully busy core.",
+        cpu.usage as f64,
+    );
+    if let Some(ewma) = metrics.history.ewma() {
+        gauge(
+            &mut out,
+            "cpu_usage_ewma_percent",
+            "Exponentially weighted moving average of cpu_usage_percent.",
+            ewma as f64,
+        );
+    }
+    // 和 load average 一样给出 1/5/15 分钟窗口; 历史不够一个窗口时用已有的全部采样
+    if !metrics.history.is_empty() {
+        let mut family = Family::gauge(
+            &mut out,
+            "cpu_usage_window_percent",
+            "Mean and p95 of cpu_usage_percent over the last 1, 5 and 15 minutes.",
+        );
+        for (window, duration) in [("1m", WINDOW_1M), ("5m", WINDOW_5M), ("15m", WINDOW_15M)] {
+            if let Some(stats) = metrics.history.stats(duration) {
+                for (stat, value) in [("mean", stats.mean), ("p95", stats.p95)] {
+                    family.sample(
+                        "cpu_usage_window_percent",
+                        &[("window", window), ("stat", stat)],
+                        value as f64,
+                    );
+                }
+            }
+        }
+    }
+    // 只有在设置了 CPU 配额的容器里才输出
+    if let Some(container) = &cpu.container {
+        gauge(
+            &mut out,
+            "container_effective_cpus",
+            "CPUs available to the container, min(quota, logical CPUs).",
+            container.effective_cpus,
+        );
+        gauge(
+            &mut out,
+            "container_cpu_usage_percent",
+            "CPU usage of the container's own cgroup, 100 per fully busy core.",
+            container.usage as f64,
+        );
+        gauge(
+            &mut out,
+            "container_cpu_quota_usage_percent",
+            "Container CPU usage relative to its quota, 100 when the quota is used up.",
+            container.quota_usage as f64,
+        );
+    }
+    let mut family = Family::gauge(
+        &mut out,
+        "cpu_time_percent",
+        "Share of CPU time spent in each mode over the last sampling interval.",
+    );
+    for (mode, value) in [
+        ("user", cpu.times.user),
+        ("system", cpu.times.system),
+        ("idle", cpu.times.idle),
+        ("iowait", cpu.times.iowait),
+    ] {
+        family.sample("cpu_time_percent", &[("mode", mode)], value as f64);
+    }
+    let mut family = Family::gauge(
+        &mut out,
+        "cpu_core_usage_percent",
+        "Usage of each logical core over the last sampling interval.",
+    );
+    for (index, core) in cpu.cores.iter().enumerate() {
+        let index = index.to_string();
+        family.sample(
+            "cpu_core_usage_percent",
+            &[("core", &index)],
+            core.usage as f64,
+        );
+    }
+    render_status(&mut out, metrics);
+
+    let memory = &snapshot.memory;
+    gauge(
+        &mut out,
+        "memory_total_bytes",
+        "Total physical memory.",
+        memory.total as f64,
+    );
+    gauge(
+        &mut out,
+        "memory_used_bytes",
+        "Used memory, total minus available.",
+        memory.used as f64,
+    );
+    gauge(
+        &mut out,
+        "memory_available_bytes",
+        "Memory available for new allocations.",
+        memory.available as f64,
+    );
+    if let Some(container) = &memory.container {
+        gauge(
+            &mut out,
+            "container_memory_limit_bytes",
+            "Memory limit of the container's cgroup.",
+            container.limit as f64,
+        );
+        gauge(
+            &mut out,
+            "container_memory_used_bytes",
+            "Memory charged to the container's cgroup.",
+            container.used as f64,
+        );
+    }
+    let swap = &snapshot.swap;
+    gauge(
+        &mut out,
+        "swap_total_bytes",
+        "Total swap space.",
+        swap.total as f64,
+    );
+    gauge(
+        &mut out,
+        "swap_used_bytes",
+        "Used swap space.",
+        swap.used as f64,
+    );
+    gauge(
+        &mut out,
+        "swap_free_bytes",
+        "Free swap space.",
+        swap.free as f64,
+    );
+    let load = &snapshot.load_average;
+    gauge(
+        &mut out,
+        "load_average_1m",
+        "1-minute load average.",
+        load.one as f64,
+    );
+    gauge(
+        &mut out,
+        "load_average_5m",
+        "5-minute load average.",
+        load.five as f64,
+    );
+    gauge(
+        &mut out,
+        "load_average_15m",
+        "15-minute load average.",
+        load.fifteen as f64,
+    );
+    gauge(
+        &mut out,
+        "uptime_seconds",
+        "Time since boot.",
+        snapshot.uptime,
+    );
+    out
+}
+
+// 读取请求头, 直到空行(\r\n\r\n)、连接关闭或者超过 MAX_REQUEST
+// ! 一次 read 不一定能读到完整的请求行, 请求可能被拆成多个 TCP 分段到达
+async fn read_request<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+// 只处理 GET /metrics, 其余一律 404
+// ? 这里不需要完整的 HTTP 实现, 读完请求头后只看请求行, 每个连接只响应一次后关闭
+async fn handle<S>(mut stream: S, metrics: SharedMetrics) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request = read_request(&mut stream).await?;
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = render(&metrics.lock().unwrap());
+            ("200 OK", CONTENT_TYPE, body)
+        }
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// 启动后台采样并在 addr 上提供 /metrics
+pub async fn serve(addr: &str, interval: Duration) -> io::Result<()> {
+    let metrics: SharedMetrics = Arc::new(Mutex::new(SampledMetrics::new(
+        900,
+        StatusConfig::default(),
+    )));
+    let sampler = spawn_sampler(interval, metrics.clone());
+    let listener = TcpListener::bind(addr).await?;
+    println!("指标服务已启动: http://{}/metrics", listener.local_addr()?);
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                sampler.abort();
+                return Err(err);
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, metrics).await {
+                eprintln!("指标请求处理失败: {}", err);
+            }
+        });
+    }
+}
+
+// 子命令入口: metrics [监听地址] [采样间隔毫秒]
+pub async fn metrics_main(args: &[String]) {
+    let addr = args.first().map_or("127.0.0.1:9898", String::as_str);
+    let interval = args.get(1).and_then(|ms| ms.parse().ok()).unwrap_or(1000);
+    if let Err(err) = serve(addr, Duration::from_millis(interval)).await {
+        eprintln!("metrics 服务启动失败: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::{
+        CoreUsage, Cpu, CpuTimes, LoadAverage, Memory, Swap, SystemSnapshot,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    fn shared() -> SharedMetrics {
+        Arc::new(Mutex::new(SampledMetrics::new(60, StatusConfig::default())))
+    }
+
+    // 2 核机器, 一个核心半忙, 一个核心满载
+    fn snapshot(usage: f32) -> SystemSnapshot {
+        let times = |user: f32, idle: f32| CpuTimes {
+            user,
+            system: 0.0,
+            idle,
+            iowait: 0.0,
+        };
+        SystemSnapshot {
+            cpu: Cpu {
+                count: 2,
+                usage,
+                times: times(75.0, 25.0),
+                cores: vec![
+                    CoreUsage {
+                        usage: 50.0,
+                        times: times(50.0, 50.0),
+                    },
+                    CoreUsage {
+                        usage: 100.0,
+                        times: times(100.0, 0.0),
+                    },
+                ],
+                container: None,
+            },
+            memory: Memory {
+                total: 8_000_000_000,
+                used: 3_000_000_000,
+                available: 5_000_000_000,
+                container: None,
+            },
+            swap: Swap {
+                total: 2048,
+                used: 512,
+                free: 1536,
+            },
+            load_average: LoadAverage {
+                one: 1.5,
+                five: 0.75,
+                fifteen: 0.25,
+            },
+            uptime: 3600.5,
+            ..Default::default()
+        }
+    }
+
+    // 最近一次 usage 是 150, 历史是 50、100、150
+    fn sampled() -> SampledMetrics {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        for (index, usage) in [50.0, 100.0, 150.0].into_iter().enumerate() {
+            metrics.record(snapshot(usage), 1_000 * (index as u64 + 1));
+        }
+        metrics
+    }
+
+    // "名字{标签}" -> 值
+    fn samples(text: &str) -> HashMap<&str, f64> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                (series, value.parse().unwrap())
+            })
+            .collect()
+    }
+
+    // 每个指标族的 HELP/TYPE 只出现一次, 并且在它的样本之前
+    fn assert_families(text: &str) {
+        let mut help = HashSet::new();
+        let mut typed = HashSet::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let name = rest.split(' ').next().unwrap();
+                assert!(help.insert(name), "HELP {} 重复", name);
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let name = rest.split(' ').next().unwrap();
+                assert_eq!(rest, format!("{} gauge", name));
+                assert!(help.contains(name), "TYPE {} 在 HELP 之前", name);
+                assert!(typed.insert(name), "TYPE {} 重复", name);
+            } else {
+                let name = line.split(['{', ' ']).next().unwrap();
+                assert!(typed.contains(name), "{} 没有 HELP/TYPE", name);
+            }
+        }
+    }
+
+    #[test]
+    fn render_known_snapshot() {
+        let text = render(&sampled());
+        assert_families(&text);
+        let values = samples(&text);
+        for (series, expected) in [
+            ("sampler_up", 1.0),
+            ("sample_timestamp_seconds", 3.0),
+            ("cpu_logical_count", 2.0),
+            ("cpu_usage_percent", 150.0),
+            ("cpu_time_percent{mode=\"user\"}", 75.0),
+            ("cpu_time_percent{mode=\"idle\"}", 25.0),
+            ("cpu_core_usage_percent{core=\"0\"}", 50.0),
+            ("cpu_core_usage_percent{core=\"1\"}", 100.0),
+            (
+                "cpu_usage_window_percent{window=\"1m\",stat=\"mean\"}",
+                100.0,
+            ),
+            (
+                "cpu_usage_window_percent{window=\"15m\",stat=\"p95\"}",
+                150.0,
+            ),
+            // 按平均值只是 Normal, 但有一个核心满载, 至少是 Busy
+            ("cpu_status{status=\"busy\"}", 1.0),
+            ("cpu_status{status=\"unknown\"}", 0.0),
+            ("memory_total_bytes", 8e9),
+            ("memory_used_bytes", 3e9),
+            ("memory_available_bytes", 5e9),
+            ("swap_used_bytes", 512.0),
+            ("load_average_1m", 1.5),
+            ("load_average_15m", 0.25),
+            ("uptime_seconds", 3600.5),
+        ] {
+            assert_eq!(values.get(series), Some(&expected), "{}", series);
+        }
+        // 5 个状态各一个时间序列, 3 个窗口各两个统计值
+        assert_eq!(text.matches("\ncpu_status{").count(), 5);
+        assert_eq!(text.matches("\ncpu_usage_window_percent{").count(), 6);
+        // 没有配额时不输出容器指标
+        assert!(!text.contains("container_"));
+    }
+
+    // 没有采样时只有 sampler_up 0 和 unknown 状态
+    #[test]
+    fn render_without_snapshot() {
+        let text = render(&SampledMetrics::new(60, StatusConfig::default()));
+        assert_families(&text);
+        let values = samples(&text);
+        assert_eq!(values.len(), 6);
+        assert_eq!(values["sampler_up"], 0.0);
+        assert_eq!(values["cpu_status{status=\"unknown\"}"], 1.0);
+        assert_eq!(values["cpu_status{status=\"idle\"}"], 0.0);
+    }
+
+    #[test]
+    fn label_escaping() {
+        assert_eq!(escape_label("plain"), "plain");
+        assert_eq!(escape_label("a\\b \"c\"\nd"), "a\\\\b \\\"c\\\"\\nd");
+        let mut out = String::new();
+        Family::gauge(&mut out, "x", "Test.")
+            .sample("x", &[("path", "C:\\tmp\n"), ("name", "\"q\"")], 1.0)
+            .sample("x", &[], 2.5);
+        assert_eq!(
+            out,
+            "# HELP x Test.\n# TYPE x gauge\nx{path=\"C:\\\\tmp\\n\",name=\"\\\"q\\\"\"} 1\nx 2.5\n"
+        );
+    }
+
+    // 请求行被拆成两段到达, 中间隔一段时间
+    #[tokio::test]
+    async fn request_split_across_segments() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let handler = tokio::spawn(handle(server, shared()));
+        client.write_all(b"GE").await.unwrap();
+        futures_timer::Delay::new(Duration::from_millis(50)).await;
+        client
+            .write_all(b"T /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        handler.await.unwrap().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains(CONTENT_TYPE));
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_not_found() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let handler = tokio::spawn(handle(server, shared()));
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        handler.await.unwrap().unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    // 客户端发完请求行就关闭写端, 没有空行也要能响应
+    #[tokio::test]
+    async fn request_ended_by_eof() {
+        let mut request: &[u8] = b"GET /metrics HTTP/1.0\r\n";
+        assert_eq!(
+            read_request(&mut request).await.unwrap(),
+            "GET /metrics HTTP/1.0\r\n"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use backend::{default_backend, Backend};
+use cgroup::{Cgroup, CgroupCpuSample, CgroupVersion, ContainerCpu, ContainerMemory};
+use error::with_timeout;
+pub use error::SystemInfoError;
+use futures::StreamExt;
+use sensors::Sensors;
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+pub mod alert;
+pub mod backend;
+pub mod background;
+pub mod cgroup;
+// 依赖 Unix domain socket 和 Unix 信号
+#[cfg(unix)]
+pub mod daemon;
+pub mod error;
+pub mod history;
+pub mod metrics;
+pub mod monitor;
+pub mod process;
+pub mod provider;
+pub mod recorder;
+pub mod report;
+pub mod sampler;
+pub mod sensors;
+pub mod snapshot;
+pub mod status;
+pub mod throughput;
+
+// 单次读取后端的时间上限
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Cpu {
+    // CPU信息
+    pub count: u64,
+    pub usage: f32,
+    // 整体的 user/system/idle/iowait 占比
+    // ? 旧版本序列化出来的数据没有下面两个字段, 反序列化时用默认值补齐
+    #[serde(default)]
+    pub times: CpuTimes,
+    // 每个逻辑核心的使用率, 顺序和 /proc/stat 中 cpu0, cpu1... 一致
+    #[serde(default)]
+    pub cores: Vec<CoreUsage>,
+    // 在设置了 CPU 配额的容器里运行时, 容器视角的 CPU; count 和 usage 仍然是宿主机的原始读数
+    #[serde(default)]
+    pub container: Option<ContainerCpu>,
+}
+
+impl Cpu {
+    // 所有核心的平均使用率(0 ~ 100)
+    pub fn average_core_usage(&self) -> f32 {
+        if self.cores.is_empty() {
+            return 0.0;
+        }
+        self.cores.iter().map(|core| core.usage).sum::<f32>() / self.cores.len() as f32
+    }
+
+    // 把 usage 口径(每个核心贡献 100, 8 核满载是 800)的读数换算成平均每个核心的使用率(0 ~ 100)
+    // ? 例如 history 平滑后的 usage, 状态分级的阈值是按这个口径配置的
+    pub fn per_core(&self, usage: f32) -> f32 {
+        if self.count > 0 {
+            usage / self.count as f32
+        } else {
+            usage
+        }
+    }
+
+    // 最忙的那个核心的使用率, 单核被打满时平均值看不出来, 要看这个
+    pub fn max_core_usage(&self) -> f32 {
+        self.cores.iter().map(|core| core.usage).fold(0.0, f32::max)
+    }
+}
+
+// 采样间隔内各类CPU时间的占比, 单位百分比, 四项相加约等于 100
+// user 包含 nice, system 包含 irq/softirq
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CpuTimes {
+    pub user: f32,
+    pub system: f32,
+    pub idle: f32,
+    pub iowait: f32,
+}
+
+// 单个逻辑核心
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CoreUsage {
+    // 使用率(0 ~ 100), 即非 idle/iowait 时间的占比
+    pub usage: f32,
+    pub times: CpuTimes,
+}
+
+// 某一时刻的累计CPU时间(秒), 两次之差才有意义
+#[derive(Debug, Default, Clone)]
+pub struct CpuTimeSample {
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+}
+
+impl CpuTimeSample {
+    // 所有状态的时间之和
+    // ? guest/guest_nice 已经计入 user/nice, 不能重复相加
+    fn total(&self) -> f64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    // 以 earlier 为基准计算这段时间内各项的占比
+    pub fn delta(&self, earlier: &CpuTimeSample) -> CoreUsage {
+        let total = self.total() - earlier.total();
+        if total <= 0.0 {
+            return CoreUsage::default();
+        }
+        let percent = |value: f64| (value / total * 100.0) as f32;
+        let times = CpuTimes {
+            user: percent(self.user + self.nice - earlier.user - earlier.nice),
+            system: percent(
+                self.system + self.irq + self.softirq
+                    - earlier.system
+                    - earlier.irq
+                    - earlier.softirq,
+            ),
+            idle: percent(self.idle - earlier.idle),
+            iowait: percent(self.iowait - earlier.iowait),
+        };
+        CoreUsage {
+            usage: (100.0 - times.idle - times.iowait).max(0.0),
+            times,
+        }
+    }
+}
+
+// 一次测量: 测量时刻 + 整体累计时间 + 每个核心的累计时间
+// 两次测量之差就是一个 Cpu 采样, 连续采样时上一次的测量可以直接作为下一次的基准
+#[derive(Debug, Clone)]
+pub struct CpuMeasurement {
+    at: Instant,
+    total: CpuTimeSample,
+    cores: Vec<CpuTimeSample>,
+    count: u64,
+    // 只有设置了 CPU 配额时才有
+    cgroup: Option<(CgroupVersion, CgroupCpuSample)>,
+}
+
+impl CpuMeasurement {
+    pub async fn take_with<B: Backend>(backend: &B) -> Result<CpuMeasurement, SystemInfoError> {
+        let ((total, cores), count, cgroup) = with_timeout("读取CPU时间", READ_TIMEOUT, async {
+            futures::try_join!(
+                backend.cpu_times(),
+                backend.logical_count(),
+                Cgroup::cpu_sample_blocking(),
+            )
+        })
+        .await?;
+        Ok(CpuMeasurement {
+            at: Instant::now(),
+            total,
+            cores,
+            count,
+            cgroup,
+        })
+    }
+
+    // 以 earlier 为基准, 计算这段时间内的 Cpu 采样
+    pub fn since(&self, earlier: &CpuMeasurement) -> Cpu {
+        // usage 沿用 heim 的口径: user + system 时间 / 墙上时间, 满载时每个核心贡献 100
+        let elapsed = self.at.duration_since(earlier.at).as_secs_f64();
+        let busy =
+            (self.total.user + self.total.system) - (earlier.total.user + earlier.total.system);
+        let usage = if elapsed > 0.0 {
+            (busy / elapsed * 100.0) as f32
+        } else {
+            0.0
+        };
+        let cores = self
+            .cores
+            .iter()
+            .zip(earlier.cores.iter())
+            .map(|(later, earlier)| later.delta(earlier))
+            .collect();
+        let container = match (&earlier.cgroup, &self.cgroup) {
+            (Some((_, before)), Some((version, after))) => Some(cgroup::container_cpu(
+                *version, before, after, elapsed, self.count,
+            )),
+            _ => None,
+        };
+        Cpu {
+            count: self.count,
+            usage,
+            times: self.total.delta(&earlier.total).times,
+            cores,
+            container,
+        }
+    }
+}
+
+// 内存信息, 单位都是字节
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Memory {
+    pub total: u64,
+    // 已用 = 总量 - 可用, 和 `free` 命令的 used 列口径一致
+    pub used: u64,
+    pub available: u64,
+    // 在设置了内存上限的容器里运行时, 容器的上限和用量
+    #[serde(default)]
+    pub container: Option<ContainerMemory>,
+}
+
+// 交换分区信息, 单位字节
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Swap {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+}
+
+// 1/5/15 分钟平均负载
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+// 一次完整的系统采样
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SystemSnapshot {
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub swap: Swap,
+    pub load_average: LoadAverage,
+    // 开机时长, 单位秒
+    pub uptime: f64,
+    // 温度和频率, 没有传感器的机器上各项为 None
+    #[serde(default)]
+    pub sensors: Sensors,
+}
+
+// ? async/await使用需要引入 features, 并且返回必须是一个Result枚举;
+pub async fn get_cpu_info(time: u64) -> Result<Cpu, SystemInfoError> {
+    // 通过两次插值计算cpu使用率: cpu_stream 的第一个采样就是 "测量一次, 等待 time, 再测量一次"
+    // ? 需要连续采样时直接消费 sampler::cpu_stream, 它会复用上一次的测量, 不用每次都等两次
+    let samples = sampler::cpu_stream(Duration::from_millis(time));
+    futures::pin_mut!(samples);
+    samples.next().await.expect("cpu_stream 不会结束")
+}
+
+// 以下读数都来自默认后端
+pub async fn get_memory_info() -> Result<Memory, SystemInfoError> {
+    let backend = default_backend();
+    let (memory, container) = futures::try_join!(backend.memory(), Cgroup::memory_blocking())?;
+    Ok(with_container_memory(memory, container))
+}
+
+// 后端读到的是宿主机的内存, 在这里补上容器的上限
+fn with_container_memory(mut memory: Memory, container: Option<ContainerMemory>) -> Memory {
+    memory.container = container;
+    memory
+}
+
+pub async fn get_swap_info() -> Result<Swap, SystemInfoError> {
+    default_backend().swap().await
+}
+
+pub async fn get_load_average() -> Result<LoadAverage, SystemInfoError> {
+    default_backend().load_average().await
+}
+
+pub async fn get_uptime() -> Result<f64, SystemInfoError> {
+    default_backend().uptime().await
+}
+
+// 采集完整快照
+// ? CPU使用率需要等待 time 毫秒做两次测量, 其余的读数在等待期间并发完成, 总耗时仍然约等于 time
+pub async fn get_system_snapshot(time: u64) -> Result<SystemSnapshot, SystemInfoError> {
+    let (cpu, memory, swap, load_average, uptime, sensors) = futures::try_join!(
+        get_cpu_info(time),
+        get_memory_info(),
+        get_swap_info(),
+        get_load_average(),
+        get_uptime(),
+        Sensors::read_blocking(),
+    )?;
+    Ok(SystemSnapshot {
+        cpu,
+        memory,
+        swap,
+        load_average,
+        uptime,
+        sensors,
+    })
+}
+
+// 已经有了 Cpu 采样(例如来自 sampler::cpu_stream), 用同一个后端补齐其余读数组成完整快照
+pub async fn complete_snapshot_with<B: Backend>(
+    backend: &B,
+    cpu: Cpu,
+) -> Result<SystemSnapshot, SystemInfoError> {
+    let (memory, container, swap, load_average, uptime, sensors) =
+        with_timeout("读取内存和负载", READ_TIMEOUT, async {
+            futures::try_join!(
+                backend.memory(),
+                Cgroup::memory_blocking(),
+                backend.swap(),
+                backend.load_average(),
+                backend.uptime(),
+                Sensors::read_blocking(),
+            )
+        })
+        .await?;
+    Ok(SystemSnapshot {
+        cpu,
+        memory: with_container_memory(memory, container),
+        swap,
+        load_average,
+        uptime,
+        sensors,
+    })
+}
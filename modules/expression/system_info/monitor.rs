@@ -0,0 +1,416 @@
+// 终端实时面板
+// 每个采样间隔重绘一次整屏: CPU 总量(按状态着色)、每个核心的条形图、历史使用率的火花线、内存和负载
+// 标准输出不是终端(重定向到文件或管道)时, 退化为每个采样一行的纯文本, 方便 grep 和 tail -f
+// ? 没有引入 TUI 库, 只用到了几个 ANSI 转义序列: 备用屏幕、清屏、光标隐藏和前景色
+use super::background::{run_sampler, SampledMetrics};
+use super::process::format_bytes;
+use super::status::{CpuStatus, StatusConfig};
+use super::{Cpu, SystemSnapshot};
+use std::io::{self, IsTerminal, Write};
+use std::time::Duration;
+
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR: &str = "\x1b[H\x1b[2J";
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// 着色开关, 不是终端时关闭
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub color: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn status(&self, status: CpuStatus) -> String {
+        self.paint(&status.to_string().to_uppercase(), status_code(status))
+    }
+}
+
+// 状态对应的颜色: 灰、绿、青、黄、红
+fn status_code(status: CpuStatus) -> &'static str {
+    match status {
+        CpuStatus::Unknown => "90",
+        CpuStatus::Idle => "32",
+        CpuStatus::Normal => "36",
+        CpuStatus::Busy => "33",
+        CpuStatus::Saturated => "1;31",
+    }
+}
+
+// 终端宽度, 取不到时用 COLUMNS 环境变量, 再不行就是 80
+pub fn terminal_width() -> usize {
+    window_columns()
+        .or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok())
+        })
+        .unwrap_or(80)
+}
+
+// TIOCGWINSZ 查询标准输出所在终端的列数
+#[cfg(unix)]
+fn window_columns() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // ? ioctl 只写入 size 这个局部变量, 失败时返回 -1, 不会有其他副作用
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn window_columns() -> Option<usize> {
+    None
+}
+
+// 0 ~ 1 的比例画成条形图
+pub fn bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+// 0 ~ 100 的数值画成火花线, 只取最后 width 个
+pub fn sparkline(values: &[f32], width: usize) -> String {
+    let start = values.len().saturating_sub(width);
+    values[start..]
+        .iter()
+        .map(|value| {
+            let level = (value.clamp(0.0, 100.0) / 100.0 * (SPARKS.len() - 1) as f32).round();
+            SPARKS[level as usize]
+        })
+        .collect()
+}
+
+// 面板上的 CPU 比例用所有核心的平均值(0 ~ 100)
+// ? Cpu.usage 是 "每个核心 100", 多核机器上会超过 100, 不适合直接画条形图
+fn cpu_percent(cpu: &Cpu) -> f32 {
+    if !cpu.cores.is_empty() {
+        cpu.average_core_usage()
+    } else if cpu.count > 0 {
+        cpu.usage / cpu.count as f32
+    } else {
+        cpu.usage
+    }
+}
+
+fn memory_percent(snapshot: &SystemSnapshot) -> f64 {
+    let memory = &snapshot.memory;
+    if memory.total == 0 {
+        0.0
+    } else {
+        memory.used as f64 / memory.total as f64 * 100.0
+    }
+}
+
+fn format_uptime(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    format!(
+        "{}d {}h {}m",
+        minutes / 1440,
+        minutes / 60 % 24,
+        minutes % 60
+    )
+}
+
+// 整屏面板
+// ? 标签用英文: 中文字符占两列, 按字符数对齐会错位
+pub fn render_dashboard(metrics: &SampledMetrics, width: usize, style: Style) -> String {
+    let mut out = String::new();
+    let status = metrics.classifier.status();
+    // 标签占 8 列, 数值占 24 列, 剩下的给条形图
+    let bar_width = width.saturating_sub(36).clamp(10, 60);
+    out.push_str(&format!(
+        "{}  status {}\n\n",
+        style.paint("system monitor", "1"),
+        style.status(status)
+    ));
+    let snapshot = match &metrics.snapshot {
+        Some(snapshot) => snapshot,
+        None => {
+            let reason = metrics
+                .last_error
+                .as_deref()
+                .unwrap_or("waiting for the first sample");
+            out.push_str(&format!("{}\n", style.paint(reason, "90")));
+            return out;
+        }
+    };
+    let cpu = &snapshot.cpu;
+    let percent = cpu_percent(cpu);
+    out.push_str(&format!(
+        "{:<8}{} {:>6.1}%  {} cpus, usage {:.1}\n",
+        "CPU",
+        style.paint(&bar(percent as f64 / 100.0, bar_width), status_code(status)),
+        percent,
+        cpu.count,
+        cpu.usage
+    ));
+    if let Some(container) = &cpu.container {
+        out.push_str(&format!(
+            "{:<8}{} {:>6.1}%  of {:.2} cpus quota\n",
+            "quota",
+            bar(container.quota_usage as f64 / 100.0, bar_width),
+            container.quota_usage,
+            container.effective_cpus
+        ));
+    }
+    let history: Vec<f32> = metrics
+        .history
+        .iter()
+        .map(|s| cpu_percent(&s.cpu))
+        .collect();
+    out.push_str(&format!(
+        "{:<8}{}\n\n",
+        "history",
+        style.paint(&sparkline(&history, bar_width + 8), "36")
+    ));
+    for (index, core) in cpu.cores.iter().enumerate() {
+        out.push_str(&format!(
+            "{:<8}{} {:>6.1}%\n",
+            format!("cpu{}", index),
+            bar(core.usage as f64 / 100.0, bar_width),
+            core.usage
+        ));
+    }
+    let memory = &snapshot.memory;
+    out.push_str(&format!(
+        "\n{:<8}{} {:>6.1}%  {} / {}\n",
+        "mem",
+        bar(memory_percent(snapshot) / 100.0, bar_width),
+        memory_percent(snapshot),
+        format_bytes(memory.used),
+        format_bytes(memory.total)
+    ));
+    if let Some(container) = &memory.container {
+        out.push_str(&format!(
+            "{:<8}{} {:>6.1}%  {} / {}\n",
+            "cgroup",
+            bar(container.used_percent() as f64 / 100.0, bar_width),
+            container.used_percent(),
+            format_bytes(container.used),
+            format_bytes(container.limit)
+        ));
+    }
+    let swap = &snapshot.swap;
+    out.push_str(&format!(
+        "{:<8}{} / {}\n",
+        "swap",
+        format_bytes(swap.used),
+        format_bytes(swap.total)
+    ));
+    let load = &snapshot.load_average;
+    out.push_str(&format!(
+        "{:<8}{:.2} {:.2} {:.2}   up {}\n",
+        "load",
+        load.one,
+        load.five,
+        load.fifteen,
+        format_uptime(snapshot.uptime)
+    ));
+    out
+}
+
+// 单行文本, 字段都是 key=value, 方便日志系统解析
+pub fn render_line(metrics: &SampledMetrics) -> String {
+    let status = metrics.classifier.status();
+    match &metrics.snapshot {
+        Some(snapshot) => {
+            let load = &snapshot.load_average;
+            format!(
+                "ts={} status={} cpu={:.1}% mem={:.1}% load={:.2},{:.2},{:.2}",
+                metrics.timestamp,
+                status,
+                cpu_percent(&snapshot.cpu),
+                memory_percent(snapshot),
+                load.one,
+                load.five,
+                load.fifteen
+            )
+        }
+        None => format!(
+            "ts={} status={} error={:?}",
+            metrics.timestamp,
+            status,
+            metrics.last_error.as_deref().unwrap_or("")
+        ),
+    }
+}
+
+// 打印一帧: 终端上是面板, 否则是单行
+pub fn print_frame(metrics: &SampledMetrics) {
+    if io::stdout().is_terminal() {
+        print!(
+            "{}",
+            render_dashboard(metrics, terminal_width(), Style { color: true })
+        );
+    } else {
+        println!("{}", render_line(metrics));
+    }
+}
+
+// 子命令入口: monitor [采样间隔毫秒]
+// ! 按 Ctrl-C 退出时要恢复终端(离开备用屏幕并显示光标), 所以这里自己处理 SIGINT
+pub async fn monitor_main(args: &[String]) {
+    let interval = args.first().and_then(|ms| ms.parse().ok()).unwrap_or(1000);
+    let tty = io::stdout().is_terminal();
+    let mut metrics = SampledMetrics::new(600, StatusConfig::default());
+    let mut stdout = io::stdout();
+    if tty {
+        let _ = write!(stdout, "{}{}", ENTER_SCREEN, CLEAR);
+        let _ = stdout.flush();
+    }
+    let sampler = run_sampler(Duration::from_millis(interval), |sample, timestamp| {
+        metrics.update(sample, timestamp);
+        let mut stdout = io::stdout();
+        let _ = if tty {
+            let frame = render_dashboard(&metrics, terminal_width(), Style { color: true });
+            write!(stdout, "{}{}", CLEAR, frame)
+        } else {
+            writeln!(stdout, "{}", render_line(&metrics))
+        };
+        let _ = stdout.flush();
+    });
+    tokio::select! {
+        _ = sampler => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    if tty {
+        let _ = write!(stdout, "{}", LEAVE_SCREEN);
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::{
+        CoreUsage, CpuTimes, LoadAverage, Memory, Swap, SystemInfoError,
+    };
+
+    const PLAIN: Style = Style { color: false };
+
+    // 2 核机器, 两个核心分别是 20% 和 60%
+    fn snapshot(usage: f32) -> SystemSnapshot {
+        let core = |usage: f32| CoreUsage {
+            usage,
+            times: CpuTimes::default(),
+        };
+        SystemSnapshot {
+            cpu: Cpu {
+                count: 2,
+                usage,
+                cores: vec![core(20.0), core(60.0)],
+                ..Default::default()
+            },
+            memory: Memory {
+                total: 8 << 30,
+                used: 2 << 30,
+                available: 6 << 30,
+                container: None,
+            },
+            swap: Swap {
+                total: 1 << 30,
+                used: 0,
+                free: 1 << 30,
+            },
+            load_average: LoadAverage {
+                one: 1.5,
+                five: 0.75,
+                fifteen: 0.25,
+            },
+            uptime: 90_061.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bars() {
+        assert_eq!(bar(0.0, 4), "░░░░");
+        assert_eq!(bar(0.5, 4), "██░░");
+        assert_eq!(bar(1.0, 4), "████");
+        // 超出 0 ~ 1 的比例不会画出界
+        assert_eq!(bar(1.7, 4), "████");
+        assert_eq!(bar(-0.3, 4), "░░░░");
+        assert_eq!(bar(0.5, 0), "");
+    }
+
+    #[test]
+    fn sparklines() {
+        assert_eq!(sparkline(&[], 8), "");
+        assert_eq!(sparkline(&[40.0; 5], 8), "▄▄▄▄▄");
+        let ramp: Vec<f32> = (0..8).map(|step| step as f32 * 100.0 / 7.0).collect();
+        assert_eq!(sparkline(&ramp, 8), "▁▂▃▄▅▆▇█");
+        // 只取最后 width 个, 超过 100 的值按 100 画
+        assert_eq!(sparkline(&ramp, 3), "▆▇█");
+        assert_eq!(sparkline(&[150.0, -5.0], 8), "█▁");
+    }
+
+    #[test]
+    fn line() {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        metrics.record(snapshot(80.0), 1_000);
+        assert_eq!(
+            render_line(&metrics),
+            format!(
+                "ts=1000 status={} cpu=40.0% mem=25.0% load=1.50,0.75,0.25",
+                metrics.classifier.status()
+            )
+        );
+        metrics.record_error(&SystemInfoError::unsupported("procfs"), 2_000);
+        assert_eq!(
+            render_line(&metrics),
+            "ts=2000 status=unknown error=\"当前平台不支持 procfs\""
+        );
+    }
+
+    #[test]
+    fn dashboard_without_snapshot() {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        assert_eq!(
+            render_dashboard(&metrics, 80, PLAIN),
+            "system monitor  status UNKNOWN\n\nwaiting for the first sample\n"
+        );
+        metrics.record_error(&SystemInfoError::unsupported("procfs"), 1_000);
+        let frame = render_dashboard(&metrics, 80, PLAIN);
+        assert!(frame.ends_with("\n\n当前平台不支持 procfs\n"));
+        // 着色时原因用灰色
+        let colored = render_dashboard(&metrics, 80, Style { color: true });
+        assert!(colored.contains("\x1b[90m当前平台不支持 procfs\x1b[0m"));
+    }
+
+    #[test]
+    fn dashboard_with_snapshot() {
+        let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+        metrics.record(snapshot(80.0), 1_000);
+        // 宽度 46 时条形图最窄, 10 列
+        let frame = render_dashboard(&metrics, 46, PLAIN);
+        let status = metrics.classifier.status().to_string().to_uppercase();
+        let expected = [
+            format!("system monitor  status {}", status),
+            String::new(),
+            "CPU     ████░░░░░░   40.0%  2 cpus, usage 80.0".to_string(),
+            "history ▄".to_string(),
+            String::new(),
+            "cpu0    ██░░░░░░░░   20.0%".to_string(),
+            "cpu1    ██████░░░░   60.0%".to_string(),
+            String::new(),
+            "mem     ███░░░░░░░   25.0%  2.0 GiB / 8.0 GiB".to_string(),
+            "swap    0.0 B / 1.0 GiB".to_string(),
+            "load    1.50 0.75 0.25   up 1d 1h 1m".to_string(),
+        ];
+        assert_eq!(frame.lines().collect::<Vec<_>>(), expected);
+        assert!(!frame.contains('\x1b'));
+    }
+}
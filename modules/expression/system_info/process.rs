@@ -0,0 +1,364 @@
+// 进程排行
+// 状态分级只能告诉我们 "机器很忙", 值班时紧接着的问题是 "谁在占用"
+// 和 get_cpu_info 一样做两次测量: 每个进程的 CPU 占比 = 两次之间累计 CPU 时间的差 / 墙上时间
+// 内存用常驻内存(RSS), 取第二次测量的值
+use super::backend::{default_backend, Backend};
+use super::error::with_timeout;
+use super::status::{StatusClassifier, StatusConfig};
+use super::{get_cpu_info, SystemInfoError, READ_TIMEOUT};
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+// 后端读取的单个进程的原始数据
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTimes {
+    pub pid: u32,
+    pub name: String,
+    // 完整命令行, 内核线程没有命令行时为空
+    pub cmdline: String,
+    pub uid: Option<u32>,
+    // 进程启动时间, Unix 时间戳(秒), 只用来识别 pid 被复用的情况
+    pub start_time: f64,
+    // 累计的 user + system CPU 时间, 单位秒
+    pub cpu_time: f64,
+    // 常驻内存, 单位字节
+    pub rss: u64,
+}
+
+// 一个采样窗口内的进程
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    // 用户名, 查不到用户名时是 uid, 连 uid 都拿不到时为 None
+    pub user: Option<String>,
+    // 和 Cpu.usage 同样的口径: 占满一个核心为 100
+    pub cpu_usage: f32,
+    pub rss: u64,
+}
+
+// 进程排行
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessTable {
+    // 两次测量之间有多少个进程
+    pub total: usize,
+    pub by_cpu: Vec<ProcessInfo>,
+    pub by_memory: Vec<ProcessInfo>,
+}
+
+impl ProcessTable {
+    // 分别按 CPU 和 RSS 从大到小取前 n 个
+    pub fn top(processes: Vec<ProcessInfo>, n: usize) -> ProcessTable {
+        let mut by_cpu = processes.clone();
+        by_cpu.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage).then(a.pid.cmp(&b.pid)));
+        by_cpu.truncate(n);
+        let total = processes.len();
+        let mut by_memory = processes;
+        by_memory.sort_by(|a, b| b.rss.cmp(&a.rss).then(a.pid.cmp(&b.pid)));
+        by_memory.truncate(n);
+        ProcessTable {
+            total,
+            by_cpu,
+            by_memory,
+        }
+    }
+}
+
+// uid -> 用户名, 来自 /etc/passwd
+#[derive(Debug, Clone, Default)]
+pub struct UserTable {
+    names: HashMap<u32, String>,
+}
+
+impl UserTable {
+    // 读不到 /etc/passwd 时返回空表, 之后都显示 uid
+    pub fn load() -> UserTable {
+        fs::read_to_string("/etc/passwd")
+            .map_or_else(|_| UserTable::default(), |t| UserTable::parse(&t))
+    }
+
+    // 每行 "name:password:uid:gid:..."
+    pub fn parse(text: &str) -> UserTable {
+        let names = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse().ok()?;
+                Some((uid, name.to_string()))
+            })
+            .collect();
+        UserTable { names }
+    }
+
+    pub fn name(&self, uid: u32) -> String {
+        self.names
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string())
+    }
+}
+
+// 一次测量: 所有进程的累计值
+#[derive(Debug, Clone)]
+pub struct ProcessMeasurement {
+    at: Instant,
+    processes: HashMap<u32, ProcessTimes>,
+}
+
+impl ProcessMeasurement {
+    pub async fn take_with<B: Backend>(backend: &B) -> Result<ProcessMeasurement, SystemInfoError> {
+        let processes = with_timeout("读取进程列表", READ_TIMEOUT, backend.processes()).await?;
+        Ok(ProcessMeasurement {
+            at: Instant::now(),
+            processes: processes.into_iter().map(|p| (p.pid, p)).collect(),
+        })
+    }
+
+    // 以 earlier 为基准计算每个进程的 CPU 占比
+    // ? 只统计两次测量中都存在的进程; pid 相同但启动时间不同说明 pid 被复用了, 也跳过
+    pub fn since(&self, earlier: &ProcessMeasurement, users: &UserTable) -> Vec<ProcessInfo> {
+        let elapsed = self.at.duration_since(earlier.at).as_secs_f64();
+        self.processes
+            .values()
+            .filter_map(|later| {
+                let before = earlier.processes.get(&later.pid)?;
+                if before.start_time != later.start_time {
+                    return None;
+                }
+                let cpu_usage = if elapsed > 0.0 {
+                    ((later.cpu_time - before.cpu_time).max(0.0) / elapsed * 100.0) as f32
+                } else {
+                    0.0
+                };
+                Some(ProcessInfo {
+                    pid: later.pid,
+                    name: later.name.clone(),
+                    cmdline: later.cmdline.clone(),
+                    user: later.uid.map(|uid| users.name(uid)),
+                    cpu_usage,
+                    rss: later.rss,
+                })
+            })
+            .collect()
+    }
+}
+
+// 测量 time 毫秒内的进程排行, 每个维度取前 n 个
+pub async fn top_processes(time: u64, n: usize) -> Result<ProcessTable, SystemInfoError> {
+    top_processes_with(&default_backend(), time, n).await
+}
+
+pub async fn top_processes_with<B: Backend>(
+    backend: &B,
+    time: u64,
+    n: usize,
+) -> Result<ProcessTable, SystemInfoError> {
+    let users = UserTable::load();
+    let measurement_1 = ProcessMeasurement::take_with(backend).await?;
+    futures_timer::Delay::new(Duration::from_millis(time)).await;
+    let measurement_2 = ProcessMeasurement::take_with(backend).await?;
+    Ok(ProcessTable::top(
+        measurement_2.since(&measurement_1, &users),
+        n,
+    ))
+}
+
+// 以 KiB/MiB/GiB 显示字节数
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// 打印成一张表, 命令行过长时截断
+pub fn print_table(title: &str, processes: &[ProcessInfo]) {
+    println!("{}", title);
+    println!(
+        "{:>8} {:<12} {:>7} {:>11}  {:<16} COMMAND",
+        "PID", "USER", "CPU%", "RSS", "NAME"
+    );
+    for process in processes {
+        let command = if process.cmdline.is_empty() {
+            format!("[{}]", process.name)
+        } else {
+            process.cmdline.chars().take(60).collect()
+        };
+        println!(
+            "{:>8} {:<12} {:>7.1} {:>11}  {:<16} {}",
+            process.pid,
+            process.user.as_deref().unwrap_or("?"),
+            process.cpu_usage,
+            format_bytes(process.rss),
+            process.name,
+            command
+        );
+    }
+}
+
+// 子命令入口: top [数量] [采样毫秒] [--json]
+// ? 同一个窗口里也测一次整体 CPU, 这样 "机器很忙" 和 "谁在占用" 出现在同一份输出里
+pub async fn top_main(args: &[String]) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let mut numbers = args.iter().filter_map(|arg| arg.parse::<u64>().ok());
+    let n = numbers.next().unwrap_or(10) as usize;
+    let time = numbers.next().unwrap_or(1000);
+    let (table, cpu) = futures::join!(top_processes(time, n), get_cpu_info(time));
+    let table = match table {
+        Ok(table) => table,
+        Err(err) => {
+            eprintln!("读取进程失败: {}", err);
+            return;
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&table) {
+            Ok(text) => println!("{}", text),
+            Err(err) => eprintln!("序列化失败: {}", err),
+        }
+        return;
+    }
+    match cpu {
+        Ok(cpu) => {
+            let mut classifier = StatusClassifier::new(StatusConfig::default());
+            classifier.observe(cpu.per_core(cpu.usage), cpu.max_core_usage(), 0);
+            println!(
+                "CPU 状态 {}, 使用率 {:.1}%, {} 个逻辑核心",
+                classifier.status(),
+                cpu.usage,
+                cpu.count
+            );
+        }
+        Err(err) => println!("CPU 状态 unknown: {}", err),
+    }
+    println!("{} 个进程, 采样 {} 毫秒", table.total, time);
+    print_table("按 CPU 排序", &table.by_cpu);
+    println!();
+    print_table("按内存排序", &table.by_memory);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn times(pid: u32, uid: Option<u32>, start_time: f64, cpu_time: f64) -> ProcessTimes {
+        ProcessTimes {
+            pid,
+            name: format!("p{}", pid),
+            uid,
+            start_time,
+            cpu_time,
+            rss: pid as u64 * 1024,
+            ..ProcessTimes::default()
+        }
+    }
+
+    fn measurement(at: Instant, processes: Vec<ProcessTimes>) -> ProcessMeasurement {
+        ProcessMeasurement {
+            at,
+            processes: processes.into_iter().map(|p| (p.pid, p)).collect(),
+        }
+    }
+
+    fn info(pid: u32, cpu_usage: f32, rss: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: format!("p{}", pid),
+            cmdline: String::new(),
+            user: None,
+            cpu_usage,
+            rss,
+        }
+    }
+
+    fn users() -> UserTable {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/etc/passwd");
+        UserTable::parse(&fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn since() {
+        let start = Instant::now();
+        let earlier = measurement(
+            start,
+            vec![
+                times(1, Some(0), 10.0, 1.0),
+                times(2, Some(1000), 20.0, 5.0),
+                // 两次测量之间退出了
+                times(3, Some(1000), 30.0, 7.0),
+                times(5, None, 50.0, 4.0),
+            ],
+        );
+        let later = measurement(
+            start + Duration::from_secs(2),
+            vec![
+                times(1, Some(0), 10.0, 2.0),
+                // pid 被复用: 启动时间变了, 累计时间不能和之前的进程相减
+                times(2, Some(1000), 99.0, 0.5),
+                // 第一次测量之后才启动
+                times(4, Some(4242), 40.0, 1.0),
+                // 累计时间不会倒退, 万一倒退按 0 处理
+                times(5, None, 50.0, 3.0),
+            ],
+        );
+        let mut processes = later.since(&earlier, &users());
+        processes.sort_by_key(|process| process.pid);
+        let pids: Vec<u32> = processes.iter().map(|process| process.pid).collect();
+        assert_eq!(pids, [1, 5]);
+        assert_eq!(processes[0].cpu_usage, 50.0);
+        assert_eq!(processes[0].user.as_deref(), Some("root"));
+        assert_eq!(processes[0].rss, 1024);
+        assert_eq!(processes[1].cpu_usage, 0.0);
+        assert_eq!(processes[1].user, None);
+
+        // 两次测量在同一时刻时不除以 0
+        let same = measurement(start, vec![times(1, Some(4242), 10.0, 2.0)]);
+        let processes = same.since(&earlier, &users());
+        assert_eq!(processes[0].cpu_usage, 0.0);
+        // 查不到用户名时显示 uid
+        assert_eq!(processes[0].user.as_deref(), Some("4242"));
+    }
+
+    #[test]
+    fn top() {
+        let processes = vec![
+            info(1, 10.0, 300),
+            info(2, 80.0, 100),
+            info(3, 10.0, 900),
+            info(4, 0.0, 300),
+        ];
+        let table = ProcessTable::top(processes, 3);
+        assert_eq!(table.total, 4);
+        let pids = |list: &[ProcessInfo]| list.iter().map(|p| p.pid).collect::<Vec<_>>();
+        // 相同的值按 pid 从小到大, 输出是稳定的
+        assert_eq!(pids(&table.by_cpu), [2, 1, 3]);
+        assert_eq!(pids(&table.by_memory), [3, 1, 4]);
+
+        let table = ProcessTable::top(vec![info(1, 1.0, 1)], 10);
+        assert_eq!((table.total, table.by_cpu.len()), (1, 1));
+        assert_eq!(ProcessTable::top(Vec::new(), 5).total, 0);
+    }
+
+    #[test]
+    fn users_from_passwd() {
+        let users = users();
+        assert_eq!(users.name(0), "root");
+        assert_eq!(users.name(33), "www-data");
+        assert_eq!(users.name(1000), "alice");
+        assert_eq!(users.name(65534), "nobody");
+        // 注释、空行和 uid 不是数字的行都被跳过
+        assert_eq!(users.names.len(), 5);
+        assert_eq!(users.name(4242), "4242");
+        assert_eq!(UserTable::parse("").name(0), "0");
+    }
+}
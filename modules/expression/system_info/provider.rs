@@ -0,0 +1,231 @@
+// 数据提供者
+// Backend 只负责读取原始的累计值, Provider 在它之上给出 "一段时间内的 Cpu" 和完整快照
+// 上层逻辑(状态分级、历史、告警)只依赖 SystemInfoProvider, 于是有两个实现:
+//   * LiveProvider: 真实测量, 默认后端见 backend::default_backend
+//   * ScriptedProvider: 按脚本回放采样和错误, 不等待也不读系统, 结果完全确定, 只在测试中编译
+// 状态分级、历史和告警用它写的测试分别在 status、history、alert 和 background 中
+use super::backend::{default_backend, AnyBackend, Backend};
+use super::history;
+use super::sampler::cpu_stream_with;
+use super::SystemInfoError;
+use super::{complete_snapshot_with, Cpu, SystemSnapshot};
+use futures::stream::{BoxStream, StreamExt};
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+pub trait SystemInfoProvider {
+    // 测量 time 毫秒内的 CPU 使用情况
+    fn cpu_info(&mut self, time: u64) -> impl Future<Output = Result<Cpu, SystemInfoError>> + Send;
+
+    // 测量 time 毫秒内的 CPU 使用情况, 并补齐其余读数
+    fn snapshot(
+        &mut self,
+        time: u64,
+    ) -> impl Future<Output = Result<SystemSnapshot, SystemInfoError>> + Send;
+
+    // 当前的毫秒级时间戳, 采样结果都用它打时间
+    fn now_millis(&self) -> u64 {
+        history::now_millis()
+    }
+
+    // 是否已经没有更多的采样(真实测量永远不会结束)
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+// 真实测量
+// CPU 采样来自 sampler::cpu_stream, 每次的测量也是下一次的起点, 连续调用时不用每次都测两次
+pub struct LiveProvider<B = AnyBackend> {
+    backend: B,
+    samples: Option<LiveSamples>,
+}
+
+// 正在使用的采样 Stream, 以及创建它时的间隔和最近一次取到采样的时间
+struct LiveSamples {
+    interval: Duration,
+    stream: BoxStream<'static, Result<Cpu, SystemInfoError>>,
+    polled: Instant,
+}
+
+impl Default for LiveProvider {
+    fn default() -> Self {
+        LiveProvider::new(default_backend())
+    }
+}
+
+impl<B: Backend> LiveProvider<B> {
+    pub fn new(backend: B) -> LiveProvider<B> {
+        LiveProvider {
+            backend,
+            samples: None,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+}
+
+impl<B: Backend + Clone + Send + Sync + 'static> SystemInfoProvider for LiveProvider<B> {
+    // ? 间隔变了, 或者上一次取采样已经超过 2 * time(基准太旧, 结果会变成很长一段时间的平均值), 就重新开始一个 Stream
+    async fn cpu_info(&mut self, time: u64) -> Result<Cpu, SystemInfoError> {
+        let interval = Duration::from_millis(time);
+        let reusable = self.samples.as_ref().is_some_and(|samples| {
+            samples.interval == interval && samples.polled.elapsed() <= interval * 2
+        });
+        if !reusable {
+            self.samples = Some(LiveSamples {
+                interval,
+                stream: cpu_stream_with(self.backend.clone(), interval).boxed(),
+                polled: Instant::now(),
+            });
+        }
+        let samples = self.samples.as_mut().unwrap();
+        let cpu = samples.stream.next().await.expect("cpu_stream 不会结束");
+        samples.polled = Instant::now();
+        cpu
+    }
+
+    async fn snapshot(&mut self, time: u64) -> Result<SystemSnapshot, SystemInfoError> {
+        let cpu = self.cpu_info(time).await?;
+        complete_snapshot_with(&self.backend, cpu).await
+    }
+}
+
+// 脚本中的一步
+#[cfg(test)]
+#[derive(Debug)]
+pub enum ScriptStep {
+    Sample(SystemSnapshot),
+    // 这一步测量失败
+    Error(SystemInfoError),
+}
+
+// 按顺序回放脚本
+// * 每次调用消耗一步, 时钟前进 time 毫秒, 不会真的等待
+// ! 脚本回放完之后再调用会返回 Backend 错误, finished() 也会变成 true
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedProvider {
+    steps: VecDeque<ScriptStep>,
+    clock: u64,
+}
+
+#[cfg(test)]
+impl ScriptedProvider {
+    pub fn new(steps: impl IntoIterator<Item = ScriptStep>) -> ScriptedProvider {
+        ScriptedProvider {
+            steps: steps.into_iter().collect(),
+            clock: 0,
+        }
+    }
+
+    // 只关心整体使用率的脚本, 其余读数都是 0
+    pub fn from_usages(usages: &[f32]) -> ScriptedProvider {
+        ScriptedProvider::new(
+            usages
+                .iter()
+                .map(|&usage| ScriptStep::Sample(usage_sample(usage))),
+        )
+    }
+
+    // 时钟的起点, 默认是 0
+    pub fn starting_at(mut self, timestamp: u64) -> ScriptedProvider {
+        self.clock = timestamp;
+        self
+    }
+
+    pub fn push(&mut self, step: ScriptStep) {
+        self.steps.push_back(step);
+    }
+
+    pub fn push_usage(&mut self, usage: f32) {
+        self.push(ScriptStep::Sample(usage_sample(usage)));
+    }
+
+    pub fn push_error(&mut self, error: SystemInfoError) {
+        self.push(ScriptStep::Error(error));
+    }
+
+    // 还没有回放的步数
+    pub fn remaining(&self) -> usize {
+        self.steps.len()
+    }
+
+    fn next_step(&mut self, time: u64) -> Result<SystemSnapshot, SystemInfoError> {
+        self.clock += time;
+        match self.steps.pop_front() {
+            Some(ScriptStep::Sample(snapshot)) => Ok(snapshot),
+            Some(ScriptStep::Error(error)) => Err(error),
+            None => Err(SystemInfoError::Backend {
+                resource: "script".to_string(),
+                source: "脚本已经回放完毕".into(),
+            }),
+        }
+    }
+}
+
+// 整体使用率为 usage 的单核快照
+#[cfg(test)]
+pub fn usage_sample(usage: f32) -> SystemSnapshot {
+    SystemSnapshot {
+        cpu: Cpu {
+            count: 1,
+            usage,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+impl SystemInfoProvider for ScriptedProvider {
+    async fn cpu_info(&mut self, time: u64) -> Result<Cpu, SystemInfoError> {
+        self.next_step(time).map(|snapshot| snapshot.cpu)
+    }
+
+    async fn snapshot(&mut self, time: u64) -> Result<SystemSnapshot, SystemInfoError> {
+        self.next_step(time)
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.clock
+    }
+
+    fn finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeout() -> SystemInfoError {
+        SystemInfoError::Timeout {
+            operation: "读取CPU时间".to_string(),
+            after: Duration::from_secs(5),
+        }
+    }
+
+    // 回放完之后返回 Backend 错误
+    #[tokio::test]
+    async fn exhausted_script() {
+        let mut provider = ScriptedProvider::default();
+        provider.push_error(timeout());
+        assert_eq!(provider.remaining(), 1);
+        assert!(matches!(
+            provider.snapshot(1000).await,
+            Err(SystemInfoError::Timeout { .. })
+        ));
+        assert!(provider.finished());
+        assert!(matches!(
+            provider.snapshot(1000).await,
+            Err(SystemInfoError::Backend { .. })
+        ));
+        assert_eq!(provider.now_millis(), 2_000);
+    }
+}
@@ -0,0 +1,857 @@
+// 指标记录
+// 容量评估需要几周的历史数据, 但为此部署一套时序数据库太重了
+// 这里把每个采样追加写入本地文件, 格式二选一:
+//   * JSON Lines: 每行一个完整的 Record, 保留全部读数(每个核心、容器限制等)
+//   * CSV: 每行一个 RecordRow, 只有汇总的列, 可以直接导入表格
+// 文件按段(segment)滚动, 文件名为 "<前缀>-<UTC 日期>-<序号>.<扩展名>", 例如 metrics-20261019-000.jsonl
+//   * 按天: 采样跨过 UTC 零点时换新文件
+//   * 按大小: 写入下一行会超过 max_bytes 时换新文件
+// 换下来的旧段可以压缩成 .gz, 读取时自动解压
+use super::background::{run_sampler, SampledMetrics};
+use super::status::{CpuStatus, StatusConfig};
+use super::SystemSnapshot;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+const CSV_HEADER: &str = "timestamp,status,cpu_usage,max_core_usage,cpu_count,memory_total,memory_used,swap_used,load_one,load_five,load_fifteen,uptime";
+
+// 一条记录: 采样时间、当时的状态和完整快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    // 毫秒级 unix 时间戳
+    pub timestamp: u64,
+    pub status: CpuStatus,
+    pub snapshot: SystemSnapshot,
+}
+
+// 一条记录的汇总列, 也是 CSV 的一行
+// ? 报表只用到这些列, 所以 JSON Lines 读回来之后也先转换成 RecordRow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordRow {
+    pub timestamp: u64,
+    pub status: CpuStatus,
+    pub cpu_usage: f32,
+    pub max_core_usage: f32,
+    pub cpu_count: u64,
+    pub memory_total: u64,
+    pub memory_used: u64,
+    pub swap_used: u64,
+    pub load_one: f32,
+    pub load_five: f32,
+    pub load_fifteen: f32,
+    pub uptime: f64,
+}
+
+impl RecordRow {
+    pub fn from_record(record: &Record) -> RecordRow {
+        let snapshot = &record.snapshot;
+        RecordRow {
+            timestamp: record.timestamp,
+            status: record.status,
+            cpu_usage: snapshot.cpu.usage,
+            max_core_usage: snapshot.cpu.max_core_usage(),
+            cpu_count: snapshot.cpu.count,
+            memory_total: snapshot.memory.total,
+            memory_used: snapshot.memory.used,
+            swap_used: snapshot.swap.used,
+            load_one: snapshot.load_average.one,
+            load_five: snapshot.load_average.five,
+            load_fifteen: snapshot.load_average.fifteen,
+            uptime: snapshot.uptime,
+        }
+    }
+
+    pub fn memory_used_percent(&self) -> f32 {
+        if self.memory_total == 0 {
+            0.0
+        } else {
+            (self.memory_used as f64 / self.memory_total as f64 * 100.0) as f32
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{:.2},{:.2},{},{},{},{},{:.2},{:.2},{:.2},{:.0}",
+            self.timestamp,
+            self.status,
+            self.cpu_usage,
+            self.max_core_usage,
+            self.cpu_count,
+            self.memory_total,
+            self.memory_used,
+            self.swap_used,
+            self.load_one,
+            self.load_five,
+            self.load_fifteen,
+            self.uptime
+        )
+    }
+
+    // 列数或者某一列不对时返回 None
+    pub fn from_csv(line: &str) -> Option<RecordRow> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() != 12 {
+            return None;
+        }
+        Some(RecordRow {
+            timestamp: fields[0].parse().ok()?,
+            status: parse_status(fields[1])?,
+            cpu_usage: fields[2].parse().ok()?,
+            max_core_usage: fields[3].parse().ok()?,
+            cpu_count: fields[4].parse().ok()?,
+            memory_total: fields[5].parse().ok()?,
+            memory_used: fields[6].parse().ok()?,
+            swap_used: fields[7].parse().ok()?,
+            load_one: fields[8].parse().ok()?,
+            load_five: fields[9].parse().ok()?,
+            load_fifteen: fields[10].parse().ok()?,
+            uptime: fields[11].parse().ok()?,
+        })
+    }
+}
+
+// CSV 中的状态是 Display 的小写形式
+fn parse_status(text: &str) -> Option<CpuStatus> {
+    Some(match text {
+        "unknown" => CpuStatus::Unknown,
+        "idle" => CpuStatus::Idle,
+        "normal" => CpuStatus::Normal,
+        "busy" => CpuStatus::Busy,
+        "saturated" => CpuStatus::Saturated,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    JsonLines,
+    Csv,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::JsonLines => "jsonl",
+            Format::Csv => "csv",
+        }
+    }
+
+    // --format 的取值, 和扩展名相同
+    pub fn from_name(name: &str) -> Option<Format> {
+        [Format::JsonLines, Format::Csv]
+            .into_iter()
+            .find(|format| format.extension() == name)
+    }
+
+    // 根据文件名(可以带 .gz)判断格式
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_str()?;
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        if name.ends_with(".jsonl") {
+            Some(Format::JsonLines)
+        } else if name.ends_with(".csv") {
+            Some(Format::Csv)
+        } else {
+            None
+        }
+    }
+
+    // 编码成一行, 带换行符
+    fn encode(&self, record: &Record) -> io::Result<String> {
+        let line = match self {
+            Format::JsonLines => serde_json::to_string(record)?,
+            Format::Csv => RecordRow::from_record(record).to_csv(),
+        };
+        Ok(line + "\n")
+    }
+
+    fn decode(&self, line: &str) -> Option<RecordRow> {
+        match self {
+            Format::JsonLines => serde_json::from_str::<Record>(line)
+                .ok()
+                .map(|record| RecordRow::from_record(&record)),
+            Format::Csv => RecordRow::from_csv(line),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    pub dir: PathBuf,
+    pub prefix: String,
+    pub format: Format,
+    // 单个文件的大小上限(字节), None 表示不按大小滚动
+    pub max_bytes: Option<u64>,
+    // 是否按 UTC 日期滚动
+    pub daily: bool,
+    // 是否压缩换下来的旧段
+    pub gzip: bool,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            dir: PathBuf::from("."),
+            prefix: "metrics".to_string(),
+            format: Format::JsonLines,
+            max_bytes: None,
+            daily: true,
+            gzip: false,
+        }
+    }
+}
+
+// 正在写入的段
+struct Segment {
+    path: PathBuf,
+    file: File,
+    // 自 1970-01-01 起的 UTC 天数
+    day: u64,
+    size: u64,
+    rows: usize,
+}
+
+pub struct Recorder {
+    config: RecorderConfig,
+    current: Option<Segment>,
+}
+
+impl Recorder {
+    pub fn new(config: RecorderConfig) -> io::Result<Recorder> {
+        fs::create_dir_all(&config.dir)?;
+        Ok(Recorder {
+            config,
+            current: None,
+        })
+    }
+
+    // 正在写入的文件, 还没有写过记录时为 None
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|segment| segment.path.as_path())
+    }
+
+    // 追加一条记录, 需要时先滚动到新的段
+    pub fn record(&mut self, record: &Record) -> io::Result<()> {
+        let line = self.config.format.encode(record)?;
+        let day = record.timestamp / MILLIS_PER_DAY;
+        let rotate = match &self.current {
+            None => true,
+            Some(segment) => {
+                (self.config.daily && segment.day != day)
+                    || self.config.max_bytes.is_some_and(|max| {
+                        // ? 每个段至少写一条记录, 否则一条超过上限的记录会留下一个空段
+                        segment.rows > 0 && segment.size + line.len() as u64 > max
+                    })
+            }
+        };
+        if rotate {
+            self.close()?;
+            self.current = Some(self.open(day)?);
+        }
+        if let Some(segment) = self.current.as_mut() {
+            // * 每条记录直接写入文件, 进程被杀掉时最多丢失正在写的那一行
+            segment.file.write_all(line.as_bytes())?;
+            segment.size += line.len() as u64;
+            segment.rows += 1;
+        }
+        Ok(())
+    }
+
+    // 结束当前段, 开启了压缩时它也会被压缩
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if let Some(segment) = self.current.take() {
+            segment.file.sync_all()?;
+            drop(segment.file);
+            if self.config.gzip {
+                compress(&segment.path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 当天第一个没有被占用的序号, 已经压缩的段也算占用
+    // ! 每次启动都从新的段开始, 不会追加到上一次运行留下的文件里
+    fn open(&self, day: u64) -> io::Result<Segment> {
+        let extension = self.config.format.extension();
+        let mut sequence = 0;
+        let path = loop {
+            let path = self.config.dir.join(format!(
+                "{}-{}-{:03}.{}",
+                self.config.prefix,
+                format_day(day),
+                sequence,
+                extension
+            ));
+            if !path.exists() && !gz_path(&path).exists() {
+                break path;
+            }
+            sequence += 1;
+        };
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let mut size = 0;
+        if self.config.format == Format::Csv {
+            writeln!(file, "{}", CSV_HEADER)?;
+            size = CSV_HEADER.len() as u64 + 1;
+        }
+        Ok(Segment {
+            path,
+            file,
+            day,
+            size,
+            rows: 0,
+        })
+    }
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+// 压缩成 <path>.gz 并删除原文件
+pub fn compress(path: &Path) -> io::Result<PathBuf> {
+    let target = gz_path(path);
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(File::create(&target)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)?;
+    Ok(target)
+}
+
+// 读回来的记录
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    // 按时间升序
+    pub rows: Vec<RecordRow>,
+    pub files: usize,
+    // 无法解析的行数, 例如进程被杀掉时写了一半的最后一行
+    pub skipped: usize,
+}
+
+// 读取目录下所有前缀匹配的段, 包括压缩过的
+pub fn read_recording(dir: &Path, prefix: &str) -> io::Result<Recording> {
+    let mut recording = Recording::default();
+    let start = format!("{}-", prefix);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matches = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&start));
+        let format = match Format::from_path(&path) {
+            Some(format) if matches => format,
+            _ => continue,
+        };
+        let file = File::open(&path)?;
+        let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        recording.files += 1;
+        for line in BufReader::new(reader).lines() {
+            // ? 压缩文件被截断时解压会出错, 把已经读出的部分留下, 剩余部分算作一行无法解析
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => {
+                    recording.skipped += 1;
+                    break;
+                }
+            };
+            if line.trim().is_empty() || line.starts_with("timestamp,") {
+                continue;
+            }
+            match format.decode(&line) {
+                Some(row) => recording.rows.push(row),
+                None => recording.skipped += 1,
+            }
+        }
+    }
+    recording.rows.sort_by_key(|row| row.timestamp);
+    Ok(recording)
+}
+
+// 自 1970-01-01 起的天数 -> (年, 月, 日), 公历
+// ? 算法来自 Howard Hinnant 的 civil_from_days, 以 3 月 1 日作为一年的开始, 闰日落在年末
+pub fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// (年, 月, 日) -> 自 1970-01-01 起的天数, civil_from_days 的逆运算
+// 1970 年之前的日期返回 None
+pub fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe).checked_sub(719_468)
+}
+
+// 文件名中的日期 "20261019"
+pub fn format_day(day: u64) -> String {
+    let (year, month, day) = civil_from_days(day);
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+const USAGE: &str = "用法: record <目录> [--format jsonl|csv] [--interval 毫秒] [--max-size 字节] [--no-daily] [--gzip] [--prefix 前缀]";
+
+// 解析 record 的参数, 返回配置和采样间隔(毫秒)
+// ! --interval 和 --max-size 的值写错时直接报错, 而不是悄悄退回默认值: 打错一位数的 --max-size 会让文件无限增长
+pub fn parse_args(args: &[String]) -> Result<(RecorderConfig, u64), String> {
+    let mut config = RecorderConfig::default();
+    let mut interval = 1000;
+    let mut args = args.iter();
+    config.dir = PathBuf::from(args.next().ok_or("缺少记录目录")?);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} 缺少取值", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                let name = value()?;
+                config.format = Format::from_name(&name).ok_or_else(|| {
+                    format!("未知的格式 {:?}, --format 只能是 jsonl 或 csv", name)
+                })?;
+            }
+            "--interval" => {
+                let text = value()?;
+                interval = match text.parse::<u64>() {
+                    Ok(ms) if ms > 0 => ms,
+                    _ => return Err(format!("--interval 需要大于 0 的毫秒数, 得到 {:?}", text)),
+                };
+            }
+            "--max-size" => {
+                let text = value()?;
+                let bytes = text
+                    .parse::<u64>()
+                    .map_err(|_| format!("--max-size 需要字节数, 得到 {:?}", text))?;
+                config.max_bytes = Some(bytes);
+            }
+            "--no-daily" => config.daily = false,
+            "--gzip" => config.gzip = true,
+            "--prefix" => config.prefix = value()?,
+            other => eprintln!("忽略未知参数 {}", other),
+        }
+    }
+    Ok((config, interval))
+}
+
+// 子命令入口: record <目录> [--format jsonl|csv] [--interval 毫秒] [--max-size 字节] [--no-daily] [--gzip] [--prefix 前缀]
+// 采样失败时不写记录, 报表中表现为一段空缺
+pub async fn record_main(args: &[String]) {
+    let (config, interval) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            return;
+        }
+    };
+    let mut recorder = match Recorder::new(config) {
+        Ok(recorder) => recorder,
+        Err(err) => {
+            eprintln!("创建记录目录失败: {}", err);
+            return;
+        }
+    };
+    let mut metrics = SampledMetrics::new(60, StatusConfig::default());
+    let sampler = run_sampler(Duration::from_millis(interval), |sample, timestamp| {
+        metrics.update(sample, timestamp);
+        let snapshot = match &metrics.snapshot {
+            Some(snapshot) => snapshot.clone(),
+            None => {
+                if let Some(err) = &metrics.last_error {
+                    eprintln!("采样失败: {}", err);
+                }
+                return;
+            }
+        };
+        let record = Record {
+            timestamp,
+            status: metrics.classifier.status(),
+            snapshot,
+        };
+        let previous = recorder.current_path().map(Path::to_path_buf);
+        if let Err(err) = recorder.record(&record) {
+            eprintln!("写入记录失败: {}", err);
+        }
+        // 每换一个段打印一次, 方便知道数据写到了哪里
+        if let Some(path) = recorder.current_path() {
+            if previous.as_deref() != Some(path) {
+                println!("写入 {}", path.display());
+            }
+        }
+    });
+    // ! 收到 Ctrl-C 后结束当前段, 否则开启 --gzip 时最后一段不会被压缩
+    tokio::select! {
+        _ = sampler => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    if let Err(err) = recorder.finish() {
+        eprintln!("关闭记录文件失败: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::{Cpu, LoadAverage, Memory, Swap};
+
+    // 2026-10-19 00:00 UTC
+    const DAY: u64 = 20_745;
+
+    // 每个测试一个独立的临时目录
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("recorder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn record(timestamp: u64, usage: f32) -> Record {
+        Record {
+            timestamp,
+            status: CpuStatus::Normal,
+            snapshot: SystemSnapshot {
+                cpu: Cpu {
+                    count: 4,
+                    usage,
+                    ..Default::default()
+                },
+                memory: Memory {
+                    total: 8_000,
+                    used: 2_000,
+                    available: 6_000,
+                    container: None,
+                },
+                swap: Swap {
+                    total: 100,
+                    used: 10,
+                    free: 90,
+                },
+                load_average: LoadAverage {
+                    one: 1.25,
+                    five: 0.5,
+                    fifteen: 0.25,
+                },
+                uptime: 3600.0,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let row = RecordRow::from_record(&record(DAY * MILLIS_PER_DAY, 37.5));
+        let line = row.to_csv();
+        assert_eq!(
+            line,
+            "1792368000000,normal,37.50,0.00,4,8000,2000,10,1.25,0.50,0.25,3600"
+        );
+        assert_eq!(RecordRow::from_csv(&line), Some(row.clone()));
+        assert_eq!(RecordRow::from_csv(&format!("{}\r\n", line)), Some(row));
+        assert_eq!(CSV_HEADER.split(',').count(), 12);
+        // 列数不对、状态未知、数字写坏的行都拒绝
+        assert_eq!(RecordRow::from_csv("1,normal,2"), None);
+        assert_eq!(
+            RecordRow::from_csv(&line.replace("normal", "melting")),
+            None
+        );
+        assert_eq!(RecordRow::from_csv(&line.replace("8000", "8k")), None);
+        assert_eq!(RecordRow::from_csv(CSV_HEADER), None);
+    }
+
+    #[test]
+    fn size_rotation() {
+        let dir = temp_dir("size");
+        let start = DAY * MILLIS_PER_DAY;
+        let line = Format::Csv.encode(&record(start, 10.0)).unwrap().len() as u64;
+        let config = RecorderConfig {
+            dir: dir.clone(),
+            format: Format::Csv,
+            // 表头加两行刚好放得下, 第三行换新段
+            max_bytes: Some(CSV_HEADER.len() as u64 + 1 + 2 * line),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new(config).unwrap();
+        for index in 0..5 {
+            recorder.record(&record(start + index, 10.0)).unwrap();
+        }
+        assert!(recorder
+            .current_path()
+            .unwrap()
+            .ends_with("metrics-20261019-002.csv"));
+        recorder.finish().unwrap();
+        assert_eq!(
+            names(&dir),
+            [
+                "metrics-20261019-000.csv",
+                "metrics-20261019-001.csv",
+                "metrics-20261019-002.csv"
+            ]
+        );
+        let first = fs::read_to_string(dir.join("metrics-20261019-000.csv")).unwrap();
+        assert_eq!(first.lines().count(), 3);
+        assert!(first.starts_with(CSV_HEADER));
+
+        // 一条记录本身就超过上限时也要写进去, 不会留下空段
+        let mut recorder = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            prefix: "tiny".to_string(),
+            max_bytes: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        recorder.record(&record(start, 1.0)).unwrap();
+        recorder.record(&record(start + 1, 2.0)).unwrap();
+        recorder.finish().unwrap();
+        let recording = read_recording(&dir, "tiny").unwrap();
+        assert_eq!((recording.files, recording.rows.len()), (2, 2));
+
+        // 再次启动时从下一个没被占用的序号开始
+        let mut recorder = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            format: Format::Csv,
+            ..Default::default()
+        })
+        .unwrap();
+        recorder.record(&record(start, 1.0)).unwrap();
+        assert!(recorder
+            .current_path()
+            .unwrap()
+            .ends_with("metrics-20261019-003.csv"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn daily_rotation() {
+        let dir = temp_dir("daily");
+        let mut recorder = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let midnight = (DAY + 1) * MILLIS_PER_DAY;
+        recorder.record(&record(midnight - 1, 1.0)).unwrap();
+        recorder.record(&record(midnight, 2.0)).unwrap();
+        recorder.record(&record(midnight + 1, 3.0)).unwrap();
+        recorder.finish().unwrap();
+        assert_eq!(
+            names(&dir),
+            ["metrics-20261019-000.jsonl", "metrics-20261020-000.jsonl"]
+        );
+
+        // --no-daily 时跨天也写在同一个文件里
+        let mut recorder = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            prefix: "flat".to_string(),
+            daily: false,
+            ..Default::default()
+        })
+        .unwrap();
+        recorder.record(&record(midnight - 1, 1.0)).unwrap();
+        recorder.record(&record(midnight, 2.0)).unwrap();
+        recorder.finish().unwrap();
+        let recording = read_recording(&dir, "flat").unwrap();
+        assert_eq!((recording.files, recording.rows.len()), (1, 2));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn gzip_rotated_segments() {
+        let dir = temp_dir("gzip");
+        let start = DAY * MILLIS_PER_DAY;
+        let line = Format::JsonLines
+            .encode(&record(start, 10.0))
+            .unwrap()
+            .len() as u64;
+        let mut recorder = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            max_bytes: Some(line),
+            gzip: true,
+            ..Default::default()
+        })
+        .unwrap();
+        for index in 0..3 {
+            recorder
+                .record(&record(start + index, index as f32))
+                .unwrap();
+        }
+        // 换下来的段已经压缩, 正在写的段还是明文
+        assert_eq!(
+            names(&dir),
+            [
+                "metrics-20261019-000.jsonl.gz",
+                "metrics-20261019-001.jsonl.gz",
+                "metrics-20261019-002.jsonl"
+            ]
+        );
+        recorder.finish().unwrap();
+        assert!(dir.join("metrics-20261019-002.jsonl.gz").exists());
+        assert!(!dir.join("metrics-20261019-002.jsonl").exists());
+
+        // 跨越压缩段读回, 按时间排序
+        let recording = read_recording(&dir, "metrics").unwrap();
+        assert_eq!((recording.files, recording.skipped), (3, 0));
+        let usages: Vec<f32> = recording.rows.iter().map(|row| row.cpu_usage).collect();
+        assert_eq!(usages, [0.0, 1.0, 2.0]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_truncated_recording() {
+        let dir = temp_dir("truncated");
+        fs::create_dir_all(&dir).unwrap();
+        let start = DAY * MILLIS_PER_DAY;
+        // 压缩过的旧段
+        let old = dir.join("metrics-20261019-000.jsonl");
+        let lines: String = (0..2)
+            .map(|index| {
+                Format::JsonLines
+                    .encode(&record(start + index, 1.0))
+                    .unwrap()
+            })
+            .collect();
+        fs::write(&old, lines).unwrap();
+        compress(&old).unwrap();
+        // 进程被杀掉时最后一行只写了一半
+        let last = Format::JsonLines.encode(&record(start + 5, 2.0)).unwrap();
+        let partial = Format::JsonLines.encode(&record(start + 6, 3.0)).unwrap();
+        fs::write(
+            dir.join("metrics-20261019-001.jsonl"),
+            format!("{}{}", last, &partial[..partial.len() / 2]),
+        )
+        .unwrap();
+        // CSV 段和其他前缀的文件混在同一个目录里
+        fs::write(
+            dir.join("metrics-20261019-002.csv"),
+            format!(
+                "{}\n{}\n",
+                CSV_HEADER,
+                RecordRow::from_record(&record(start + 3, 4.0)).to_csv()
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("other-20261019-000.jsonl"), "garbage\n").unwrap();
+        fs::write(dir.join("metrics-notes.txt"), "garbage\n").unwrap();
+
+        let recording = read_recording(&dir, "metrics").unwrap();
+        assert_eq!((recording.files, recording.skipped), (3, 1));
+        let timestamps: Vec<u64> = recording
+            .rows
+            .iter()
+            .map(|row| row.timestamp - start)
+            .collect();
+        assert_eq!(timestamps, [0, 1, 3, 5]);
+
+        // 压缩文件被截断时, 已经解压出的行保留下来
+        let gz = dir.join("metrics-20261019-000.jsonl.gz");
+        let bytes = fs::read(&gz).unwrap();
+        fs::write(&gz, &bytes[..bytes.len() - 12]).unwrap();
+        let recording = read_recording(&dir, "metrics").unwrap();
+        assert_eq!(recording.files, 3);
+        assert!(recording.skipped >= 2);
+        assert!(recording.rows.iter().any(|row| row.timestamp == start + 3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_088), (2024, 12, 31));
+        assert_eq!(civil_from_days(20_089), (2025, 1, 1));
+        assert_eq!(format_day(DAY), "20261019");
+        for days in 0..200_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), Some(days));
+        }
+        assert_eq!(days_from_civil(1969, 12, 31), None);
+        assert_eq!(days_from_civil(2026, 13, 1), None);
+        assert_eq!(days_from_civil(2026, 10, 0), None);
+    }
+
+    #[test]
+    fn usage_errors() {
+        let (config, interval) = parse_args(&args(&[
+            "out",
+            "--format",
+            "csv",
+            "--max-size",
+            "4096",
+            "--interval",
+            "250",
+        ]))
+        .unwrap();
+        assert_eq!(config.dir, PathBuf::from("out"));
+        assert_eq!(config.format, Format::Csv);
+        assert_eq!(config.max_bytes, Some(4096));
+        assert_eq!(interval, 250);
+
+        let (config, interval) = parse_args(&args(&["out"])).unwrap();
+        assert_eq!((config.max_bytes, interval), (None, 1000));
+
+        for bad in [
+            &["out", "--max-size", "10M"][..],
+            &["out", "--max-size", "-1"],
+            &["out", "--max-size"],
+            &["out", "--interval", "fast"],
+            &["out", "--interval", "0"],
+            &["out", "--format", "xml"],
+            &[],
+        ] {
+            assert!(parse_args(&args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// 记录报表
+// 读取 record 子命令写下的文件, 对一段时间内的采样做汇总, 用于容量评估:
+// CPU、最忙核心、内存和 1 分钟负载的分布(最小/平均/百分位/最大), 以及各状态所占的比例
+// 时间范围可以是毫秒级 unix 时间戳、UTC 日期 "2026-10-19", 或者 "7d" 这样相对现在的时长
+// ? --to 不包含终点, 只给日期时包含这一整天: --from 2026-10-19 --to 2026-10-19 是这一天的全部采样
+use super::history::{now_millis, parse_duration, UsageStats};
+use super::process::format_bytes;
+use super::recorder::{days_from_civil, read_recording, RecordRow};
+use super::status::CpuStatus;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    // 范围内第一个和最后一个采样的时间戳
+    pub first: u64,
+    pub last: u64,
+    pub samples: usize,
+    // 无法解析而跳过的行数
+    pub skipped: usize,
+    pub cpu_usage: UsageStats,
+    pub max_core_usage: UsageStats,
+    pub memory_used_percent: UsageStats,
+    pub load_one: UsageStats,
+    // 范围内内存用量的最大值(字节)
+    pub peak_memory_used: u64,
+    // 各状态的采样占比, 单位百分比
+    pub status: BTreeMap<CpuStatus, f32>,
+}
+
+impl Report {
+    // rows 需要按时间升序, 范围是 [from, to), 包含 from 不包含 to; 范围内没有采样时返回 None
+    pub fn build(rows: &[RecordRow], from: Option<u64>, to: Option<u64>) -> Option<Report> {
+        let rows: Vec<&RecordRow> = rows
+            .iter()
+            .filter(|row| from.is_none_or(|from| row.timestamp >= from))
+            .filter(|row| to.is_none_or(|to| row.timestamp < to))
+            .collect();
+        let stats = |value: fn(&RecordRow) -> f32| {
+            UsageStats::from_values(&rows.iter().map(|row| value(row)).collect::<Vec<_>>())
+        };
+        let mut status = BTreeMap::new();
+        for row in &rows {
+            *status.entry(row.status).or_insert(0.0) += 100.0 / rows.len() as f32;
+        }
+        Some(Report {
+            first: rows.first()?.timestamp,
+            last: rows.last()?.timestamp,
+            samples: rows.len(),
+            skipped: 0,
+            cpu_usage: stats(|row| row.cpu_usage)?,
+            max_core_usage: stats(|row| row.max_core_usage)?,
+            memory_used_percent: stats(RecordRow::memory_used_percent)?,
+            load_one: stats(|row| row.load_one)?,
+            peak_memory_used: rows.iter().map(|row| row.memory_used).max()?,
+            status,
+        })
+    }
+}
+
+// 解析时间范围的起点, now 是当前的毫秒级时间戳
+pub fn parse_time(text: &str, now: u64) -> Option<u64> {
+    // 纯数字是毫秒级时间戳
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) {
+        return text.parse().ok();
+    }
+    // UTC 日期, 取当天零点
+    if let Some(days) = parse_date(text) {
+        return Some(days * MILLIS_PER_DAY);
+    }
+    // 相对现在的时长
+    let ago = parse_duration(text)?;
+    Some(now.saturating_sub(ago.as_millis() as u64))
+}
+
+// 解析时间范围的终点(不包含)
+// 只给日期时指的是这一整天, 所以取第二天零点; 时间戳和时长与起点相同
+pub fn parse_end_time(text: &str, now: u64) -> Option<u64> {
+    match parse_date(text) {
+        Some(days) => Some((days + 1) * MILLIS_PER_DAY),
+        None => parse_time(text, now),
+    }
+}
+
+// "2026-10-19" 这样的 UTC 日期, 返回 1970-01-01 以来的天数
+fn parse_date(text: &str) -> Option<u64> {
+    let parts: Vec<&str> = text.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return None;
+    };
+    days_from_civil(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+}
+
+fn print_stats(name: &str, stats: &UsageStats, unit: &str) {
+    println!(
+        "{:<16} min {:>7.2}{u}  mean {:>7.2}{u}  p50 {:>7.2}{u}  p95 {:>7.2}{u}  p99 {:>7.2}{u}  max {:>7.2}{u}",
+        name,
+        stats.min,
+        stats.mean,
+        stats.p50,
+        stats.p95,
+        stats.p99,
+        stats.max,
+        u = unit
+    );
+}
+
+pub fn print_report(report: &Report) {
+    let hours = (report.last - report.first) as f64 / 3_600_000.0;
+    println!(
+        "{} 个采样, {} ~ {} ({:.1} 小时), 跳过 {} 行",
+        report.samples, report.first, report.last, hours, report.skipped
+    );
+    print_stats("cpu usage", &report.cpu_usage, "%");
+    print_stats("max core usage", &report.max_core_usage, "%");
+    print_stats("memory used", &report.memory_used_percent, "%");
+    print_stats("load 1m", &report.load_one, "");
+    println!("内存用量峰值 {}", format_bytes(report.peak_memory_used));
+    for (status, percent) in &report.status {
+        println!("{:<16} {:>6.2}%", status.to_string(), percent);
+    }
+}
+
+// 子命令入口: report <目录> [--from 时间] [--to 时间] [--prefix 前缀] [--json]
+pub fn report_main(args: &[String]) {
+    let dir = match args.first() {
+        Some(dir) => dir,
+        None => {
+            eprintln!("用法: report <目录> [--from 时间] [--to 时间] [--prefix 前缀] [--json]");
+            return;
+        }
+    };
+    let now = now_millis();
+    let (mut from, mut to, mut prefix, mut json) = (None, None, "metrics".to_string(), false);
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--from" | "--to" => {
+                let value = rest.next().map(String::as_str).unwrap_or_default();
+                let time = if arg == "--from" {
+                    parse_time(value, now)
+                } else {
+                    parse_end_time(value, now)
+                };
+                let Some(time) = time else {
+                    eprintln!("无法解析时间 {:?}", value);
+                    return;
+                };
+                if arg == "--from" {
+                    from = Some(time);
+                } else {
+                    to = Some(time);
+                }
+            }
+            "--prefix" => prefix = rest.next().cloned().unwrap_or(prefix),
+            "--json" => json = true,
+            other => eprintln!("忽略未知参数 {}", other),
+        }
+    }
+    let recording = match read_recording(Path::new(dir), &prefix) {
+        Ok(recording) => recording,
+        Err(err) => {
+            eprintln!("读取记录失败: {}", err);
+            return;
+        }
+    };
+    let mut report = match Report::build(&recording.rows, from, to) {
+        Some(report) => report,
+        None => {
+            println!("{} 个文件中没有这段时间的采样", recording.files);
+            return;
+        }
+    };
+    report.skipped = recording.skipped;
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(err) => eprintln!("序列化失败: {}", err),
+        }
+    } else {
+        print_report(&report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(timestamp: u64) -> RecordRow {
+        RecordRow {
+            timestamp,
+            status: CpuStatus::Normal,
+            cpu_usage: 50.0,
+            max_core_usage: 80.0,
+            cpu_count: 4,
+            memory_total: 1000,
+            memory_used: 500,
+            swap_used: 0,
+            load_one: 1.0,
+            load_five: 1.0,
+            load_fifteen: 1.0,
+            uptime: 100.0,
+        }
+    }
+
+    #[test]
+    fn bare_date_covers_the_whole_day() {
+        let midnight = days_from_civil(2026, 10, 19).unwrap() * MILLIS_PER_DAY;
+        assert_eq!(parse_time("2026-10-19", 0), Some(midnight));
+        assert_eq!(
+            parse_end_time("2026-10-19", 0),
+            Some(midnight + MILLIS_PER_DAY)
+        );
+        // 时间戳和时长作为终点时不变
+        assert_eq!(parse_end_time("1234", 0), Some(1234));
+        assert_eq!(parse_end_time("1h", 7_200_000), Some(3_600_000));
+
+        let rows = [
+            row(midnight - 1),
+            row(midnight),
+            row(midnight + MILLIS_PER_DAY - 1),
+            row(midnight + MILLIS_PER_DAY),
+        ];
+        let report = Report::build(
+            &rows,
+            parse_time("2026-10-19", 0),
+            parse_end_time("2026-10-19", 0),
+        )
+        .unwrap();
+        assert_eq!(report.samples, 2);
+        assert_eq!(report.first, midnight);
+        assert_eq!(report.last, midnight + MILLIS_PER_DAY - 1);
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(parse_end_time("2026-13-01", 0), None);
+        assert_eq!(parse_end_time("2026-10", 0), None);
+    }
+}
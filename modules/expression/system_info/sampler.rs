@@ -0,0 +1,180 @@
+// 连续采样
+// get_cpu_info(time) 每次都要测两次再相减, 在监控循环里一半的测量都浪费了
+// 这里把采样做成一个 Stream: 每一次的测量既是本次采样的终点, 也是下一次采样的起点
+use super::backend::{default_backend, Backend};
+use super::SystemInfoError;
+use super::{Cpu, CpuMeasurement};
+use futures::stream::{self, Stream};
+use std::time::{Duration, Instant};
+
+struct SamplerState<B> {
+    backend: B,
+    interval: Duration,
+    // 上一次成功的测量, 作为下一次的基准
+    baseline: Option<CpuMeasurement>,
+    // 下一次测量的时间点
+    next_tick: Instant,
+}
+
+impl<B> SamplerState<B> {
+    // 等到下一个时间点
+    // ? 如果消费者处理得太慢, 错过的时间点直接跳过, 不会积压, 也不会连续补测
+    async fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next_tick > now {
+            futures_timer::Delay::new(self.next_tick - now).await;
+        }
+        let now = Instant::now();
+        while self.next_tick <= now {
+            self.next_tick += self.interval;
+        }
+    }
+}
+
+// 按固定间隔产生 Cpu 采样的 Stream
+// * 背压: Stream 是拉取式的, 只有消费者 poll 的时候才会测量, 不会在后台堆积采样
+// * 取消: 直接 drop 这个 Stream 即可, 没有后台任务需要清理
+// ! 测量失败时产出一个 Err, 之后继续采样; 基准保持为上一次成功的测量
+pub fn cpu_stream(interval: Duration) -> impl Stream<Item = Result<Cpu, SystemInfoError>> {
+    cpu_stream_with(default_backend(), interval)
+}
+
+// 使用指定后端的采样 Stream
+pub fn cpu_stream_with<B: Backend>(
+    backend: B,
+    interval: Duration,
+) -> impl Stream<Item = Result<Cpu, SystemInfoError>> {
+    let state = SamplerState {
+        backend,
+        interval,
+        baseline: None,
+        next_tick: Instant::now(),
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            // 第一个时间点就是现在, 所以基准测量不需要等待
+            state.wait().await;
+            let measurement = match CpuMeasurement::take_with(&state.backend).await {
+                Ok(measurement) => measurement,
+                Err(err) => return Some((Err(err), state)),
+            };
+            let cpu = state
+                .baseline
+                .as_ref()
+                .map(|earlier| measurement.since(earlier));
+            state.baseline = Some(measurement);
+            // 第一次测量只是基准, 不产出采样
+            if let Some(cpu) = cpu {
+                return Some((Ok(cpu), state));
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::process::ProcessTimes;
+    use crate::modules::expression::system_info::throughput::{
+        DiskCounters, FilesystemUsage, InterfaceCounters,
+    };
+    use crate::modules::expression::system_info::{CpuTimeSample, LoadAverage, Memory, Swap};
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    // 第 n 次测量时 user 累计 n² 秒, idle 累计 n 秒, 记录测量了多少次
+    // 于是第 n 次和第 n + 1 次之间 user 占 (2n + 1) / (2n + 2), 从采样的值就能看出基准是哪一次
+    #[derive(Clone, Default)]
+    struct CountingBackend {
+        measurements: Arc<AtomicU64>,
+    }
+
+    impl Backend for CountingBackend {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn cpu_times(&self) -> Result<(CpuTimeSample, Vec<CpuTimeSample>), SystemInfoError> {
+            let n = self.measurements.fetch_add(1, Ordering::SeqCst) + 1;
+            let sample = CpuTimeSample {
+                user: (n * n) as f64,
+                idle: n as f64,
+                ..Default::default()
+            };
+            Ok((sample.clone(), vec![sample]))
+        }
+
+        async fn logical_count(&self) -> Result<u64, SystemInfoError> {
+            Ok(1)
+        }
+
+        async fn memory(&self) -> Result<Memory, SystemInfoError> {
+            Err(SystemInfoError::unsupported("memory"))
+        }
+
+        async fn swap(&self) -> Result<Swap, SystemInfoError> {
+            Err(SystemInfoError::unsupported("swap"))
+        }
+
+        async fn load_average(&self) -> Result<LoadAverage, SystemInfoError> {
+            Err(SystemInfoError::unsupported("load_average"))
+        }
+
+        async fn uptime(&self) -> Result<f64, SystemInfoError> {
+            Err(SystemInfoError::unsupported("uptime"))
+        }
+
+        async fn processes(&self) -> Result<Vec<ProcessTimes>, SystemInfoError> {
+            Err(SystemInfoError::unsupported("processes"))
+        }
+
+        async fn disk_counters(&self) -> Result<Vec<DiskCounters>, SystemInfoError> {
+            Err(SystemInfoError::unsupported("disk_counters"))
+        }
+
+        async fn network_counters(&self) -> Result<Vec<InterfaceCounters>, SystemInfoError> {
+            Err(SystemInfoError::unsupported("network_counters"))
+        }
+
+        async fn filesystems(&self) -> Result<Vec<FilesystemUsage>, SystemInfoError> {
+            Err(SystemInfoError::unsupported("filesystems"))
+        }
+    }
+
+    // 3 个采样只测量 4 次, 每个采样的基准都是上一个采样的终点
+    #[tokio::test]
+    async fn consecutive_samples_share_baseline() {
+        let backend = CountingBackend::default();
+        let measurements = backend.measurements.clone();
+        let samples: Vec<Cpu> = cpu_stream_with(backend, Duration::from_millis(10))
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(measurements.load(Ordering::SeqCst), 4);
+        let user: Vec<f32> = samples.iter().map(|cpu| cpu.times.user).collect();
+        assert_eq!(user, [75.0, 500.0 / 6.0, 87.5]);
+        assert_eq!(samples[2].cores[0].times.user, 87.5);
+    }
+
+    // drop 之后不再测量; 消费者处理得慢时错过的时间点直接跳过, 不会补测
+    #[tokio::test]
+    async fn drop_cancels_sampling() {
+        let interval = Duration::from_millis(10);
+        let backend = CountingBackend::default();
+        let measurements = backend.measurements.clone();
+        let mut samples = Box::pin(cpu_stream_with(backend, interval));
+        samples.next().await.unwrap().unwrap();
+        assert_eq!(measurements.load(Ordering::SeqCst), 2);
+
+        futures_timer::Delay::new(interval * 5).await;
+        assert_eq!(measurements.load(Ordering::SeqCst), 2);
+        samples.next().await.unwrap().unwrap();
+        assert_eq!(measurements.load(Ordering::SeqCst), 3);
+
+        drop(samples);
+        futures_timer::Delay::new(interval * 5).await;
+        assert_eq!(measurements.load(Ordering::SeqCst), 3);
+    }
+}
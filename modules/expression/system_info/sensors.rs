@@ -0,0 +1,408 @@
+// 温度和 CPU 频率
+// 使用率很高但构建还是很慢时, 常见的原因是 CPU 过热降频: 核心都在 100%, 频率却只有标称的一半
+// 这里从 sysfs 读取:
+//   * /sys/class/thermal/thermal_zoneN: type, temp(千分之一摄氏度), trip_point_K_type/temp 中的 critical
+//   * /sys/class/hwmon/hwmonN: name, tempK_input, tempK_label, tempK_max, tempK_crit
+//   * /sys/devices/system/cpu/cpuN/cpufreq: scaling_cur_freq, cpuinfo_max_freq(kHz)
+// 虚拟机、容器和很多 ARM 板子上这些目录都不存在, 这时对应的读数为 None(不可用), 而不是错误
+// 设置 SYSTEM_INFO_SYSFS_ROOT 可以把 sysfs 根目录指向别处(例如 fixtures/sys)
+use super::error::{blocking, SystemInfoError};
+use super::status::CpuStatus;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 没有 critical/max 温度时, 超过这个温度就认为接近降频
+const HOT_CELSIUS: f32 = 90.0;
+// 距离上限不到这么多度就认为接近降频
+const LIMIT_MARGIN: f32 = 5.0;
+// CPU 忙碌时平均频率低于最大频率的这个比例, 就认为在降频
+const LOW_FREQUENCY_RATIO: f32 = 0.7;
+
+// 一个温度传感器
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Temperature {
+    // 来源, 例如 "thermal_zone0" 或 "hwmon1/temp2"
+    pub source: String,
+    // 传感器名, 例如 "x86_pkg_temp" 或 "coretemp: Package id 0"
+    pub label: String,
+    pub celsius: f32,
+    // 硬件给出的上限, 没有时为 None
+    pub max: Option<f32>,
+    pub critical: Option<f32>,
+}
+
+impl Temperature {
+    // 最近的一个上限: max 比 critical 先触发降频
+    pub fn limit(&self) -> Option<f32> {
+        self.max.or(self.critical)
+    }
+}
+
+// 一个核心的频率, 单位 MHz
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoreFrequency {
+    pub cpu: u32,
+    pub current: f32,
+    // 硬件支持的最大频率, 读不到时为 None
+    pub max: Option<f32>,
+}
+
+// 传感器读数
+// ! None 表示这台机器上没有这类传感器, 和 "读到了但是值为 0" 不同
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Sensors {
+    pub temperatures: Option<Vec<Temperature>>,
+    pub frequencies: Option<Vec<CoreFrequency>>,
+}
+
+// 降频提示
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ThrottleHint {
+    // 某个传感器接近或超过上限
+    NearLimit {
+        label: String,
+        celsius: f32,
+        limit: f32,
+    },
+    // CPU 很忙但平均频率明显低于最大频率
+    LowFrequency {
+        ratio: f32,
+    },
+}
+
+impl fmt::Display for ThrottleHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThrottleHint::NearLimit {
+                label,
+                celsius,
+                limit,
+            } => write!(
+                f,
+                "{} 温度 {:.1}°C, 上限 {:.1}°C, 可能正在降频",
+                label, celsius, limit
+            ),
+            ThrottleHint::LowFrequency { ratio } => write!(
+                f,
+                "CPU 忙碌但平均频率只有最大频率的 {:.0}%, 可能正在降频",
+                ratio * 100.0
+            ),
+        }
+    }
+}
+
+impl Sensors {
+    // 从默认的 sysfs 读取
+    pub fn read() -> Sensors {
+        let root = std::env::var("SYSTEM_INFO_SYSFS_ROOT").unwrap_or("/sys".to_string());
+        Sensors::read_from(Path::new(&root))
+    }
+
+    // 在阻塞线程池里执行 read, 供 async 代码使用
+    pub async fn read_blocking() -> Result<Sensors, SystemInfoError> {
+        blocking("sysfs", || Ok(Sensors::read())).await
+    }
+
+    pub fn read_from(root: &Path) -> Sensors {
+        let mut temperatures = thermal_zones(&root.join("class/thermal"));
+        temperatures.extend(hwmon(&root.join("class/hwmon")));
+        let frequencies = cpufreq(&root.join("devices/system/cpu"));
+        Sensors {
+            temperatures: (!temperatures.is_empty()).then_some(temperatures),
+            frequencies: (!frequencies.is_empty()).then_some(frequencies),
+        }
+    }
+
+    // 最热的传感器
+    pub fn hottest(&self) -> Option<&Temperature> {
+        self.temperatures
+            .as_ref()?
+            .iter()
+            .max_by(|a, b| a.celsius.total_cmp(&b.celsius))
+    }
+
+    // 平均当前频率 / 平均最大频率, 没有最大频率时为 None
+    pub fn frequency_ratio(&self) -> Option<f32> {
+        let cores: Vec<&CoreFrequency> = self
+            .frequencies
+            .as_ref()?
+            .iter()
+            .filter(|core| core.max.is_some_and(|max| max > 0.0))
+            .collect();
+        if cores.is_empty() {
+            return None;
+        }
+        let current: f32 = cores.iter().map(|core| core.current).sum();
+        let max: f32 = cores.iter().filter_map(|core| core.max).sum();
+        Some(current / max)
+    }
+
+    // 结合 CPU 状态给出降频提示, 没有迹象或者传感器不可用时返回 None
+    // ? 频率低本身不说明问题, 空闲时降频是正常的节能行为, 所以只在 Busy 及以上时检查频率
+    pub fn throttle_hint(&self, status: CpuStatus) -> Option<ThrottleHint> {
+        let near_limit =
+            self.temperatures
+                .iter()
+                .flatten()
+                .find(|temperature| match temperature.limit() {
+                    Some(limit) => temperature.celsius >= limit - LIMIT_MARGIN,
+                    None => temperature.celsius >= HOT_CELSIUS,
+                });
+        if let Some(temperature) = near_limit {
+            return Some(ThrottleHint::NearLimit {
+                label: temperature.label.clone(),
+                celsius: temperature.celsius,
+                limit: temperature.limit().unwrap_or(HOT_CELSIUS),
+            });
+        }
+        if status < CpuStatus::Busy {
+            return None;
+        }
+        self.frequency_ratio()
+            .filter(|&ratio| ratio < LOW_FREQUENCY_RATIO)
+            .map(|ratio| ThrottleHint::LowFrequency { ratio })
+    }
+}
+
+// 读取一个数值文件, 不存在或者读不出来时为 None
+// ? 有些 thermal zone 的 temp 在读取时返回 EIO/ENODATA, 和不存在一样处理
+fn read_number(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    Some(text.trim().to_string())
+}
+
+// 千分之一摄氏度 -> 摄氏度
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    read_number(path).map(|value| (value / 1000.0) as f32)
+}
+
+// dir 下名为 "<prefix><数字>" 的子目录, 按数字排序(thermal_zone10 排在 thermal_zone2 之后)
+fn numbered_entries(dir: &Path, prefix: &str) -> Vec<(u32, PathBuf)> {
+    let mut entries: Vec<(u32, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let number = name.to_str()?.strip_prefix(prefix)?.parse().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    entries
+}
+
+fn thermal_zones(dir: &Path) -> Vec<Temperature> {
+    numbered_entries(dir, "thermal_zone")
+        .into_iter()
+        .filter_map(|(number, zone)| {
+            let celsius = read_millidegrees(&zone.join("temp"))?;
+            // 在 trip point 中找类型为 critical 的那个
+            let critical = (0..)
+                .map_while(|k| {
+                    read_text(&zone.join(format!("trip_point_{}_type", k))).map(|t| (k, t))
+                })
+                .find(|(_, kind)| kind == "critical")
+                .and_then(|(k, _)| read_millidegrees(&zone.join(format!("trip_point_{}_temp", k))));
+            Some(Temperature {
+                source: format!("thermal_zone{}", number),
+                label: read_text(&zone.join("type")).unwrap_or_default(),
+                celsius,
+                max: None,
+                critical,
+            })
+        })
+        .collect()
+}
+
+// 一个 hwmon 设备下可以有多个 tempK_input
+fn hwmon(dir: &Path) -> Vec<Temperature> {
+    let mut temperatures = Vec::new();
+    for (number, device) in numbered_entries(dir, "hwmon") {
+        let name = read_text(&device.join("name")).unwrap_or_default();
+        let mut inputs: Vec<u32> = fs::read_dir(&device)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                let name = name.to_str()?;
+                name.strip_prefix("temp")?
+                    .strip_suffix("_input")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        inputs.sort_unstable();
+        for k in inputs {
+            let file = |suffix: &str| device.join(format!("temp{}_{}", k, suffix));
+            let Some(celsius) = read_millidegrees(&file("input")) else {
+                continue;
+            };
+            let label = match read_text(&file("label")) {
+                Some(label) => format!("{}: {}", name, label),
+                None => name.clone(),
+            };
+            temperatures.push(Temperature {
+                source: format!("hwmon{}/temp{}", number, k),
+                label,
+                celsius,
+                max: read_millidegrees(&file("max")),
+                critical: read_millidegrees(&file("crit")),
+            });
+        }
+    }
+    temperatures
+}
+
+// kHz -> MHz
+fn cpufreq(dir: &Path) -> Vec<CoreFrequency> {
+    numbered_entries(dir, "cpu")
+        .into_iter()
+        .filter_map(|(cpu, core)| {
+            let freq = core.join("cpufreq");
+            let current = read_number(&freq.join("scaling_cur_freq"))?;
+            let max = read_number(&freq.join("cpuinfo_max_freq"));
+            Some(CoreFrequency {
+                cpu,
+                current: (current / 1000.0) as f32,
+                max: max.map(|max| (max / 1000.0) as f32),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> Sensors {
+        Sensors::read_from(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/sys"))
+    }
+
+    fn sensor(celsius: f32, max: Option<f32>) -> Sensors {
+        Sensors {
+            temperatures: Some(vec![Temperature {
+                source: "hwmon0/temp1".to_string(),
+                label: "coretemp: Package id 0".to_string(),
+                celsius,
+                max,
+                critical: None,
+            }]),
+            frequencies: None,
+        }
+    }
+
+    fn frequency(current: f32) -> Sensors {
+        Sensors {
+            temperatures: None,
+            frequencies: Some(vec![CoreFrequency {
+                cpu: 0,
+                current,
+                max: Some(1000.0),
+            }]),
+        }
+    }
+
+    #[test]
+    fn fixture_readings() {
+        let sensors = fixtures();
+        let temperatures = sensors.temperatures.as_ref().unwrap();
+        let sources: Vec<&str> = temperatures.iter().map(|t| t.source.as_str()).collect();
+        // thermal_zone1 没有 temp, 跳过; thermal_zone10 按数字排在 thermal_zone2 之后
+        assert_eq!(
+            sources,
+            [
+                "thermal_zone0",
+                "thermal_zone2",
+                "thermal_zone10",
+                "hwmon0/temp1",
+                "hwmon0/temp2",
+                "hwmon1/temp1"
+            ]
+        );
+        // critical 来自类型为 critical 的 trip point, 而不是第一个
+        assert_eq!(temperatures[0].critical, Some(105.0));
+        assert_eq!(temperatures[3].label, "coretemp: Package id 0");
+        assert_eq!(temperatures[3].limit(), Some(100.0));
+        assert_eq!(temperatures[5].label, "nvme: Composite");
+        assert_eq!(sensors.hottest().unwrap().celsius, 97.0);
+
+        let frequencies = sensors.frequencies.as_ref().unwrap();
+        assert_eq!(frequencies.len(), 2);
+        assert_eq!(
+            (frequencies[1].current, frequencies[1].max),
+            (1100.0, Some(4800.0))
+        );
+        assert_eq!(sensors.frequency_ratio(), Some(2300.0 / 9600.0));
+        // 第一个接近上限的是没有上限、按 HOT_CELSIUS 判断的 thermal_zone10
+        assert_eq!(
+            sensors.throttle_hint(CpuStatus::Idle),
+            Some(ThrottleHint::NearLimit {
+                label: "x86_pkg_temp".to_string(),
+                celsius: 97.0,
+                limit: HOT_CELSIUS,
+            })
+        );
+    }
+
+    // 目录不存在时读数是 None, 不是错误, 也不给出提示
+    #[test]
+    fn missing_directories_are_unavailable() {
+        let sensors = Sensors::read_from(Path::new("fixtures/sys/none"));
+        assert!(sensors.temperatures.is_none());
+        assert!(sensors.frequencies.is_none());
+        assert!(sensors.hottest().is_none());
+        assert_eq!(sensors.frequency_ratio(), None);
+        assert_eq!(sensors.throttle_hint(CpuStatus::Saturated), None);
+
+        // 只有 thermal, 没有 hwmon 和 cpufreq
+        let root = std::env::temp_dir().join(format!("sensors-{}", std::process::id()));
+        let zone = root.join("class/thermal/thermal_zone0");
+        fs::create_dir_all(&zone).unwrap();
+        fs::write(zone.join("temp"), "41000\n").unwrap();
+        let sensors = Sensors::read_from(&root);
+        assert_eq!(sensors.temperatures.unwrap()[0].celsius, 41.0);
+        assert!(sensors.frequencies.is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn temperature_thresholds() {
+        // 有上限时, 距离上限 LIMIT_MARGIN 度以内
+        assert!(sensor(95.0, Some(100.0))
+            .throttle_hint(CpuStatus::Idle)
+            .is_some());
+        assert_eq!(
+            sensor(94.9, Some(100.0)).throttle_hint(CpuStatus::Idle),
+            None
+        );
+        // 没有上限时按 HOT_CELSIUS
+        assert_eq!(
+            sensor(90.0, None).throttle_hint(CpuStatus::Idle),
+            Some(ThrottleHint::NearLimit {
+                label: "coretemp: Package id 0".to_string(),
+                celsius: 90.0,
+                limit: 90.0,
+            })
+        );
+        assert_eq!(sensor(89.9, None).throttle_hint(CpuStatus::Saturated), None);
+    }
+
+    #[test]
+    fn frequency_thresholds() {
+        assert_eq!(
+            frequency(690.0).throttle_hint(CpuStatus::Busy),
+            Some(ThrottleHint::LowFrequency { ratio: 0.69 })
+        );
+        assert_eq!(frequency(700.0).throttle_hint(CpuStatus::Saturated), None);
+        // 不忙时频率低是正常的节能
+        assert_eq!(frequency(300.0).throttle_hint(CpuStatus::Normal), None);
+    }
+}
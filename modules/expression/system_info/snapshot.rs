@@ -0,0 +1,551 @@
+// 快照保存和对比
+// 跑基准测试前后各拍一张快照, 对比两次的 CPU、内存、负载和占用最多的进程,
+// 用来证明两次测量时机器处在同样的状态(没有别的构建在跑、内存没有被吃掉一半)
+//   * snapshot save <文件>      采集一次完整快照写成 JSON
+//   * snapshot diff <a> <b>     逐项给出 前 -> 后、差值和变化百分比
+// 文件就是 SystemSnapshot 和 ProcessTable 的 serde 序列化结果, 可以直接用 jq 查看
+use super::history::now_millis;
+use super::process::{format_bytes, top_processes, ProcessInfo, ProcessTable};
+use super::{get_system_snapshot, SystemInfoError, SystemSnapshot};
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// 默认的容差: 变化不超过 10% 认为两次状态一致
+const DEFAULT_TOLERANCE: f64 = 10.0;
+// 低于这个值的 CPU 占比视为噪声, 0.1% -> 0.3% 虽然是 200% 的变化, 但对基准测试没有影响
+const NOISE_FLOOR: f64 = 1.0;
+
+// 保存到文件的快照
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedSnapshot {
+    // 拍摄时间, 毫秒级 unix 时间戳
+    pub timestamp: u64,
+    pub system: SystemSnapshot,
+    pub processes: ProcessTable,
+}
+
+impl SavedSnapshot {
+    // CPU 和进程在同一个 time 毫秒的窗口里测量
+    pub async fn capture(time: u64, n: usize) -> Result<SavedSnapshot, SystemInfoError> {
+        let (system, processes) =
+            futures::try_join!(get_system_snapshot(time), top_processes(time, n))?;
+        Ok(SavedSnapshot {
+            timestamp: now_millis(),
+            system,
+            processes,
+        })
+    }
+
+    // 写文件和 recorder 一样直接返回 io::Error; SystemInfoError 描述的是读取系统信息时的错误
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> Result<SavedSnapshot, SystemInfoError> {
+        let text = fs::read_to_string(path).map_err(|err| SystemInfoError::from_io(path, err))?;
+        serde_json::from_str(&text).map_err(|err| {
+            SystemInfoError::parse_with(path.display().to_string(), "不是快照文件", err)
+        })
+    }
+}
+
+// 数值的单位, 只影响显示
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    Percent,
+    Bytes,
+    Number,
+}
+
+impl Unit {
+    fn format(self, value: f64) -> String {
+        match self {
+            Unit::Percent => format!("{:.1}%", value),
+            Unit::Bytes if value < 0.0 => format!("-{}", format_bytes(-value as u64)),
+            Unit::Bytes => format_bytes(value as u64),
+            Unit::Number => format!("{:.2}", value),
+        }
+    }
+}
+
+// 一项读数的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub name: String,
+    pub unit: Unit,
+    pub before: f64,
+    pub after: f64,
+    pub delta: f64,
+    // 相对 before 的变化百分比, before 为 0 时为 None
+    pub percent: Option<f64>,
+}
+
+impl Change {
+    pub fn new(name: &str, unit: Unit, before: f64, after: f64) -> Change {
+        Change {
+            name: name.to_string(),
+            unit,
+            before,
+            after,
+            delta: after - before,
+            percent: (before != 0.0).then(|| (after - before) / before.abs() * 100.0),
+        }
+    }
+
+    // 是否超出容差
+    // ? 本身就是百分比的读数(CPU 使用率)按百分点比较, 其余按相对变化比较;
+    //   CPU 从 2% 到 3% 相对变化是 50%, 但只差 1 个百分点, 不应该算作状态不同
+    pub fn exceeds(&self, tolerance: f64) -> bool {
+        match (self.unit, self.percent) {
+            (Unit::Percent, _) => self.delta.abs() > tolerance,
+            (_, Some(percent)) => percent.abs() > tolerance,
+            (_, None) => self.after != 0.0,
+        }
+    }
+}
+
+// 对比的读数: 名称、单位和取值方式
+type Metric = (&'static str, Unit, fn(&SystemSnapshot) -> f64);
+
+// ? cpu usage 按核心平均(0 ~ 100): Cpu.usage 在 8 核机器上满载是 800, 按百分点比较时容差会被放大 8 倍
+const METRICS: [Metric; 11] = [
+    ("cpu usage", Unit::Percent, |s| {
+        s.cpu.per_core(s.cpu.usage) as f64
+    }),
+    ("cpu max core", Unit::Percent, |s| {
+        s.cpu.max_core_usage() as f64
+    }),
+    ("cpu iowait", Unit::Percent, |s| s.cpu.times.iowait as f64),
+    ("cpu count", Unit::Number, |s| s.cpu.count as f64),
+    ("memory total", Unit::Bytes, |s| s.memory.total as f64),
+    ("memory used", Unit::Bytes, |s| s.memory.used as f64),
+    ("memory available", Unit::Bytes, |s| {
+        s.memory.available as f64
+    }),
+    ("swap used", Unit::Bytes, |s| s.swap.used as f64),
+    ("load 1m", Unit::Number, |s| s.load_average.one as f64),
+    ("load 5m", Unit::Number, |s| s.load_average.five as f64),
+    ("load 15m", Unit::Number, |s| s.load_average.fifteen as f64),
+];
+
+// 同一个进程(pid 和名称都相同)在两次快照中的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessChange {
+    pub pid: u32,
+    pub name: String,
+    pub cpu_usage: Change,
+    pub rss: Change,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    // b 比 a 晚了多少毫秒
+    pub elapsed_ms: i64,
+    pub changes: Vec<Change>,
+    // 只出现在后一次排行中的进程, 和只出现在前一次排行中的进程
+    // ! 快照里只有排行前 n 的进程, "消失" 可能只是掉出了排行, 不一定已经退出
+    pub appeared: Vec<ProcessInfo>,
+    pub disappeared: Vec<ProcessInfo>,
+    pub processes: Vec<ProcessChange>,
+}
+
+// 排行中所有的进程, 按 CPU 和按内存两张表去重
+fn listed(table: &ProcessTable) -> Vec<&ProcessInfo> {
+    let mut seen = HashMap::new();
+    for process in table.by_cpu.iter().chain(&table.by_memory) {
+        seen.entry((process.pid, process.name.as_str()))
+            .or_insert(process);
+    }
+    let mut processes: Vec<&ProcessInfo> = seen.into_values().collect();
+    processes.sort_by_key(|process| process.pid);
+    processes
+}
+
+impl SnapshotDiff {
+    pub fn between(a: &SavedSnapshot, b: &SavedSnapshot) -> SnapshotDiff {
+        let (x, y) = (&a.system, &b.system);
+        let mut changes: Vec<Change> = METRICS
+            .iter()
+            .map(|(name, unit, value)| Change::new(name, *unit, value(x), value(y)))
+            .collect();
+        // 温度只在两次都读到时比较
+        if let (Some(before), Some(after)) = (x.sensors.hottest(), y.sensors.hottest()) {
+            changes.push(Change::new(
+                "hottest °C",
+                Unit::Number,
+                before.celsius as f64,
+                after.celsius as f64,
+            ));
+        }
+
+        let before = listed(&a.processes);
+        let after = listed(&b.processes);
+        let key = |process: &&ProcessInfo| (process.pid, process.name.clone());
+        let before_by_key: HashMap<_, _> = before.iter().map(|p| (key(p), *p)).collect();
+        let after_by_key: HashMap<_, _> = after.iter().map(|p| (key(p), *p)).collect();
+        let processes = after
+            .iter()
+            .filter_map(|later| {
+                let earlier = before_by_key.get(&key(later))?;
+                Some(ProcessChange {
+                    pid: later.pid,
+                    name: later.name.clone(),
+                    cpu_usage: Change::new(
+                        "cpu",
+                        Unit::Percent,
+                        earlier.cpu_usage as f64,
+                        later.cpu_usage as f64,
+                    ),
+                    rss: Change::new("rss", Unit::Bytes, earlier.rss as f64, later.rss as f64),
+                })
+            })
+            .collect();
+        SnapshotDiff {
+            elapsed_ms: b.timestamp as i64 - a.timestamp as i64,
+            changes,
+            appeared: after
+                .iter()
+                .filter(|p| !before_by_key.contains_key(&key(p)))
+                .map(|p| (*p).clone())
+                .collect(),
+            disappeared: before
+                .iter()
+                .filter(|p| !after_by_key.contains_key(&key(p)))
+                .map(|p| (*p).clone())
+                .collect(),
+            processes,
+        }
+    }
+
+    // 超出容差的读数
+    pub fn exceeded(&self, tolerance: f64) -> Vec<&Change> {
+        self.changes
+            .iter()
+            .filter(|change| change.exceeds(tolerance))
+            .collect()
+    }
+}
+
+fn format_percent(percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) => format!("{:+.1}%", percent),
+        None => "-".to_string(),
+    }
+}
+
+fn format_delta(unit: Unit, delta: f64) -> String {
+    let sign = if delta >= 0.0 { "+" } else { "" };
+    format!("{}{}", sign, unit.format(delta))
+}
+
+// 超出容差的行在行首标 "!"
+pub fn print_diff(diff: &SnapshotDiff, tolerance: f64) {
+    println!("两次快照相隔 {:.1} 秒", diff.elapsed_ms as f64 / 1000.0);
+    println!(
+        "  {:<18} {:>12} {:>12} {:>12} {:>9}",
+        "METRIC", "BEFORE", "AFTER", "DELTA", "CHANGE"
+    );
+    for change in &diff.changes {
+        println!(
+            "{} {:<18} {:>12} {:>12} {:>12} {:>9}",
+            if change.exceeds(tolerance) { "!" } else { " " },
+            change.name,
+            change.unit.format(change.before),
+            change.unit.format(change.after),
+            format_delta(change.unit, change.delta),
+            format_percent(change.percent)
+        );
+    }
+    println!();
+    println!(
+        "  {:>8} {:<16} {:>8} {:>8} {:>11} {:>11} {:>9}",
+        "PID", "NAME", "CPU%", "->", "RSS", "->", "RSS CHG"
+    );
+    for process in &diff.processes {
+        // 两次都很闲的进程不值得看
+        if process.cpu_usage.before.max(process.cpu_usage.after) < NOISE_FLOOR
+            && !process.rss.exceeds(tolerance)
+        {
+            continue;
+        }
+        println!(
+            "{} {:>8} {:<16} {:>8.1} {:>8.1} {:>11} {:>11} {:>9}",
+            if process.cpu_usage.exceeds(tolerance) || process.rss.exceeds(tolerance) {
+                "!"
+            } else {
+                " "
+            },
+            process.pid,
+            process.name,
+            process.cpu_usage.before,
+            process.cpu_usage.after,
+            format_bytes(process.rss.before as u64),
+            format_bytes(process.rss.after as u64),
+            format_percent(process.rss.percent)
+        );
+    }
+    for (title, processes) in [
+        ("新进入排行:", &diff.appeared),
+        ("掉出排行:", &diff.disappeared),
+    ] {
+        if processes.is_empty() {
+            continue;
+        }
+        println!("{}", title);
+        for process in processes {
+            println!(
+                "  {:>8} {:<16} {:>7.1}% {:>11}",
+                process.pid,
+                process.name,
+                process.cpu_usage,
+                format_bytes(process.rss)
+            );
+        }
+    }
+    println!();
+    let exceeded = diff.exceeded(tolerance);
+    if exceeded.is_empty() {
+        println!("两次状态一致(容差 {}%)", tolerance);
+    } else {
+        let names: Vec<&str> = exceeded.iter().map(|change| change.name.as_str()).collect();
+        println!(
+            "{} 项超出容差 {}%: {}",
+            exceeded.len(),
+            tolerance,
+            names.join(", ")
+        );
+    }
+}
+
+// 子命令入口:
+//   snapshot save <文件> [采样毫秒] [进程数]
+//   snapshot diff <a> <b> [--tolerance 百分比] [--json]
+pub async fn snapshot_main(args: &[String]) {
+    match (args.first().map(String::as_str), &args[args.len().min(1)..]) {
+        (Some("save"), [file, rest @ ..]) => {
+            let mut numbers = rest.iter().filter_map(|arg| arg.parse::<u64>().ok());
+            let time = numbers.next().unwrap_or(1000);
+            let n = numbers.next().unwrap_or(10) as usize;
+            let snapshot = match SavedSnapshot::capture(time, n).await {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    eprintln!("采集快照失败: {}", err);
+                    return;
+                }
+            };
+            match snapshot.save(Path::new(file)) {
+                Ok(()) => println!("快照已保存到 {}", file),
+                Err(err) => eprintln!("写入 {} 失败: {}", file, err),
+            }
+        }
+        (Some("diff"), [a, b, rest @ ..]) => {
+            let (mut tolerance, mut json) = (DEFAULT_TOLERANCE, false);
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--tolerance" => match rest.next().and_then(|value| value.parse().ok()) {
+                        Some(value) => tolerance = value,
+                        None => {
+                            eprintln!("--tolerance 需要一个数字");
+                            return;
+                        }
+                    },
+                    "--json" => json = true,
+                    other => eprintln!("忽略未知参数 {}", other),
+                }
+            }
+            let (a, b) = match (SavedSnapshot::load(Path::new(a)), SavedSnapshot::load(Path::new(b))) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(err), _) | (_, Err(err)) => {
+                    eprintln!("读取快照失败: {}", err);
+                    return;
+                }
+            };
+            let diff = SnapshotDiff::between(&a, &b);
+            if json {
+                match serde_json::to_string_pretty(&diff) {
+                    Ok(text) => println!("{}", text),
+                    Err(err) => eprintln!("序列化失败: {}", err),
+                }
+            } else {
+                print_diff(&diff, tolerance);
+            }
+        }
+        _ => eprintln!(
+            "用法: snapshot save <文件> [采样毫秒] [进程数]\n      snapshot diff <a> <b> [--tolerance 百分比] [--json]"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        let snapshot = SavedSnapshot {
+            timestamp: 1234,
+            ..SavedSnapshot::default()
+        };
+        snapshot.save(&path).unwrap();
+        assert_eq!(SavedSnapshot::load(&path).unwrap().timestamp, 1234);
+        fs::remove_file(&path).unwrap();
+    }
+
+    // 写入失败是 io::Error, 不会被说成 "读取" 失败
+    fn process(pid: u32, name: &str, cpu_usage: f32, rss: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            cmdline: String::new(),
+            user: None,
+            cpu_usage,
+            rss,
+        }
+    }
+
+    // 8 核机器, usage 是所有核心之和
+    fn saved(timestamp: u64, usage: f32, used: u64, processes: Vec<ProcessInfo>) -> SavedSnapshot {
+        let mut snapshot = SavedSnapshot {
+            timestamp,
+            ..SavedSnapshot::default()
+        };
+        snapshot.system.cpu.count = 8;
+        snapshot.system.cpu.usage = usage;
+        snapshot.system.memory.total = 16_000;
+        snapshot.system.memory.used = used;
+        snapshot.processes = ProcessTable::top(processes, 10);
+        snapshot
+    }
+
+    fn change<'a>(diff: &'a SnapshotDiff, name: &str) -> &'a Change {
+        diff.changes
+            .iter()
+            .find(|change| change.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn change_percent() {
+        let change = Change::new("x", Unit::Bytes, 200.0, 150.0);
+        assert_eq!(change.delta, -50.0);
+        assert_eq!(change.percent, Some(-25.0));
+        // 负数基准按绝对值计算, 变大始终是正的百分比
+        assert_eq!(
+            Change::new("x", Unit::Number, -2.0, -1.0).percent,
+            Some(50.0)
+        );
+        // 基准为 0 时没有百分比
+        assert_eq!(Change::new("x", Unit::Bytes, 0.0, 10.0).percent, None);
+    }
+
+    #[test]
+    fn exceeds() {
+        // 百分比读数按百分点比较: 2% -> 3% 只差 1 个百分点
+        assert!(!Change::new("cpu", Unit::Percent, 2.0, 3.0).exceeds(10.0));
+        assert!(Change::new("cpu", Unit::Percent, 20.0, 31.0).exceeds(10.0));
+        assert!(Change::new("cpu", Unit::Percent, 31.0, 20.0).exceeds(10.0));
+        // 刚好等于容差不算超出
+        assert!(!Change::new("cpu", Unit::Percent, 20.0, 30.0).exceeds(10.0));
+        // 其余按相对变化
+        assert!(!Change::new("mem", Unit::Bytes, 1000.0, 1090.0).exceeds(10.0));
+        assert!(Change::new("mem", Unit::Bytes, 1000.0, 850.0).exceeds(10.0));
+        // 基准为 0: 只要后一次不是 0 就算超出
+        assert!(!Change::new("swap", Unit::Bytes, 0.0, 0.0).exceeds(10.0));
+        assert!(Change::new("swap", Unit::Bytes, 0.0, 1.0).exceeds(10.0));
+        assert!(Change::new("cpu", Unit::Percent, 0.0, 11.0).exceeds(10.0));
+    }
+
+    #[test]
+    fn between() {
+        let a = saved(
+            1_000,
+            80.0,
+            4_000,
+            vec![
+                process(1, "init", 0.0, 100),
+                process(10, "build", 90.0, 5_000),
+                process(20, "old", 5.0, 200),
+            ],
+        );
+        let b = saved(
+            3_500,
+            160.0,
+            4_000,
+            vec![
+                process(1, "init", 0.0, 100),
+                process(10, "build", 95.0, 8_000),
+                // pid 被复用成了另一个进程
+                process(20, "new", 1.0, 300),
+            ],
+        );
+        let diff = SnapshotDiff::between(&a, &b);
+        assert_eq!(diff.elapsed_ms, 2_500);
+        assert_eq!(diff.changes.len(), METRICS.len());
+
+        // 8 核上 usage 80 -> 160 是平均每核 10% -> 20%
+        let cpu = change(&diff, "cpu usage");
+        assert_eq!((cpu.before, cpu.after, cpu.delta), (10.0, 20.0, 10.0));
+        assert!(!cpu.exceeds(DEFAULT_TOLERANCE));
+        assert!(cpu.exceeds(5.0));
+        let memory = change(&diff, "memory used");
+        assert_eq!(memory.percent, Some(0.0));
+        // 两次都是 0 的读数
+        let swap = change(&diff, "swap used");
+        assert_eq!(swap.percent, None);
+        assert!(!swap.exceeds(DEFAULT_TOLERANCE));
+        assert!(diff.exceeded(DEFAULT_TOLERANCE).is_empty());
+
+        // 两次都在排行里的进程, 按 pid 排序
+        let pids: Vec<u32> = diff.processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [1, 10]);
+        let build = &diff.processes[1];
+        assert_eq!(build.rss.percent, Some(60.0));
+        assert!(build.rss.exceeds(DEFAULT_TOLERANCE));
+        assert!(!build.cpu_usage.exceeds(DEFAULT_TOLERANCE));
+        // 只出现在一边的进程
+        let names = |list: &[ProcessInfo]| list.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&diff.appeared), ["new"]);
+        assert_eq!(names(&diff.disappeared), ["old"]);
+    }
+
+    #[test]
+    fn between_empty_snapshots() {
+        // 基准全是 0: 没有百分比, 也不会除以 0
+        let a = SavedSnapshot::default();
+        let b = saved(0, 8.0, 100, vec![process(7, "late", 50.0, 10)]);
+        let diff = SnapshotDiff::between(&a, &b);
+        assert_eq!(diff.elapsed_ms, 0);
+        assert!(diff.changes.iter().all(|change| change.percent.is_none()));
+        assert_eq!(change(&diff, "cpu usage").after, 1.0);
+        let exceeded: Vec<&str> = diff
+            .exceeded(DEFAULT_TOLERANCE)
+            .iter()
+            .map(|change| change.name.as_str())
+            .collect();
+        assert_eq!(exceeded, ["cpu count", "memory total", "memory used"]);
+        assert!(diff.processes.is_empty());
+        assert_eq!(diff.appeared.len(), 1);
+        assert!(diff.disappeared.is_empty());
+
+        // 反过来对比, 进程只在前一次出现
+        let diff = SnapshotDiff::between(&b, &a);
+        assert_eq!(diff.elapsed_ms, 0);
+        assert_eq!(diff.disappeared.len(), 1);
+        assert!(diff.appeared.is_empty());
+    }
+
+    #[test]
+    fn save_into_missing_directory() {
+        let path = std::env::temp_dir().join("snapshot-missing-dir/a.json");
+        let err = SavedSnapshot::default().save(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,257 @@
+// CPU状态分级
+// 原来只有 Idle/Busy 两档, 阈值写死为 10%, 使用率在 10% 上下抖动时状态就来回跳
+// 这里做了三件事:
+//   1. 分为 Idle/Normal/Busy/Saturated 四档, 阈值可配置
+//   2. 每一档有单独的进入/退出阈值(迟滞), 例如 60% 进入 Busy, 降到 50% 以下才退出
+//   3. 最短停留时间: 状态切换后至少保持 min_dwell 才允许再次切换
+// 状态切换会作为事件发给所有订阅者
+// 还没有采样或者采样失败时状态是 Unknown, 而不是把缺失的读数当成 0% 归为 Idle
+use futures::channel::mpsc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+// ? 派生 PartialOrd/Ord 后, 枚举按声明顺序比较大小, Unknown < Idle < Normal < Busy < Saturated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum CpuStatus {
+    // 没有可用的读数
+    Unknown,
+    Idle,
+    Normal,
+    Busy,
+    Saturated,
+}
+
+impl CpuStatus {
+    const LEVELS: [CpuStatus; 4] = [
+        CpuStatus::Idle,
+        CpuStatus::Normal,
+        CpuStatus::Busy,
+        CpuStatus::Saturated,
+    ];
+}
+
+impl fmt::Display for CpuStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CpuStatus::Unknown => "unknown",
+            CpuStatus::Idle => "idle",
+            CpuStatus::Normal => "normal",
+            CpuStatus::Busy => "busy",
+            CpuStatus::Saturated => "saturated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 一档的进入/退出阈值, 单位百分比
+// ! exit 应当不大于 enter, 两者之间就是不会引起切换的缓冲带
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Threshold {
+    pub enter: f32,
+    pub exit: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    pub normal: Threshold,
+    pub busy: Threshold,
+    pub saturated: Threshold,
+    // 任意一个核心超过这个值时, 状态至少是 Busy
+    pub hot_core: f32,
+    // 最短停留时间, 单位毫秒
+    pub min_dwell_ms: u64,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        StatusConfig {
+            // 和原来的 IDLE_USEAGE_PERCENT = 10.0 保持一致
+            normal: Threshold {
+                enter: 10.0,
+                exit: 8.0,
+            },
+            busy: Threshold {
+                enter: 60.0,
+                exit: 50.0,
+            },
+            saturated: Threshold {
+                enter: 90.0,
+                exit: 80.0,
+            },
+            hot_core: 90.0,
+            min_dwell_ms: 5_000,
+        }
+    }
+}
+
+impl StatusConfig {
+    // Idle 没有阈值, 永远可以进入; Unknown 不参与阈值判断
+    fn threshold(&self, status: CpuStatus) -> Option<&Threshold> {
+        match status {
+            CpuStatus::Unknown | CpuStatus::Idle => None,
+            CpuStatus::Normal => Some(&self.normal),
+            CpuStatus::Busy => Some(&self.busy),
+            CpuStatus::Saturated => Some(&self.saturated),
+        }
+    }
+
+    // 不考虑停留时间, 只根据阈值和当前状态得出目标状态
+    // ! usage 是平均每个核心的使用率(0 ~ 100), 不是 Cpu.usage, 后者在多核机器上会超过 100, 见 Cpu::per_core
+    pub fn target(&self, current: CpuStatus, usage: f32, max_core_usage: f32) -> CpuStatus {
+        let entered = |status: &CpuStatus| self.threshold(*status).is_none_or(|t| usage >= t.enter);
+        let kept = |status: &CpuStatus| self.threshold(*status).is_none_or(|t| usage >= t.exit);
+        // 往上走看 enter, 往下走看 exit
+        let up = CpuStatus::LEVELS.iter().rev().find(|s| entered(s)).copied();
+        let target = match up {
+            Some(up) if up > current => up,
+            _ => CpuStatus::LEVELS
+                .iter()
+                .rev()
+                .filter(|s| **s <= current)
+                .find(|s| kept(s))
+                .copied()
+                .unwrap_or(CpuStatus::Idle),
+        };
+        if max_core_usage >= self.hot_core {
+            target.max(CpuStatus::Busy)
+        } else {
+            target
+        }
+    }
+}
+
+// 一次状态切换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: CpuStatus,
+    pub to: CpuStatus,
+    // 触发切换的使用率, 切换到 Unknown 时为 NaN
+    pub usage: f32,
+    // 毫秒级 unix 时间戳
+    pub timestamp: u64,
+}
+
+pub struct StatusClassifier {
+    config: StatusConfig,
+    status: CpuStatus,
+    // 上一次切换的时间, 还没有切换过时为 None, 不受停留时间限制
+    last_change: Option<u64>,
+    subscribers: Vec<mpsc::UnboundedSender<StatusTransition>>,
+}
+
+impl StatusClassifier {
+    pub fn new(config: StatusConfig) -> StatusClassifier {
+        StatusClassifier {
+            config,
+            status: CpuStatus::Unknown,
+            last_change: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn status(&self) -> CpuStatus {
+        self.status
+    }
+
+    // 最近一次状态切换的时间戳, 还没有切换过时为 None
+    pub fn last_change(&self) -> Option<u64> {
+        self.last_change
+    }
+
+    pub fn config(&self) -> &StatusConfig {
+        &self.config
+    }
+
+    // 订阅状态切换事件, 返回的 Receiver 本身就是一个 Stream
+    // ? Receiver 被 drop 后, 下一次发送时会自动清理对应的订阅者
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<StatusTransition> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // 输入一次观测值(可以是单次采样, 也可以是 history 平滑后的值)
+    // 发生切换时返回切换事件, 同时通知所有订阅者
+    pub fn observe(
+        &mut self,
+        usage: f32,
+        max_core_usage: f32,
+        timestamp: u64,
+    ) -> Option<StatusTransition> {
+        let target = self.config.target(self.status, usage, max_core_usage);
+        if target == self.status {
+            return None;
+        }
+        let dwelled = self
+            .last_change
+            .is_none_or(|last| timestamp.saturating_sub(last) >= self.config.min_dwell_ms);
+        if !dwelled {
+            return None;
+        }
+        let transition = StatusTransition {
+            from: self.status,
+            to: target,
+            usage,
+            timestamp,
+        };
+        self.status = target;
+        self.last_change = Some(timestamp);
+        self.subscribers
+            .retain(|sender| sender.unbounded_send(transition.clone()).is_ok());
+        Some(transition)
+    }
+
+    // 读数不可用(采样失败), 立即切换到 Unknown
+    // ? 不受最短停留时间限制: 没有数据时继续显示旧状态反而会误导; 恢复采样后第一次 observe 也会立即切换
+    pub fn mark_unknown(&mut self, timestamp: u64) -> Option<StatusTransition> {
+        if self.status == CpuStatus::Unknown {
+            return None;
+        }
+        let transition = StatusTransition {
+            from: self.status,
+            to: CpuStatus::Unknown,
+            usage: f32::NAN,
+            timestamp,
+        };
+        self.status = CpuStatus::Unknown;
+        self.last_change = None;
+        self.subscribers
+            .retain(|sender| sender.unbounded_send(transition.clone()).is_ok());
+        Some(transition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::expression::system_info::provider::{ScriptedProvider, SystemInfoProvider};
+
+    // 迟滞和最短停留时间, 时钟来自脚本, 不需要真的等 5 秒
+    #[tokio::test]
+    async fn classifier_hysteresis_and_dwell() {
+        let mut provider =
+            ScriptedProvider::from_usages(&[5.0, 70.0, 70.0, 70.0, 70.0, 70.0, 55.0, 45.0]);
+        let mut classifier = StatusClassifier::new(StatusConfig::default());
+        let mut transitions = Vec::new();
+        while !provider.finished() {
+            let cpu = provider.cpu_info(1000).await.unwrap();
+            if let Some(transition) =
+                classifier.observe(cpu.usage, cpu.max_core_usage(), provider.now_millis())
+            {
+                transitions.push((transition.timestamp, transition.from, transition.to));
+            }
+        }
+        assert_eq!(
+            transitions,
+            vec![
+                (1_000, CpuStatus::Unknown, CpuStatus::Idle),
+                // 2000 起就超过了 60%, 但要在 Idle 停留满 5 秒
+                (6_000, CpuStatus::Idle, CpuStatus::Busy),
+            ]
+        );
+        // 45% 低于 Busy 的退出阈值, 但距离上次切换不到 5 秒
+        assert_eq!(classifier.status(), CpuStatus::Busy);
+    }
+}
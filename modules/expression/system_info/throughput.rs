@@ -0,0 +1,293 @@
+// 磁盘和网络吞吐
+// 构建变慢时只看 CPU 往往得不出结论, 还要看同一段时间里磁盘和网络在做什么
+// 和 get_cpu_info(time) 一样做两次测量, 累计值之差除以墙上时间得到速率:
+//   * 每个块设备的读写字节数和 IOPS
+//   * 每个网卡的收发字节数和包数
+// 文件系统的用量是当前值, 只读一次
+use super::backend::{default_backend, Backend};
+use super::error::with_timeout;
+use super::process::format_bytes;
+use super::{get_cpu_info, SystemInfoError, READ_TIMEOUT};
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// 后端读取的块设备累计值
+#[derive(Debug, Clone, Default)]
+pub struct DiskCounters {
+    pub device: String,
+    // 完成的读/写请求数
+    pub reads: u64,
+    pub writes: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+// 后端读取的网卡累计值
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceCounters {
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+// 一个块设备在采样间隔内的速率, 单位都是 "每秒"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskIo {
+    pub device: String,
+    pub read_bytes: f64,
+    pub write_bytes: f64,
+    pub read_iops: f64,
+    pub write_iops: f64,
+}
+
+// 一个网卡在采样间隔内的速率, 单位都是 "每秒"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkIo {
+    pub interface: String,
+    pub rx_bytes: f64,
+    pub tx_bytes: f64,
+    pub rx_packets: f64,
+    pub tx_packets: f64,
+}
+
+// 文件系统用量, 单位字节
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilesystemUsage {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub total: u64,
+    pub used: u64,
+    // 普通用户可用的空间, 不含为 root 保留的部分
+    pub free: u64,
+}
+
+impl FilesystemUsage {
+    // 和 df 的 Use% 口径一致: used / (used + free), 保留空间不计入分母
+    pub fn used_percent(&self) -> f32 {
+        let usable = self.used + self.free;
+        if usable == 0 {
+            0.0
+        } else {
+            (self.used as f64 / usable as f64 * 100.0) as f32
+        }
+    }
+}
+
+// 一次完整的 I/O 采样
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IoInfo {
+    pub disks: Vec<DiskIo>,
+    pub filesystems: Vec<FilesystemUsage>,
+    pub networks: Vec<NetworkIo>,
+}
+
+// 一次测量: 所有设备和网卡的累计值
+#[derive(Debug, Clone)]
+pub struct IoMeasurement {
+    at: Instant,
+    disks: Vec<DiskCounters>,
+    networks: Vec<InterfaceCounters>,
+}
+
+// 累计值之差 / 秒数
+// ? 网卡被重建或计数器回绕时后一次的值会变小, 这时按 0 处理, 不输出负的速率
+fn rate(later: u64, earlier: u64, elapsed: f64) -> f64 {
+    if elapsed > 0.0 {
+        later.saturating_sub(earlier) as f64 / elapsed
+    } else {
+        0.0
+    }
+}
+
+impl IoMeasurement {
+    pub async fn take_with<B: Backend>(backend: &B) -> Result<IoMeasurement, SystemInfoError> {
+        let (disks, networks) = with_timeout("读取磁盘和网络计数", READ_TIMEOUT, async {
+            futures::try_join!(backend.disk_counters(), backend.network_counters())
+        })
+        .await?;
+        Ok(IoMeasurement {
+            at: Instant::now(),
+            disks,
+            networks,
+        })
+    }
+
+    // 以 earlier 为基准计算速率, 只统计两次测量中都存在的设备和网卡
+    pub fn since(&self, earlier: &IoMeasurement) -> (Vec<DiskIo>, Vec<NetworkIo>) {
+        let elapsed = self.at.duration_since(earlier.at).as_secs_f64();
+        let disks_before: HashMap<&str, &DiskCounters> = earlier
+            .disks
+            .iter()
+            .map(|disk| (disk.device.as_str(), disk))
+            .collect();
+        let disks = self
+            .disks
+            .iter()
+            .filter_map(|later| {
+                let before = disks_before.get(later.device.as_str())?;
+                Some(DiskIo {
+                    device: later.device.clone(),
+                    read_bytes: rate(later.read_bytes, before.read_bytes, elapsed),
+                    write_bytes: rate(later.write_bytes, before.write_bytes, elapsed),
+                    read_iops: rate(later.reads, before.reads, elapsed),
+                    write_iops: rate(later.writes, before.writes, elapsed),
+                })
+            })
+            .collect();
+        let networks_before: HashMap<&str, &InterfaceCounters> = earlier
+            .networks
+            .iter()
+            .map(|network| (network.interface.as_str(), network))
+            .collect();
+        let networks = self
+            .networks
+            .iter()
+            .filter_map(|later| {
+                let before = networks_before.get(later.interface.as_str())?;
+                Some(NetworkIo {
+                    interface: later.interface.clone(),
+                    rx_bytes: rate(later.rx_bytes, before.rx_bytes, elapsed),
+                    tx_bytes: rate(later.tx_bytes, before.tx_bytes, elapsed),
+                    rx_packets: rate(later.rx_packets, before.rx_packets, elapsed),
+                    tx_packets: rate(later.tx_packets, before.tx_packets, elapsed),
+                })
+            })
+            .collect();
+        (disks, networks)
+    }
+}
+
+// 测量 time 毫秒内的磁盘和网络速率, 并读取文件系统用量
+pub async fn get_io_info(time: u64) -> Result<IoInfo, SystemInfoError> {
+    get_io_info_with(&default_backend(), time).await
+}
+
+pub async fn get_io_info_with<B: Backend>(
+    backend: &B,
+    time: u64,
+) -> Result<IoInfo, SystemInfoError> {
+    let measurement_1 = IoMeasurement::take_with(backend).await?;
+    futures_timer::Delay::new(Duration::from_millis(time)).await;
+    let measurement_2 = IoMeasurement::take_with(backend).await?;
+    let (disks, networks) = measurement_2.since(&measurement_1);
+    let filesystems = with_timeout("读取文件系统用量", READ_TIMEOUT, backend.filesystems()).await?;
+    Ok(IoInfo {
+        disks,
+        filesystems,
+        networks,
+    })
+}
+
+// statvfs 读取挂载点的用量, 返回 (total, used, free)
+// ? f_bfree 包含为 root 保留的块, f_bavail 不包含; used 按 f_bfree 算, free 按 f_bavail 算, 和 df 一致
+#[cfg(unix)]
+pub fn statvfs(mount_point: &str) -> Result<(u64, u64, u64), SystemInfoError> {
+    let path = std::ffi::CString::new(mount_point)
+        .map_err(|err| SystemInfoError::parse_with(mount_point, "路径中有 NUL 字符", err))?;
+    // ? statvfs 只写入 stat 这个局部变量
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(SystemInfoError::from_io(
+            std::path::Path::new(mount_point),
+            std::io::Error::last_os_error(),
+        ));
+    }
+    let block = stat.f_frsize as u64;
+    let total = stat.f_blocks as u64 * block;
+    let used = (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * block;
+    Ok((total, used, stat.f_bavail as u64 * block))
+}
+
+#[cfg(not(unix))]
+pub fn statvfs(_mount_point: &str) -> Result<(u64, u64, u64), SystemInfoError> {
+    Err(SystemInfoError::unsupported("statvfs"))
+}
+
+// 每秒字节数, 例如 "12.3 MiB/s"
+pub fn format_rate(bytes: f64) -> String {
+    format!("{}/s", format_bytes(bytes as u64))
+}
+
+pub fn print_io(info: &IoInfo) {
+    println!(
+        "{:<12} {:>14} {:>14} {:>9} {:>9}",
+        "DEVICE", "READ", "WRITE", "R IOPS", "W IOPS"
+    );
+    for disk in &info.disks {
+        println!(
+            "{:<12} {:>14} {:>14} {:>9.1} {:>9.1}",
+            disk.device,
+            format_rate(disk.read_bytes),
+            format_rate(disk.write_bytes),
+            disk.read_iops,
+            disk.write_iops
+        );
+    }
+    println!();
+    println!(
+        "{:<12} {:>14} {:>14} {:>9} {:>9}",
+        "INTERFACE", "RX", "TX", "RX PPS", "TX PPS"
+    );
+    for network in &info.networks {
+        println!(
+            "{:<12} {:>14} {:>14} {:>9.1} {:>9.1}",
+            network.interface,
+            format_rate(network.rx_bytes),
+            format_rate(network.tx_bytes),
+            network.rx_packets,
+            network.tx_packets
+        );
+    }
+    println!();
+    println!(
+        "{:<20} {:<8} {:>11} {:>11} {:>6}  MOUNT",
+        "FILESYSTEM", "TYPE", "SIZE", "USED", "USE%"
+    );
+    for fs in &info.filesystems {
+        println!(
+            "{:<20} {:<8} {:>11} {:>11} {:>5.1}%  {}",
+            fs.device,
+            fs.fs_type,
+            format_bytes(fs.total),
+            format_bytes(fs.used),
+            fs.used_percent(),
+            fs.mount_point
+        );
+    }
+}
+
+// 子命令入口: io [采样毫秒] [--json]
+// ? 同一个窗口里也测一次 CPU, 慢构建排查时 CPU 和 I/O 要放在一起看
+pub async fn io_main(args: &[String]) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let time = args.iter().find_map(|arg| arg.parse().ok()).unwrap_or(1000);
+    let (io, cpu) = futures::join!(get_io_info(time), get_cpu_info(time));
+    let io = match io {
+        Ok(io) => io,
+        Err(err) => {
+            eprintln!("读取磁盘和网络失败: {}", err);
+            return;
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&io) {
+            Ok(text) => println!("{}", text),
+            Err(err) => eprintln!("序列化失败: {}", err),
+        }
+        return;
+    }
+    match cpu {
+        Ok(cpu) => println!(
+            "CPU 使用率 {:.1}%, iowait {:.1}%, {} 个逻辑核心",
+            cpu.usage, cpu.times.iowait, cpu.count
+        ),
+        Err(err) => println!("CPU 读数不可用: {}", err),
+    }
+    println!("采样 {} 毫秒\n", time);
+    print_io(&io);
+}
@@ -0,0 +1,451 @@
+// 教材生成器: 把 modules 下带注释标记的源码整理成 Markdown 章节
+// 源码中约定的注释标记:
+//   // + 标题        -> 二级标题
+//   // * 要点        -> 加粗的要点
+//   // ? 解释        -> "提示" 引用块
+//   // ! 警告        -> "注意" 引用块
+//   // 其他普通注释  -> 正文段落
+// 注释之间的代码原样放进 ```rust 代码块中
+// 块注释里被注释掉的代码(例如 `/* let x = ...; */`)也放进代码块, 保留原来的缩进, 不当作正文
+// ! 输出必须是确定性的(文件按路径排序, 不包含时间戳), 这样生成结果才能直接 diff
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// 章节中的一个块
+#[derive(Debug, PartialEq)]
+enum Block {
+    Heading(String),
+    Point(String),
+    Callout(CalloutKind, Vec<String>),
+    Prose(Vec<String>),
+    Code(Vec<String>),
+    // 块注释中的代码, 和真正的代码分开, 避免和相邻的代码块合并
+    CommentedCode(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CalloutKind {
+    Note,
+    Warning,
+}
+
+impl CalloutKind {
+    fn label(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "提示",
+            CalloutKind::Warning => "注意",
+        }
+    }
+}
+
+// 一行注释文本的分类
+enum Marker<'a> {
+    Heading(&'a str),
+    Point(&'a str),
+    Callout(CalloutKind, &'a str),
+    Plain(&'a str),
+}
+
+// 识别标记, 标记字符后必须跟空格(或者整行只有标记), 避免把 `*e` 之类的内容误判
+fn classify(text: &str) -> Marker<'_> {
+    let text = text.trim();
+    let mut chars = text.chars();
+    let marker = chars.next();
+    let rest = chars.as_str();
+    if !(rest.is_empty() || rest.starts_with(' ')) {
+        return Marker::Plain(text);
+    }
+    let rest = rest.trim();
+    match marker {
+        Some('+') => Marker::Heading(rest),
+        Some('*') => Marker::Point(rest),
+        Some('?') => Marker::Callout(CalloutKind::Note, rest),
+        Some('!') => Marker::Callout(CalloutKind::Warning, rest),
+        _ => Marker::Plain(text),
+    }
+}
+
+// 块注释中的一行看起来像不像代码
+// ? 只是启发式的判断: 以 ; { } 结尾, 或者以常见的关键字开头, 行尾的 // 注释不算在内
+//   字符串字面量以外出现了中文的行是正文, 例如 "Some(author) => 之后的代码是..."
+fn looks_like_code(text: &str) -> bool {
+    let code = text.split("//").next().unwrap_or("").trim();
+    let has_cjk = code
+        .split('"')
+        .step_by(2)
+        .any(|outside| outside.chars().any(|c| c >= '\u{2E80}'));
+    if has_cjk {
+        return false;
+    }
+    const KEYWORDS: [&str; 10] = [
+        "let ", "fn ", "for ", "if ", "match ", "return ", "use ", "struct ", "impl ", "pub ",
+    ];
+    code.ends_with(';')
+        || code.ends_with('{')
+        || code.starts_with('}')
+        || code.contains(" => ")
+        || KEYWORDS.iter().any(|keyword| code.starts_with(keyword))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// 逐行扫描源码, 生成块序列
+struct Parser {
+    blocks: Vec<Block>,
+    in_block_comment: bool,
+    // 块注释中正在收集的代码里还没闭合的 { 的个数, 大于 0 时后面的行不论内容都属于代码
+    commented_depth: usize,
+}
+
+impl Parser {
+    fn new() -> Self {
+        Parser {
+            blocks: Vec::new(),
+            in_block_comment: false,
+            commented_depth: 0,
+        }
+    }
+
+    fn parse(mut self, source: &str) -> Vec<Block> {
+        for line in source.lines() {
+            self.line(line);
+        }
+        self.finish()
+    }
+
+    fn line(&mut self, line: &str) {
+        let trimmed = line.trim();
+        if self.in_block_comment {
+            // 块注释内部, 一直到 */ 为止都是注释; 保留行首的缩进, 注释掉的代码需要它
+            let (text, closed) = match line.find("*/") {
+                Some(end) => (&line[..end], true),
+                None => (line, false),
+            };
+            self.block_comment(text);
+            if closed {
+                self.in_block_comment = false;
+                self.commented_depth = 0;
+                self.break_paragraph();
+            }
+            return;
+        }
+        if let Some(rest) = trimmed.strip_prefix("/*") {
+            // ? 和 /* 写在同一行的内容, 缩进按 /* 所在的列计算, 这样和下面几行对得齐
+            let (rest, closed) = match rest.find("*/") {
+                Some(end) => (&rest[..end], true),
+                None => (rest, false),
+            };
+            let text = format!("{}{}", &line[..indentation(line)], rest.trim_start());
+            self.block_comment(&text);
+            self.in_block_comment = !closed;
+            if closed {
+                self.commented_depth = 0;
+            }
+            return;
+        }
+        if let Some(rest) = trimmed.strip_prefix("//") {
+            // 文档注释 `///` 也当作普通注释处理
+            self.comment(rest.trim_start_matches('/'));
+            return;
+        }
+        self.code(line);
+    }
+
+    fn comment(&mut self, text: &str) {
+        match classify(text) {
+            Marker::Heading(t) => self.blocks.push(Block::Heading(t.to_string())),
+            Marker::Point(t) => self.blocks.push(Block::Point(t.to_string())),
+            Marker::Callout(kind, t) => match self.blocks.last_mut() {
+                // 连续的同类标记合并成一个引用块
+                Some(Block::Callout(last, lines)) if *last == kind => lines.push(t.to_string()),
+                _ => self.blocks.push(Block::Callout(kind, vec![t.to_string()])),
+            },
+            Marker::Plain("") => self.break_paragraph(),
+            Marker::Plain(t) => match self.blocks.last_mut() {
+                Some(Block::Prose(lines)) => lines.push(t.to_string()),
+                _ => self.blocks.push(Block::Prose(vec![t.to_string()])),
+            },
+        }
+    }
+
+    // 块注释中的一行: 像代码的行(以及没闭合的花括号里的行)收集成代码块, 其余按注释处理
+    fn block_comment(&mut self, text: &str) {
+        let in_code = matches!(self.blocks.last(), Some(Block::CommentedCode(_)));
+        let is_code = (in_code && self.commented_depth > 0)
+            || (matches!(classify(text), Marker::Plain(_)) && looks_like_code(text));
+        if !is_code {
+            self.comment(text);
+            return;
+        }
+        let code = text.split("//").next().unwrap_or("");
+        let opened = code.matches('{').count();
+        let closed = code.matches('}').count();
+        self.commented_depth = (self.commented_depth + opened).saturating_sub(closed);
+        let line = text.trim_end().to_string();
+        match self.blocks.last_mut() {
+            Some(Block::CommentedCode(lines)) => lines.push(line),
+            _ => self.blocks.push(Block::CommentedCode(vec![line])),
+        }
+    }
+
+    fn code(&mut self, line: &str) {
+        let line = line.trim_end();
+        match self.blocks.last_mut() {
+            Some(Block::Code(lines)) => lines.push(line.to_string()),
+            // 注释之间的空行不需要单独开一个代码块
+            _ if line.is_empty() => self.break_paragraph(),
+            _ => self.blocks.push(Block::Code(vec![line.to_string()])),
+        }
+    }
+
+    // 用一个空的正文块把相邻的段落隔开, 渲染时会被丢弃
+    fn break_paragraph(&mut self) {
+        if let Some(Block::Prose(lines)) = self.blocks.last() {
+            if !lines.is_empty() {
+                self.blocks.push(Block::Prose(Vec::new()));
+            }
+        }
+    }
+
+    fn finish(self) -> Vec<Block> {
+        self.blocks
+            .into_iter()
+            .filter_map(|block| match block {
+                Block::Prose(lines) if lines.is_empty() => None,
+                Block::Code(lines) => {
+                    // 去掉代码块首尾的空行
+                    let start = lines.iter().position(|l| !l.is_empty())?;
+                    let end = lines.iter().rposition(|l| !l.is_empty())?;
+                    Some(Block::Code(lines[start..=end].to_vec()))
+                }
+                Block::CommentedCode(lines) => {
+                    // 去掉共同的缩进, 保留相对的缩进
+                    let end = lines.iter().rposition(|l| !l.trim().is_empty())?;
+                    let indent = lines[..=end]
+                        .iter()
+                        .filter(|l| !l.trim().is_empty())
+                        .map(|l| indentation(l))
+                        .min()
+                        .unwrap_or(0);
+                    let lines = lines[..=end]
+                        .iter()
+                        .map(|l| l.get(indent..).unwrap_or("").to_string())
+                        .collect();
+                    Some(Block::CommentedCode(lines))
+                }
+                block => Some(block),
+            })
+            .collect()
+    }
+}
+
+// 把一个模块渲染为一章 Markdown
+pub fn render_chapter(title: &str, source: &str) -> String {
+    let mut out = format!("# {}\n", title);
+    for block in Parser::new().parse(source) {
+        out.push('\n');
+        match block {
+            Block::Heading(text) => out.push_str(&format!("## {}\n", text)),
+            Block::Point(text) => out.push_str(&format!("**{}**\n", text)),
+            Block::Callout(kind, lines) => {
+                out.push_str(&format!("> **{}**\n", kind.label()));
+                for line in lines {
+                    out.push_str(&format!("> {}\n", line));
+                }
+            }
+            Block::Prose(lines) => {
+                // 源码注释是逐行断句的, 用 CommonMark 的硬换行(行尾反斜杠)保留原有的换行
+                out.push_str(&lines.join("\\\n"));
+                out.push('\n');
+            }
+            Block::Code(lines) | Block::CommentedCode(lines) => {
+                out.push_str("```rust\n");
+                for line in lines {
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                out.push_str("```\n");
+            }
+        }
+    }
+    out
+}
+
+// 递归收集 .rs 文件
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// modules/expression/language.rs -> expression::language
+fn module_path(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file).with_extension("");
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+// 除了注释和空行, 只有 mod 声明
+fn only_declarations(source: &str) -> bool {
+    source.lines().map(str::trim).all(|line| {
+        line.is_empty()
+            || line.starts_with("//")
+            || line.starts_with("mod ")
+            || line.starts_with("pub mod ")
+    })
+}
+
+// 生成整本教材, 返回写出的章节数
+// 每个模块一章, 文件名为模块路径(用 _ 连接), 另外生成一个 SUMMARY.md 目录
+pub fn generate_book(src: &Path, out: &Path) -> io::Result<usize> {
+    let mut files = Vec::new();
+    collect_sources(src, &mut files)?;
+    let mut chapters = Vec::new();
+    for file in &files {
+        let source = fs::read_to_string(file)?;
+        let path = module_path(src, file);
+        // 只有模块声明的 mod.rs 不单独成章; 写了代码的 mod.rs(例如 expression/system_info/mod.rs)以目录名成章
+        let title = match path.strip_suffix("::mod") {
+            _ if path == "mod" => continue,
+            Some(_) if only_declarations(&source) => continue,
+            Some(parent) => parent.to_string(),
+            None => path,
+        };
+        chapters.push((title, source));
+    }
+    // 按模块路径排序保证输出顺序稳定, 目录自己的章节排在子模块前面
+    chapters.sort();
+    fs::create_dir_all(out)?;
+
+    let mut summary = String::from("# Summary\n\n");
+    for (title, source) in &chapters {
+        let name = format!("{}.md", title.replace("::", "_"));
+        fs::write(out.join(&name), render_chapter(title, source))?;
+        summary.push_str(&format!("- [{}]({})\n", title, name));
+    }
+    fs::write(out.join("SUMMARY.md"), summary)?;
+    Ok(chapters.len())
+}
+
+// 子命令入口: book [源码目录] [输出目录]
+pub fn book_main(args: &[String]) {
+    let src = args.first().map_or("modules", String::as_str);
+    let out = args.get(1).map_or("book", String::as_str);
+    match generate_book(Path::new(src), Path::new(out)) {
+        Ok(count) => println!("已生成 {} 个章节到 {}", count, out),
+        Err(err) => eprintln!("生成教材失败: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/book")
+    }
+
+    #[test]
+    fn headings_callouts_and_prose() {
+        let source = fs::read_to_string(fixtures().join("basics.rs")).unwrap();
+        let expected = "\
+# basics
+
+## 变量
+
+变量默认不可变\\
+需要修改时加 mut
+
+```rust
+let x = 5;
+```
+
+**遮蔽和 mut 不同**
+
+> **提示**
+> 遮蔽会创建一个新变量
+> 类型也可以改变
+
+> **注意**
+> 不能给不可变变量赋值
+
+```rust
+let x = x + 1;
+```
+
+*e 不是要点
+";
+        assert_eq!(render_chapter("basics", &source), expected);
+    }
+
+    #[test]
+    fn summary_is_deterministic() {
+        let out = std::env::temp_dir().join(format!("lesson-book-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out);
+        assert_eq!(generate_book(&fixtures(), &out).unwrap(), 4);
+        let summary = fs::read_to_string(out.join("SUMMARY.md")).unwrap();
+        // 只有声明的根 mod.rs 不成章, 目录的 mod.rs 排在子模块前面
+        assert_eq!(
+            summary,
+            "\
+# Summary
+
+- [basics](basics.md)
+- [expression](expression.md)
+- [expression::language](expression_language.md)
+- [zeta](zeta.md)
+"
+        );
+        let chapter = fs::read_to_string(out.join("expression.md")).unwrap();
+        assert!(chapter.starts_with("# expression\n\n表达式\n"));
+        // 再生成一次, 每个文件都和第一次完全相同
+        let first: Vec<(PathBuf, String)> = ["SUMMARY.md", "basics.md", "expression.md"]
+            .iter()
+            .map(|name| (out.join(name), fs::read_to_string(out.join(name)).unwrap()))
+            .collect();
+        generate_book(&fixtures(), &out).unwrap();
+        for (path, text) in first {
+            assert_eq!(fs::read_to_string(path).unwrap(), text);
+        }
+        fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[test]
+    fn commented_out_code_keeps_indentation() {
+        let source = "\
+fn f() {
+    /* let x = match y {
+        Some(v) => v,
+        None => 0,
+    } */
+    /*
+        上面的 match 是一个表达式
+        let z = x + 1;
+    */
+}
+";
+        let chapter = render_chapter("t", source);
+        assert!(chapter
+            .contains("```rust\nlet x = match y {\n    Some(v) => v,\n    None => 0,\n}\n```"));
+        assert!(chapter.contains("上面的 match 是一个表达式\n\n```rust\nlet z = x + 1;\n```"));
+    }
+
+    #[test]
+    fn prose_with_arrows_stays_prose() {
+        let chapter = render_chapter("t", "/*\n    Some(a) => 之后是一个表达式\n*/\n");
+        assert!(chapter.contains("Some(a) => 之后是一个表达式\n"));
+        assert!(!chapter.contains("```"));
+    }
+}
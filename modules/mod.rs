@@ -10,4 +10,6 @@ pub mod enum_mod;
 pub mod mem_replace;
 pub mod climb_stairs;
 pub mod reference;
-pub mod expression;
\ No newline at end of file
+pub mod expression;
+pub mod lesson_book;
+pub mod sequences;
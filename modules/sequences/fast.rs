@@ -0,0 +1,210 @@
+// 大下标的斐波那契
+// mem_replace::fibonacci 和 Fibonacci 的迭代器都是一项一项往后加, 要做 n 次大数加法,
+// F(10,000,000) 有两百多万位, 这样算要很久
+// 这里用两种 O(log n) 次乘法的方法:
+//   * 快速倍增: F(2k) = F(k) * (2F(k+1) - F(k)), F(2k+1) = F(k)^2 + F(k+1)^2
+//   * 矩阵快速幂: [[1, 1], [1, 0]]^n = [[F(n+1), F(n)], [F(n), F(n-1)]]
+// 快速倍增每一步只需要 3 次乘法, 比矩阵(每次相乘 8 次)快, 矩阵的好处是任何二阶线性递推都能用
+use num::bigint::BigUint;
+use num::traits::{One, ToPrimitive, Zero};
+use std::ops::{Add, Mul};
+use std::time::Instant;
+
+// F(n), 下标约定和 mem_replace::fibonacci 一样, F(0) = 0
+pub fn fibonacci(n: u64) -> BigUint {
+    // (a, b) = (F(k), F(k+1)), 从 k = 0 开始, 按 n 的二进制从高位到低位, 每一位把 k 翻倍(再加 1)
+    let mut a = BigUint::zero();
+    let mut b = BigUint::one();
+    for bit in (0..u64::BITS - n.leading_zeros()).rev() {
+        // ? F(k+1) >= F(k), 所以 2b - a 不会出现负数, BigUint 可以直接减
+        let double = &a * (&b + &b - &a);
+        let double_next = &a * &a + &b * &b;
+        if n >> bit & 1 == 1 {
+            a = double_next;
+            b = double + &a;
+        } else {
+            a = double;
+            b = double_next;
+        }
+    }
+    a
+}
+
+// 2x2 矩阵 [[a, b], [c, d]]
+// ? 只要求元素的引用能做加法和乘法, BigUint、u64 都可以用, 不需要 Copy
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix2<T> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+    pub d: T,
+}
+
+impl<T> Matrix2<T>
+where
+    T: Clone + Zero + One,
+    for<'x> &'x T: Add<&'x T, Output = T> + Mul<&'x T, Output = T>,
+{
+    pub fn new(a: T, b: T, c: T, d: T) -> Matrix2<T> {
+        Matrix2 { a, b, c, d }
+    }
+
+    pub fn identity() -> Matrix2<T> {
+        Matrix2::new(T::one(), T::zero(), T::zero(), T::one())
+    }
+
+    pub fn mul(&self, other: &Matrix2<T>) -> Matrix2<T> {
+        Matrix2 {
+            a: &(&self.a * &other.a) + &(&self.b * &other.c),
+            b: &(&self.a * &other.b) + &(&self.b * &other.d),
+            c: &(&self.c * &other.a) + &(&self.d * &other.c),
+            d: &(&self.c * &other.b) + &(&self.d * &other.d),
+        }
+    }
+
+    // 平方-乘: 把 n 拆成二进制, 只需要 O(log n) 次矩阵乘法
+    pub fn pow(&self, mut n: u64) -> Matrix2<T> {
+        let mut result = Matrix2::identity();
+        let mut base = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(&base);
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+}
+
+// 二阶线性递推 a(n) = p * a(n-1) + q * a(n-2) 的第 n 项, a(0)、a(1) 是初始值
+// [[p, q], [1, 0]]^n * [a(1), a(0)] = [a(n+1), a(n)]
+pub fn recurrence(p: u32, q: u32, a0: u32, a1: u32, n: u64) -> BigUint {
+    let step = Matrix2::new(
+        BigUint::from(p),
+        BigUint::from(q),
+        BigUint::one(),
+        BigUint::zero(),
+    );
+    let m = step.pow(n);
+    m.c * a1 + m.d * a0
+}
+
+// 同样的 F(n), 用矩阵幂计算
+pub fn fibonacci_matrix(n: u64) -> BigUint {
+    recurrence(1, 1, 0, 1, n)
+}
+
+// L(n), L(0) = 2, L(1) = 1
+pub fn lucas(n: u64) -> BigUint {
+    recurrence(1, 1, 2, 1, n)
+}
+
+// Pell 数 P(n), P(0) = 0, P(1) = 1
+pub fn pell(n: u64) -> BigUint {
+    recurrence(2, 1, 0, 1, n)
+}
+
+// 十进制位数
+// ! F(10,000,000) 转成十进制字符串要十几秒, 比算出它还慢得多;
+//   这里先用最高 64 位估出 log10, 再和 10^k 比较一次修正估计的误差
+pub fn decimal_digits(value: &BigUint) -> u64 {
+    if value.is_zero() {
+        return 1;
+    }
+    let shift = value.bits().saturating_sub(64);
+    let top = (value >> shift).to_f64().unwrap_or(f64::MAX);
+    let estimate = (top.log10() + shift as f64 * std::f64::consts::LOG10_2).floor() as u32;
+    // 10^estimate <= value < 10^(estimate+1) 时位数是 estimate + 1, 估计最多差 1
+    let power = BigUint::from(10u32).pow(estimate);
+    if value < &power {
+        estimate as u64
+    } else if value >= &(power * 10u32) {
+        estimate as u64 + 2
+    } else {
+        estimate as u64 + 1
+    }
+}
+
+// 子命令入口: fib <n> [--digits] [--matrix]
+// --digits 只打印位数(F(10,000,000) 本身有两百多万位), --matrix 换成矩阵幂计算
+pub fn fib_main(args: &[String]) {
+    let Some(n) = args.first().and_then(|arg| arg.parse::<u64>().ok()) else {
+        eprintln!("用法: fib <n> [--digits] [--matrix]");
+        return;
+    };
+    let digits = args.iter().any(|arg| arg == "--digits");
+    let matrix = args.iter().any(|arg| arg == "--matrix");
+    let start = Instant::now();
+    let value = if matrix {
+        fibonacci_matrix(n)
+    } else {
+        fibonacci(n)
+    };
+    let elapsed = start.elapsed();
+    if digits {
+        println!(
+            "F({}) 有 {} 位, 计算耗时 {:?}",
+            n,
+            decimal_digits(&value),
+            elapsed
+        );
+    } else {
+        println!("{}", value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sequences::{Fibonacci, Lucas, Pell, Sequence};
+
+    // 两种 O(log n) 的算法和逐项相加的迭代器一致
+    #[test]
+    fn against_iterators() {
+        let iterators = Fibonacci.iter().zip(Lucas.iter()).zip(Pell.iter());
+        for (n, ((f, l), p)) in iterators.enumerate().take(300) {
+            let n = n as u64;
+            assert_eq!(fibonacci(n), f, "F({})", n);
+            assert_eq!(fibonacci_matrix(n), f, "F({})", n);
+            assert_eq!(lucas(n), l, "L({})", n);
+            assert_eq!(pell(n), p, "P({})", n);
+        }
+    }
+
+    #[test]
+    fn large_index() {
+        let value = fibonacci(100_000);
+        assert_eq!(value, fibonacci_matrix(100_000));
+        assert_eq!(decimal_digits(&value), value.to_string().len() as u64);
+        assert_eq!(decimal_digits(&value), 20899);
+    }
+
+    // 矩阵对 u64 同样可用
+    #[test]
+    fn matrix_over_u64() {
+        let m = Matrix2::new(1u64, 1, 1, 0).pow(10);
+        assert_eq!(m, Matrix2::new(89, 55, 55, 34));
+        assert_eq!(Matrix2::new(1u64, 1, 1, 0).pow(0), Matrix2::identity());
+    }
+
+    // 10^k - 1 有 k 位, 10^k 有 k + 1 位; 估计值正好落在边界两侧时要靠比较修正
+    #[test]
+    fn digits_at_powers_of_ten() {
+        assert_eq!(decimal_digits(&BigUint::zero()), 1);
+        for k in [1u32, 2, 15, 16, 19, 20, 100, 1000, 10_000] {
+            let power = BigUint::from(10u32).pow(k);
+            assert_eq!(decimal_digits(&(&power - 1u32)), k as u64, "10^{} - 1", k);
+            assert_eq!(decimal_digits(&power), k as u64 + 1, "10^{}", k);
+            assert_eq!(
+                decimal_digits(&(&power + 1u32)),
+                k as u64 + 1,
+                "10^{} + 1",
+                k
+            );
+        }
+        assert_eq!(decimal_digits(&BigUint::from(u64::MAX)), 20);
+    }
+}
@@ -0,0 +1,304 @@
+// 记忆化
+// control_flow::fibonacci_over_flow 是最朴素的递归, fib(n) 会调用 fib(n-1) 和 fib(n-2),
+// 同一个参数被反复计算, 调用次数是 2F(n+1) - 1, 和结果本身一样按指数增长
+// 给纯函数加一层按参数缓存的包装, 同样的递归写法就只需要线性次数的计算:
+//   * Memoized 保存缓存和被包装的函数, 递归时调用 call 而不是直接调用自己
+//   * 可以限制缓存的容量, 满了以后淘汰最早放入的结果
+//   * 记录调用次数和命中次数, 用来实际测量两种写法的差别
+// ! 记忆化只适用于纯函数: 结果只由参数决定, 没有副作用
+use num::bigint::BigUint;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+pub struct Memoized<A, R> {
+    f: fn(&mut Memoized<A, R>, A) -> R,
+    cache: HashMap<A, R>,
+    // 放入缓存的顺序, 超出容量时从前面淘汰
+    order: VecDeque<A>,
+    capacity: Option<usize>,
+    // call 被调用的次数, 以及其中命中缓存的次数
+    pub calls: u64,
+    pub hits: u64,
+}
+
+impl<A: Hash + Eq + Clone, R: Clone> Memoized<A, R> {
+    // f 是要包装的函数, 它的第一个参数就是这个包装本身, 递归时通过它调用
+    pub fn new(f: fn(&mut Memoized<A, R>, A) -> R) -> Memoized<A, R> {
+        Memoized {
+            f,
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: None,
+            calls: 0,
+            hits: 0,
+        }
+    }
+
+    // 最多缓存 capacity 个结果
+    // ? 容量太小时仍然正确, 只是被淘汰的结果需要重新计算
+    pub fn with_capacity(f: fn(&mut Memoized<A, R>, A) -> R, capacity: usize) -> Memoized<A, R> {
+        Memoized {
+            capacity: Some(capacity),
+            ..Memoized::new(f)
+        }
+    }
+
+    pub fn call(&mut self, arg: A) -> R {
+        self.calls += 1;
+        if let Some(result) = self.cache.get(&arg) {
+            self.hits += 1;
+            return result.clone();
+        }
+        // ? 计算期间 self 被 f 可变借用, 所以先算出结果, 再放进缓存
+        let result = (self.f)(self, arg.clone());
+        if self.capacity != Some(0) {
+            if self
+                .capacity
+                .is_some_and(|capacity| self.cache.len() >= capacity)
+            {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.cache.remove(&oldest);
+                }
+            }
+            self.cache.insert(arg.clone(), result.clone());
+            self.order.push_back(arg);
+        }
+        result
+    }
+
+    // 真正执行计算的次数
+    pub fn misses(&self) -> u64 {
+        self.calls - self.hits
+    }
+
+    // 清空缓存和计数
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.order.clear();
+        self.calls = 0;
+        self.hits = 0;
+    }
+}
+
+// 和 fibonacci_over_flow 同样的递归写法, 只是递归调用换成了 memo.call
+fn fibonacci_step(memo: &mut Memoized<u64, BigUint>, n: u64) -> BigUint {
+    if n < 2 {
+        return BigUint::from(n);
+    }
+    memo.call(n - 1) + memo.call(n - 2)
+}
+
+// 记忆化的 F(n), 调用 call(n)
+// ! 递归深度是 n, 主线程的栈放不下太深的递归, n 很大时用 fast::fibonacci
+pub fn fibonacci_memo() -> Memoized<u64, BigUint> {
+    Memoized::new(fibonacci_step)
+}
+
+// 朴素递归, 和 fibonacci_over_flow 一样, 只是多了调用计数, 结果用 BigUint
+pub fn fibonacci_counted(n: u64, calls: &mut u64) -> BigUint {
+    *calls += 1;
+    if n < 2 {
+        return BigUint::from(n);
+    }
+    fibonacci_counted(n - 1, calls) + fibonacci_counted(n - 2, calls)
+}
+
+// 一次对比的结果
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub n: u64,
+    pub naive_calls: u64,
+    pub naive_time: Duration,
+    pub memo_calls: u64,
+    // 记忆化时真正执行计算的次数, 其余的调用都命中了缓存
+    pub memo_misses: u64,
+    pub memo_time: Duration,
+}
+
+// 记忆化用传入的 memo, 先清空缓存和计数; 两种写法的结果不同时返回描述, 不会 panic
+pub fn compare(n: u64, memo: &mut Memoized<u64, BigUint>) -> Result<Comparison, String> {
+    let mut naive_calls = 0;
+    let start = Instant::now();
+    let naive = fibonacci_counted(n, &mut naive_calls);
+    let naive_time = start.elapsed();
+
+    memo.clear();
+    let start = Instant::now();
+    let memoized = memo.call(n);
+    let memo_time = start.elapsed();
+    if naive != memoized {
+        return Err(format!(
+            "F({}): 朴素递归得到 {}, 记忆化得到 {}",
+            n, naive, memoized
+        ));
+    }
+    Ok(Comparison {
+        n,
+        naive_calls,
+        naive_time,
+        memo_calls: memo.calls,
+        memo_misses: memo.misses(),
+        memo_time,
+    })
+}
+
+// 朴素递归在 n = 35 时已经要调用近三千万次, 再往上每多 5 项耗时乘以 11
+const MAX_NAIVE_N: u64 = 35;
+
+// 子命令入口: memo [n] [--capacity k]
+// 从 n = 5 开始每隔 5 对比一次, 直到 n(默认 30, 最大 MAX_NAIVE_N); 朴素递归的耗时每多 5 项大约乘以 11
+// --capacity 3 就够了: 算 fib(n) 时先算 fib(n-1), 回来以后 fib(n-2) 还在缓存里, 调用次数和不限容量时一样
+// ? 容量 2 不够: fib(1)、fib(0)、fib(2) 依次放入后 fib(1) 被淘汰, 算 fib(3) 时要重新算
+pub fn memo_main(args: &[String]) {
+    let mut n = 30;
+    let mut capacity = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--capacity" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(value) => capacity = Some(value),
+                None => {
+                    eprintln!("--capacity 需要一个数字");
+                    return;
+                }
+            },
+            other => match other.parse() {
+                Ok(value) => n = value,
+                Err(_) => {
+                    eprintln!("用法: memo [n] [--capacity k]");
+                    return;
+                }
+            },
+        }
+    }
+    if n > MAX_NAIVE_N {
+        eprintln!(
+            "n = {} 时朴素递归要算很久, 只对比到 n = {}; 单独计算大的 F(n) 用 fib 子命令",
+            n, MAX_NAIVE_N
+        );
+        n = MAX_NAIVE_N;
+    }
+    let mut memo = match capacity {
+        Some(capacity) => Memoized::with_capacity(fibonacci_step, capacity),
+        None => fibonacci_memo(),
+    };
+    println!(
+        "{:>4} {:>14} {:>12} {:>10} {:>10} {:>12}",
+        "N", "NAIVE CALLS", "NAIVE TIME", "MEMO CALLS", "COMPUTED", "MEMO TIME"
+    );
+    for n in (5..=n).step_by(5) {
+        let comparison = match compare(n, &mut memo) {
+            Ok(comparison) => comparison,
+            Err(mismatch) => {
+                eprintln!("{}", mismatch);
+                return;
+            }
+        };
+        println!(
+            "{:>4} {:>14} {:>12} {:>10} {:>10} {:>12}",
+            comparison.n,
+            comparison.naive_calls,
+            format!("{:.2?}", comparison.naive_time),
+            comparison.memo_calls,
+            comparison.memo_misses,
+            format!("{:.2?}", comparison.memo_time)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sequences::{Fibonacci, Sequence};
+
+    // 朴素递归的调用次数是 2F(n+1) - 1
+    #[test]
+    fn naive_calls() {
+        let fibonacci = Fibonacci.first(27);
+        for n in 0..25 {
+            let mut calls = 0;
+            let value = fibonacci_counted(n as u64, &mut calls);
+            assert_eq!(value, fibonacci[n]);
+            assert_eq!(
+                BigUint::from(calls),
+                &fibonacci[n + 1] * 2u32 - 1u32,
+                "n = {}",
+                n
+            );
+        }
+    }
+
+    // 记忆化后 F(0) ~ F(n) 各算一次, 调用次数 2n - 1, 都是线性的
+    #[test]
+    fn memo_is_linear() {
+        for (n, f) in Fibonacci.iter().enumerate().take(200).skip(2) {
+            let n = n as u64;
+            let mut memo = fibonacci_memo();
+            assert_eq!(memo.call(n), f);
+            assert_eq!(memo.misses(), n + 1, "n = {}", n);
+            assert_eq!(memo.calls, 2 * n - 1, "n = {}", n);
+        }
+        // F(0)、F(1) 直接返回, 只有一次调用
+        for n in [0, 1] {
+            let mut memo = fibonacci_memo();
+            memo.call(n);
+            assert_eq!((memo.calls, memo.misses()), (1, 1));
+        }
+    }
+
+    // 容量 3 时调用次数和不限容量一样, 容量 2 时被淘汰的结果要重新算
+    #[test]
+    fn bounded_capacity() {
+        let n = 30;
+        let mut unbounded = fibonacci_memo();
+        let expected = unbounded.call(n);
+        let mut bounded = Memoized::with_capacity(fibonacci_step, 3);
+        assert_eq!(bounded.call(n), expected);
+        assert_eq!(bounded.calls, unbounded.calls);
+        assert!(bounded.cache.len() <= 3);
+
+        let mut two = Memoized::with_capacity(fibonacci_step, 2);
+        assert_eq!(two.call(n), expected);
+        assert!(
+            two.calls > unbounded.calls,
+            "容量 2 调用了 {} 次",
+            two.calls
+        );
+        assert!(two.cache.len() <= 2);
+    }
+
+    // 容量 0 时什么都不缓存, 和朴素递归的调用次数相同
+    #[test]
+    fn zero_capacity() {
+        let mut memo = Memoized::with_capacity(fibonacci_step, 0);
+        let mut naive_calls = 0;
+        assert_eq!(memo.call(20), fibonacci_counted(20, &mut naive_calls));
+        assert_eq!(memo.calls, naive_calls);
+        assert_eq!(memo.hits, 0);
+        assert!(memo.cache.is_empty());
+    }
+
+    #[test]
+    fn clear_resets() {
+        let mut memo = fibonacci_memo();
+        memo.call(10);
+        memo.clear();
+        assert_eq!((memo.calls, memo.hits), (0, 0));
+        memo.call(10);
+        assert_eq!(memo.misses(), 11);
+    }
+
+    // 同一个 memo 重复使用时每次都从空缓存开始
+    #[test]
+    fn compare_counts() {
+        let mut memo = fibonacci_memo();
+        for _ in 0..2 {
+            let comparison = compare(20, &mut memo).unwrap();
+            assert_eq!(comparison.naive_calls, 21891);
+            assert_eq!((comparison.memo_calls, comparison.memo_misses), (39, 21));
+        }
+        let bounded = compare(20, &mut Memoized::with_capacity(fibonacci_step, 3)).unwrap();
+        assert_eq!(bounded.memo_calls, 39);
+    }
+}
@@ -0,0 +1,359 @@
+// 整数数列
+// 斐波那契在仓库里已经写了三遍, 下标的约定各不相同:
+//   * control_flow::fibonacci(n)   n >= 1 时等于 F(n), 但 fibonacci(0) 返回 1
+//   * mem_replace::fibonacci(n)    就是 F(n), F(0) = 0
+//   * climb_stairs::climb_stairs(n) 也是 F(n), 只是换成了 i32, n > 45 时溢出
+// 这里统一成一个 Sequence trait, 每个数列都是一个惰性的 BigUint 迭代器
+// ! 下标约定: 所有数列都从第 0 项开始, nth(n) 就是迭代器的第 n 个元素(和 Iterator::nth 一致)
+//   各数列的前几项见每个类型上的注释, 与 OEIS 的偏移一致, Padovan 除外(见 Padovan)
+use num::bigint::BigUint;
+use num::traits::{One, Zero};
+
+pub mod fast;
+pub mod memo;
+pub mod overflow;
+pub mod pisano;
+
+pub trait Sequence {
+    // 名称, 也是命令行里使用的名字
+    fn name(&self) -> &'static str;
+    // 从第 0 项开始的惰性迭代器, 不会结束
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>>;
+
+    // 第 n 项, 从 0 开始
+    // ? 默认实现从头迭代, O(n) 次大数加法; 二阶递推的数列覆盖成 fast 里 O(log n) 的算法
+    fn nth(&self, n: usize) -> BigUint {
+        self.iter().nth(n).expect("数列是无穷的")
+    }
+
+    // 前 count 项
+    fn first(&self, count: usize) -> Vec<BigUint> {
+        self.iter().take(count).collect()
+    }
+}
+
+// 常系数线性递推: a(n) = c1 * a(n-1) + c2 * a(n-2) + ... + ck * a(n-k)
+// 迭代器只保存最近 k 项, 每一步算出新的一项, 把最旧的一项交出去
+pub struct Recurrence {
+    // 最近的 k 项, window[0] 是下一个要交出去的项
+    window: Vec<BigUint>,
+    // coefficients[i] 是 a(n-1-i) 的系数
+    coefficients: Vec<u32>,
+}
+
+impl Recurrence {
+    // initial 是前 k 项, coefficients 和 initial 一样长
+    pub fn new(initial: &[u32], coefficients: &[u32]) -> Recurrence {
+        assert_eq!(
+            initial.len(),
+            coefficients.len(),
+            "初始项和系数的个数要一致"
+        );
+        Recurrence {
+            window: initial.iter().map(|&value| BigUint::from(value)).collect(),
+            coefficients: coefficients.to_vec(),
+        }
+    }
+}
+
+impl Iterator for Recurrence {
+    type Item = BigUint;
+
+    fn next(&mut self) -> Option<BigUint> {
+        let next = self
+            .coefficients
+            .iter()
+            .zip(self.window.iter().rev())
+            .filter(|(&c, _)| c != 0)
+            .fold(BigUint::zero(), |sum, (&c, term)| sum + term * c);
+        // ? 和 mem_replace::fibonacci 一样, 新的一项放到末尾, 旧的第一项直接移出来返回, 不需要 clone
+        self.window.push(next);
+        Some(self.window.remove(0))
+    }
+}
+
+// 0, 1, 1, 2, 3, 5, 8, ...  (OEIS A000045)
+pub struct Fibonacci;
+
+// 2, 1, 3, 4, 7, 11, 18, ...  (OEIS A000032), L(n) = F(n-1) + F(n+1)
+pub struct Lucas;
+
+// 0, 0, 1, 1, 2, 4, 7, 13, ...  (OEIS A000073), 每一项是前三项之和
+pub struct Tribonacci;
+
+// 1, 1, 1, 2, 2, 3, 4, 5, 7, 9, ...  P(n) = P(n-2) + P(n-3)
+// ? 和 ownership::print_padovan 的初始值一致(OEIS A134816);
+//   OEIS A000931 的 Padovan 从 1, 0, 0 开始, 是这里的数列向后平移 5 项
+pub struct Padovan;
+
+// 0, 1, 2, 5, 12, 29, 70, ...  (OEIS A000129), P(n) = 2P(n-1) + P(n-2)
+pub struct Pell;
+
+// 1, 1, 2, 5, 14, 42, 132, ...  (OEIS A000108)
+pub struct Catalan;
+
+impl Sequence for Fibonacci {
+    fn name(&self) -> &'static str {
+        "fibonacci"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        Box::new(Recurrence::new(&[0, 1], &[1, 1]))
+    }
+    fn nth(&self, n: usize) -> BigUint {
+        fast::fibonacci(n as u64)
+    }
+}
+
+impl Sequence for Lucas {
+    fn name(&self) -> &'static str {
+        "lucas"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        Box::new(Recurrence::new(&[2, 1], &[1, 1]))
+    }
+    fn nth(&self, n: usize) -> BigUint {
+        fast::lucas(n as u64)
+    }
+}
+
+impl Sequence for Tribonacci {
+    fn name(&self) -> &'static str {
+        "tribonacci"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        Box::new(Recurrence::new(&[0, 0, 1], &[1, 1, 1]))
+    }
+}
+
+impl Sequence for Padovan {
+    fn name(&self) -> &'static str {
+        "padovan"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        // a(n-1) 的系数是 0
+        Box::new(Recurrence::new(&[1, 1, 1], &[0, 1, 1]))
+    }
+}
+
+impl Sequence for Pell {
+    fn name(&self) -> &'static str {
+        "pell"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        Box::new(Recurrence::new(&[0, 1], &[2, 1]))
+    }
+    fn nth(&self, n: usize) -> BigUint {
+        fast::pell(n as u64)
+    }
+}
+
+// 卡特兰数不是线性递推, 用 C(n+1) = C(n) * 2(2n+1) / (n+2)
+// ? 先乘后除, 每一步的除法都是整除(C(n+1) 是整数), 不会丢精度
+pub struct CatalanIter {
+    n: u64,
+    current: BigUint,
+}
+
+impl Iterator for CatalanIter {
+    type Item = BigUint;
+
+    fn next(&mut self) -> Option<BigUint> {
+        let next = &self.current * (2 * (2 * self.n + 1)) / (self.n + 2);
+        self.n += 1;
+        Some(std::mem::replace(&mut self.current, next))
+    }
+}
+
+impl Sequence for Catalan {
+    fn name(&self) -> &'static str {
+        "catalan"
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = BigUint>> {
+        Box::new(CatalanIter {
+            n: 0,
+            current: BigUint::one(),
+        })
+    }
+}
+
+pub fn all() -> Vec<Box<dyn Sequence>> {
+    vec![
+        Box::new(Fibonacci),
+        Box::new(Lucas),
+        Box::new(Tribonacci),
+        Box::new(Padovan),
+        Box::new(Pell),
+        Box::new(Catalan),
+    ]
+}
+
+pub fn find(name: &str) -> Option<Box<dyn Sequence>> {
+    all().into_iter().find(|sequence| sequence.name() == name)
+}
+
+// ownership::print_padovan 打印的 p(1..10)
+// ? 那个函数只演示 Vec 的所有权, 结果直接 println, 没有返回值, 这里抄下它的输出用于对照
+const PRINTED_PADOVAN: [u32; 10] = [1, 1, 1, 2, 2, 3, 4, 5, 7, 9];
+
+// 和仓库里已有的实现对照前 count 项, 返回不一致的描述, 全部一致时为空
+// ? sequence check 用来演示对照的结果; 同样的性质在各个文件末尾的测试里检查
+pub fn cross_check(count: usize) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let mut expect = |what: String, expected: BigUint, actual: BigUint| {
+        if expected != actual {
+            mismatches.push(format!("{}: 期望 {}, 实际 {}", what, expected, actual));
+        }
+    };
+    let fibonacci = Fibonacci.first(count + 2);
+    for (n, f) in fibonacci.iter().enumerate().take(count) {
+        expect(
+            format!("mem_replace::fibonacci({})", n),
+            f.clone(),
+            crate::modules::mem_replace::fibonacci(n),
+        );
+        // control_flow::fibonacci(0) 返回 1, 不是 F(0), 从 1 开始比较
+        if n >= 1 {
+            expect(
+                format!("control_flow::fibonacci({})", n),
+                f.clone(),
+                crate::modules::control_flow::fibonacci(n as isize),
+            );
+        }
+        // ! 循环的最后一步会多算出 F(n+1), i32 最多放到 F(46), 所以 n 最大只能到 45
+        if n <= 45 {
+            let stairs = crate::modules::climb_stairs::climb_stairs(n as i32);
+            expect(
+                format!("climb_stairs({})", n),
+                f.clone(),
+                BigUint::from(stairs as u32),
+            );
+        }
+    }
+    for (n, (expected, actual)) in PRINTED_PADOVAN.iter().zip(Padovan.iter()).enumerate() {
+        expect(
+            format!("print_padovan p({})", n),
+            BigUint::from(*expected),
+            actual,
+        );
+    }
+    // L(n) = F(n-1) + F(n+1)
+    for (n, lucas) in Lucas
+        .iter()
+        .enumerate()
+        .skip(1)
+        .take(count.saturating_sub(1))
+    {
+        expect(
+            format!("L({}) = F({}) + F({})", n, n - 1, n + 1),
+            &fibonacci[n - 1] + &fibonacci[n + 1],
+            lucas,
+        );
+    }
+    // fibonacci_over_flow 是指数级的递归, 只比较前 25 项
+    for n in 0..count.min(25) {
+        let recursive = crate::modules::control_flow::fibonacci_over_flow(n as isize);
+        if overflow::fibonacci_checked::<isize>(n as u32) != Some(recursive) {
+            mismatches.push(format!(
+                "control_flow::fibonacci_over_flow({}) = {}",
+                n, recursive
+            ));
+        }
+    }
+    mismatches
+}
+
+// 子命令入口:
+//   sequence list
+//   sequence check [项数]
+//   sequence <名称> [项数]       打印前若干项, 默认 10 项
+//   sequence <名称> --nth <n>    打印第 n 项(从 0 开始)
+pub fn sequence_main(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for sequence in all() {
+                let terms: Vec<String> = sequence.first(8).iter().map(|t| t.to_string()).collect();
+                println!("{:<12} {}, ...", sequence.name(), terms.join(", "));
+            }
+        }
+        Some("check") => {
+            let count = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(100);
+            let mismatches = cross_check(count);
+            if mismatches.is_empty() {
+                println!("前 {} 项与已有实现一致", count);
+            }
+            for mismatch in mismatches {
+                println!("{}", mismatch);
+            }
+        }
+        Some(name) => {
+            let Some(sequence) = find(name) else {
+                eprintln!("未知数列 {:?}, 用 sequence list 查看可用的数列", name);
+                return;
+            };
+            match &args[1..] {
+                [flag, n] if flag == "--nth" => match n.parse() {
+                    Ok(n) => println!("{}", sequence.nth(n)),
+                    Err(_) => eprintln!("无法解析下标 {:?}", n),
+                },
+                rest => {
+                    let count = rest.first().and_then(|arg| arg.parse().ok()).unwrap_or(10);
+                    for (n, term) in sequence.iter().take(count).enumerate() {
+                        println!("{:>4}  {}", n, term);
+                    }
+                }
+            }
+        }
+        None => eprintln!("用法: sequence list | check [项数] | <名称> [项数] | <名称> --nth <n>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(sequence: &dyn Sequence, count: usize) -> Vec<u64> {
+        sequence
+            .first(count)
+            .iter()
+            .map(|term| term.try_into().unwrap())
+            .collect()
+    }
+
+    // 前几项和 OEIS 一致
+    #[test]
+    fn first_terms() {
+        assert_eq!(terms(&Fibonacci, 10), [0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+        assert_eq!(terms(&Lucas, 10), [2, 1, 3, 4, 7, 11, 18, 29, 47, 76]);
+        assert_eq!(terms(&Tribonacci, 10), [0, 0, 1, 1, 2, 4, 7, 13, 24, 44]);
+        assert_eq!(terms(&Pell, 10), [0, 1, 2, 5, 12, 29, 70, 169, 408, 985]);
+        assert_eq!(
+            terms(&Catalan, 10),
+            [1, 1, 2, 5, 14, 42, 132, 429, 1430, 4862]
+        );
+        assert_eq!(terms(&Padovan, 10), PRINTED_PADOVAN.map(u64::from));
+    }
+
+    #[test]
+    fn find_by_name() {
+        for sequence in all() {
+            assert_eq!(find(sequence.name()).unwrap().name(), sequence.name());
+        }
+        assert!(find("primes").is_none());
+    }
+
+    // L(n) = F(n-1) + F(n+1)
+    #[test]
+    fn lucas_from_fibonacci() {
+        let fibonacci = Fibonacci.first(102);
+        for (n, lucas) in Lucas.iter().enumerate().skip(1).take(100) {
+            assert_eq!(lucas, &fibonacci[n - 1] + &fibonacci[n + 1], "L({})", n);
+        }
+    }
+
+    // 已有的三个斐波那契实现都和 Fibonacci 一致:
+    // mem_replace::fibonacci 就是 F(n); control_flow::fibonacci 从 1 开始比较; climb_stairs 只比较到 45
+    #[test]
+    fn cross_check_is_clean() {
+        assert_eq!(cross_check(100), Vec::<String>::new());
+    }
+}
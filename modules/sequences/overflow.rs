@@ -0,0 +1,399 @@
+// 定宽整数的斐波那契和阶乘
+// variables::variables_data_type 讲了整数溢出的四种显式处理方式, control_flow::fibonacci_over_flow
+// 则是直接用 isize 相加, 溢出时 debug 下 panic、release 下悄悄回绕
+// 这里对每一种原生整数类型, 按四种策略分别计算 F(n) 和 n!:
+//   * checked      第一次溢出就返回 None
+//   * wrapping     按 2 的位数次幂回绕, 继续算下去
+//   * saturating   停在类型的最大值
+//   * overflowing  返回回绕后的值, 以及中途是否溢出过
+// 另外给出每种类型能放下的最大 n(limits)
+// ! 迭代只算到需要的那一项: 求 F(n) 时不会顺带算出 F(n+1), 否则 F(n) 本身放得下也会被判为溢出
+//   (climb_stairs 就是这样, 它在 n = 46 时溢出, 尽管 F(46) 放得进 i32)
+use std::fmt;
+
+// 四种策略需要的原生整数方法
+pub trait FixedInt: Copy + PartialEq + fmt::Display + fmt::Debug {
+    const NAME: &'static str;
+    const ZERO: Self;
+    const ONE: Self;
+    const MAX: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn saturating_add(self, other: Self) -> Self;
+    fn overflowing_add(self, other: Self) -> (Self, bool);
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn wrapping_mul(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn overflowing_mul(self, other: Self) -> (Self, bool);
+    // 阶乘的乘数; 放不下时返回 None(乘积一定也放不下)
+    fn from_u32(value: u32) -> Option<Self>;
+    // 和 `value as T` 一样, 放不下时按 2 的位数次幂回绕
+    fn wrapping_from_u32(value: u32) -> Self;
+}
+
+macro_rules! fixed_int {
+    ($($t:ty),*) => {
+        $(
+            impl FixedInt for $t {
+                const NAME: &'static str = stringify!($t);
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+                const MAX: Self = <$t>::MAX;
+
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+                fn saturating_add(self, other: Self) -> Self {
+                    <$t>::saturating_add(self, other)
+                }
+                fn overflowing_add(self, other: Self) -> (Self, bool) {
+                    <$t>::overflowing_add(self, other)
+                }
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+                fn saturating_mul(self, other: Self) -> Self {
+                    <$t>::saturating_mul(self, other)
+                }
+                fn overflowing_mul(self, other: Self) -> (Self, bool) {
+                    <$t>::overflowing_mul(self, other)
+                }
+                fn from_u32(value: u32) -> Option<Self> {
+                    <$t>::try_from(value).ok()
+                }
+                fn wrapping_from_u32(value: u32) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+fixed_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+// 溢出策略, 命令行和 limits 的输出里使用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    Checked,
+    Wrapping,
+    Saturating,
+    Overflowing,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 4] = [
+        Policy::Checked,
+        Policy::Wrapping,
+        Policy::Saturating,
+        Policy::Overflowing,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::Checked => "checked",
+            Policy::Wrapping => "wrapping",
+            Policy::Saturating => "saturating",
+            Policy::Overflowing => "overflowing",
+        }
+    }
+}
+
+// F(n), 每一步的加法由 add 决定, add 返回 None 时立即停止
+// ? 循环 n - 1 次, 最后一次算出的就是 F(n), 不会多算一项
+fn fibonacci_by<T: FixedInt>(n: u32, mut add: impl FnMut(T, T) -> Option<T>) -> Option<T> {
+    if n == 0 {
+        return Some(T::ZERO);
+    }
+    let (mut a, mut b) = (T::ZERO, T::ONE);
+    for _ in 1..n {
+        (a, b) = (b, add(a, b)?);
+    }
+    Some(b)
+}
+
+// n!, 每一步的乘法由 mul 决定, 乘数 k 由 mul 自己转换成 T
+fn factorial_by<T: FixedInt>(n: u32, mul: impl FnMut(T, u32) -> Option<T>) -> Option<T> {
+    (2..=n).try_fold(T::ONE, mul)
+}
+
+pub fn fibonacci_checked<T: FixedInt>(n: u32) -> Option<T> {
+    fibonacci_by(n, T::checked_add)
+}
+
+pub fn fibonacci_wrapping<T: FixedInt>(n: u32) -> T {
+    fibonacci_by(n, |a: T, b| Some(a.wrapping_add(b))).unwrap_or(T::ZERO)
+}
+
+pub fn fibonacci_saturating<T: FixedInt>(n: u32) -> T {
+    fibonacci_by(n, |a: T, b| Some(a.saturating_add(b))).unwrap_or(T::ZERO)
+}
+
+// (回绕后的值, 中途是否溢出过)
+pub fn fibonacci_overflowing<T: FixedInt>(n: u32) -> (T, bool) {
+    let mut overflowed = false;
+    let value = fibonacci_by(n, |a: T, b| {
+        let (sum, overflow) = a.overflowing_add(b);
+        overflowed |= overflow;
+        Some(sum)
+    });
+    (value.unwrap_or(T::ZERO), overflowed)
+}
+
+pub fn factorial_checked<T: FixedInt>(n: u32) -> Option<T> {
+    factorial_by(n, |product: T, k| product.checked_mul(T::from_u32(k)?))
+}
+
+// ? 乘数 k 本身放不下时(例如 u8 的 300!), 把 k 也按回绕处理: 先回绕再相乘和直接回绕乘积的结果相同
+pub fn factorial_wrapping<T: FixedInt>(n: u32) -> T {
+    factorial_by(n, |product: T, k| {
+        Some(product.wrapping_mul(T::wrapping_from_u32(k)))
+    })
+    .unwrap_or(T::ONE)
+}
+
+// ? 乘数放不下时乘积一定已经超过最大值, 直接饱和
+pub fn factorial_saturating<T: FixedInt>(n: u32) -> T {
+    factorial_by(n, |product: T, k| {
+        Some(match T::from_u32(k) {
+            Some(k) => product.saturating_mul(k),
+            None => product.saturating_mul(T::MAX),
+        })
+    })
+    .unwrap_or(T::ONE)
+}
+
+pub fn factorial_overflowing<T: FixedInt>(n: u32) -> (T, bool) {
+    let mut overflowed = false;
+    let value = factorial_by(n, |product: T, k| {
+        let (result, overflow) = product.overflowing_mul(T::wrapping_from_u32(k));
+        overflowed |= overflow || T::from_u32(k).is_none();
+        Some(result)
+    });
+    (value.unwrap_or(T::ONE), overflowed)
+}
+
+// 一种类型能放下的最大 n
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub name: &'static str,
+    // F(n) 放得下的最大 n
+    pub fibonacci: u32,
+    // n! 放得下的最大 n
+    pub factorial: u32,
+}
+
+pub fn limits_of<T: FixedInt>() -> Limits {
+    // 斐波那契和阶乘都是递增的, 从 0 开始找到第一个放不下的 n
+    let last = |fits: &dyn Fn(u32) -> bool| (1..).find(|&n| !fits(n)).unwrap_or(1) - 1;
+    Limits {
+        name: T::NAME,
+        fibonacci: last(&|n| fibonacci_checked::<T>(n).is_some()),
+        factorial: last(&|n| factorial_checked::<T>(n).is_some()),
+    }
+}
+
+// 对每一种原生整数类型调用一次泛型函数, 例如 for_each_type!(print_policies, n)
+macro_rules! for_each_type {
+    ($f:ident $(, $arg:expr)*) => {
+        [
+            $f::<i8>($($arg),*),
+            $f::<i16>($($arg),*),
+            $f::<i32>($($arg),*),
+            $f::<i64>($($arg),*),
+            $f::<i128>($($arg),*),
+            $f::<isize>($($arg),*),
+            $f::<u8>($($arg),*),
+            $f::<u16>($($arg),*),
+            $f::<u32>($($arg),*),
+            $f::<u64>($($arg),*),
+            $f::<u128>($($arg),*),
+            $f::<usize>($($arg),*),
+        ]
+    };
+}
+
+pub fn limits() -> Vec<Limits> {
+    for_each_type!(limits_of).to_vec()
+}
+
+fn format_checked<T: FixedInt>(value: Option<T>) -> String {
+    value.map_or("None".to_string(), |value| value.to_string())
+}
+
+// 一种类型在四种策略下的 F(n) 和 n!, 每种策略一行
+fn policy_rows<T: FixedInt>(n: u32) -> Vec<String> {
+    Policy::ALL
+        .iter()
+        .map(|policy| {
+            let (fibonacci, factorial) = match policy {
+                Policy::Checked => (
+                    format_checked(fibonacci_checked::<T>(n)),
+                    format_checked(factorial_checked::<T>(n)),
+                ),
+                Policy::Wrapping => (
+                    fibonacci_wrapping::<T>(n).to_string(),
+                    factorial_wrapping::<T>(n).to_string(),
+                ),
+                Policy::Saturating => (
+                    fibonacci_saturating::<T>(n).to_string(),
+                    factorial_saturating::<T>(n).to_string(),
+                ),
+                Policy::Overflowing => (
+                    format!("{:?}", fibonacci_overflowing::<T>(n)),
+                    format!("{:?}", factorial_overflowing::<T>(n)),
+                ),
+            };
+            format!(
+                "{:<6} {:<12} {:>42} {:>42}",
+                T::NAME,
+                policy.name(),
+                fibonacci,
+                factorial
+            )
+        })
+        .collect()
+}
+
+// 子命令入口:
+//   overflow          每种类型能放下的最大 n
+//   overflow <n>      每种类型在四种策略下的 F(n) 和 n!
+pub fn overflow_main(args: &[String]) {
+    match args.first().map(|arg| arg.parse::<u32>()) {
+        None => {
+            println!("{:<6} {:>12} {:>12}", "TYPE", "MAX FIB N", "MAX FACT N");
+            for limits in limits() {
+                println!(
+                    "{:<6} {:>12} {:>12}",
+                    limits.name, limits.fibonacci, limits.factorial
+                );
+            }
+        }
+        Some(Ok(n)) => {
+            println!(
+                "{:<6} {:<12} {:>42} {:>42}",
+                "TYPE",
+                "POLICY",
+                format!("F({})", n),
+                format!("{}!", n)
+            );
+            for rows in for_each_type!(policy_rows, n) {
+                for row in rows {
+                    println!("{}", row);
+                }
+            }
+        }
+        Some(Err(_)) => eprintln!("用法: overflow [n]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sequences::{Fibonacci, Sequence};
+    use num::bigint::BigUint;
+    use num::traits::One;
+
+    // F(13) = 233, F(14) = 377; 5! = 120, 6! = 720
+    #[test]
+    fn known_limits() {
+        let expected = [
+            ("i8", 11, 5),
+            ("u8", 13, 5),
+            ("i32", 46, 12),
+            ("u32", 47, 12),
+            ("i64", 92, 20),
+            ("u64", 93, 20),
+            ("i128", 184, 33),
+            ("u128", 186, 34),
+        ];
+        let limits = limits();
+        for (name, fibonacci, factorial) in expected {
+            let found = limits.iter().find(|limits| limits.name == name).unwrap();
+            assert_eq!(
+                (found.fibonacci, found.factorial),
+                (fibonacci, factorial),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(limits.len(), 12);
+    }
+
+    // u8 第一次溢出的 n: F(14) = 377 = 256 + 121, 6! = 720 = 2 * 256 + 208
+    #[test]
+    fn first_overflow_u8() {
+        assert_eq!(fibonacci_checked::<u8>(13), Some(233));
+        assert_eq!(fibonacci_overflowing::<u8>(13), (233, false));
+        assert_eq!(fibonacci_checked::<u8>(14), None);
+        assert_eq!(fibonacci_wrapping::<u8>(14), 121);
+        assert_eq!(fibonacci_saturating::<u8>(14), 255);
+        assert_eq!(fibonacci_overflowing::<u8>(14), (121, true));
+
+        assert_eq!(factorial_overflowing::<u8>(5), (120, false));
+        assert_eq!(factorial_checked::<u8>(6), None);
+        assert_eq!(factorial_wrapping::<u8>(6), 208);
+        assert_eq!(factorial_saturating::<u8>(6), 255);
+        assert_eq!(factorial_overflowing::<u8>(6), (208, true));
+    }
+
+    // 有符号类型回绕到负数, 饱和停在正的最大值
+    #[test]
+    fn first_overflow_i32() {
+        // F(47) = 2971215073 = 2^32 - 1323752223
+        assert_eq!(fibonacci_checked::<i32>(46), Some(1836311903));
+        assert_eq!(fibonacci_checked::<i32>(47), None);
+        assert_eq!(fibonacci_wrapping::<i32>(47), -1323752223);
+        assert_eq!(fibonacci_saturating::<i32>(47), i32::MAX);
+        assert_eq!(fibonacci_overflowing::<i32>(47), (-1323752223, true));
+        // 13! = 6227020800 = 2^32 + 1932053504
+        assert_eq!(factorial_checked::<i32>(13), None);
+        assert_eq!(factorial_wrapping::<i32>(13), 1932053504);
+        assert_eq!(factorial_saturating::<i32>(13), i32::MAX);
+        assert_eq!(factorial_overflowing::<i32>(13), (1932053504, true));
+    }
+
+    // 乘数 k 本身放不下时走 wrapping_from_u32, 和 `k as i8` 一样
+    #[test]
+    fn signed_multiplier_wraps() {
+        assert_eq!(<i8 as FixedInt>::wrapping_from_u32(200), -56);
+        assert_eq!(<i8 as FixedInt>::wrapping_from_u32(300), 44);
+        assert_eq!(<i32 as FixedInt>::wrapping_from_u32(u32::MAX), -1);
+        assert_eq!(<i8 as FixedInt>::from_u32(200), None);
+        // 6! = 720 ≡ 208 (mod 256), 作为 i8 是 -48
+        assert_eq!(factorial_wrapping::<i8>(6), -48);
+        // 从 10! 起是 256 的倍数, 乘数超过 127 以后仍然是 0, 溢出标记一直为真
+        assert_eq!(factorial_wrapping::<i8>(200), 0);
+        assert_eq!(factorial_overflowing::<i8>(200), (0, true));
+        assert_eq!(factorial_saturating::<i8>(200), i8::MAX);
+        assert_eq!(factorial_checked::<i8>(200), None);
+    }
+
+    // checked 在放得下时等于大数结果, 之后都是 None; wrapping 等于大数结果对 2^64 取模
+    #[test]
+    fn u64_against_big() {
+        let limit = limits_of::<u64>().fibonacci;
+        for (n, f) in Fibonacci.iter().enumerate().take(300) {
+            let n = n as u32;
+            match fibonacci_checked::<u64>(n) {
+                Some(value) => {
+                    assert!(n <= limit);
+                    assert_eq!(BigUint::from(value), f);
+                }
+                None => assert!(n > limit),
+            }
+            assert_eq!(
+                BigUint::from(fibonacci_wrapping::<u64>(n)),
+                &f % (BigUint::one() << 64u32),
+                "F({})",
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,354 @@
+// 取模的斐波那契和皮萨诺周期
+// climb_stairs 就是 LeetCode 70, 这类题常常要求 "答案对 1e9+7 取模", 这时不需要大数:
+//   * fib_mod(n, m): 和 fast::fibonacci 同样的快速倍增, 每一步都取模, 只用 u64(乘法临时用 u128)
+//   * pisano(m): F(n) mod m 的周期 π(m), 例如 π(10) = 60, 所以 F(n) 的个位数每 60 项重复一次
+// 有了周期, n 大到 u64 放不下(例如 10^100)时也能先对 π(m) 取模再计算
+// π(m) 的计算:
+//   1. 把 m 分解成 p1^k1 * p2^k2 * ..., π(m) = lcm(π(p1^k1), π(p2^k2), ...)
+//   2. π(p^k) = p^(k-1) * π(p)
+//      ! 这一步是 Wall 猜想, 至今没有证明, 但已经对 10^14 以内的素数验证过
+//   3. π(2) = 3, π(5) = 20; p ≡ ±1 (mod 10) 时 π(p) 整除 p - 1, p ≡ ±3 (mod 10) 时整除 2(p + 1)
+//      从这个倍数出发, 逐个去掉素因子, 直到不再是周期
+// 分解用 Miller-Rabin 判断素数、Pollard rho 找因子, u64 范围内都很快
+use num::bigint::BigUint;
+use num::integer::Integer;
+use num::traits::ToPrimitive;
+
+// 常见的模数
+pub const MOD: u64 = 1_000_000_007;
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+// (F(n) mod m, F(n+1) mod m)
+// ? 和 fast::fibonacci 一样的快速倍增, 2F(k+1) - F(k) 在取模后可能是负数, 所以先加上 m
+fn fib_pair_mod(n: u64, m: u64) -> (u64, u64) {
+    let (mut a, mut b) = (0, 1 % m);
+    for bit in (0..u64::BITS - n.leading_zeros()).rev() {
+        let double = mul_mod(
+            a,
+            ((2 * b as u128 + m as u128 - a as u128) % m as u128) as u64,
+            m,
+        );
+        let double_next =
+            ((mul_mod(a, a, m) as u128 + mul_mod(b, b, m) as u128) % m as u128) as u64;
+        if n >> bit & 1 == 1 {
+            a = double_next;
+            b = ((double as u128 + a as u128) % m as u128) as u64;
+        } else {
+            a = double;
+            b = double_next;
+        }
+    }
+    (a, b)
+}
+
+// F(n) mod m, m 必须大于 0
+pub fn fib_mod(n: u64, m: u64) -> u64 {
+    assert!(m > 0, "模数必须大于 0");
+    fib_pair_mod(n, m).0
+}
+
+// n 超出 u64 时, 先对周期取模
+pub fn fib_mod_big(n: &BigUint, m: u64) -> u64 {
+    let period = BigUint::from(pisano(m));
+    let reduced = (n % period).to_u64().expect("余数小于周期");
+    fib_mod(reduced, m)
+}
+
+// 确定性的 Miller-Rabin: 用前 12 个素数做底, 对所有 u64 都是准确的
+pub fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    // n - 1 = d * 2^s
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    BASES.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// Pollard rho(Floyd 判圈), 返回 n 的一个非平凡因子, n 必须是奇合数
+fn pollard_rho(n: u64) -> u64 {
+    // ? 某个常数 c 可能失败(x 和 y 同时回到起点), 换下一个 c 重试
+    for c in 1.. {
+        let f = |x: u64| ((mul_mod(x, x, n) as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = x.abs_diff(y).gcd(&n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!()
+}
+
+// 素因数分解, 返回按素数升序的 (素数, 指数)
+pub fn factorize(n: u64) -> Vec<(u64, u32)> {
+    let mut primes = Vec::new();
+    let mut rest = n;
+    // 小素数先用试除去掉, Pollard rho 只处理剩下的大因子
+    for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        while rest.is_multiple_of(p) {
+            primes.push(p);
+            rest /= p;
+        }
+    }
+    let mut stack = vec![rest];
+    while let Some(n) = stack.pop() {
+        if n == 1 {
+            continue;
+        }
+        if is_prime(n) {
+            primes.push(n);
+            continue;
+        }
+        let d = pollard_rho(n);
+        stack.push(d);
+        stack.push(n / d);
+    }
+    primes.sort_unstable();
+    let mut factors: Vec<(u64, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Some((last, k)) if *last == p => *k += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+// d 是不是 F mod m 的一个周期(不一定最小): F(d) ≡ 0 且 F(d+1) ≡ 1
+fn is_period(d: u64, m: u64) -> bool {
+    fib_pair_mod(d, m) == (0, 1 % m)
+}
+
+// 素数 p 的周期 π(p)
+fn pisano_prime(p: u64) -> u64 {
+    let multiple = match p {
+        2 => return 3,
+        5 => return 20,
+        _ if matches!(p % 10, 1 | 9) => p - 1,
+        _ => 2 * (p + 1),
+    };
+    // 最小周期整除 multiple, 只要去掉一个素因子后仍是周期就继续去掉
+    let mut period = multiple;
+    for (q, _) in factorize(multiple) {
+        while period % q == 0 && is_period(period / q, p) {
+            period /= q;
+        }
+    }
+    period
+}
+
+// F(n) mod m 的最小周期 π(m), π(1) = 1
+// ! π(m) <= 6m, 所以 m 不能超过 u64::MAX / 6
+pub fn pisano(m: u64) -> u64 {
+    assert!(m > 0, "模数必须大于 0");
+    assert!(m <= u64::MAX / 6, "模数太大, 周期会超出 u64");
+    factorize(m)
+        .into_iter()
+        .map(|(p, k)| pisano_prime(p) * p.pow(k - 1))
+        .fold(1, |period, part| period.lcm(&part))
+}
+
+fn format_factors(factors: &[(u64, u32)]) -> String {
+    if factors.is_empty() {
+        return "1".to_string();
+    }
+    let parts: Vec<String> = factors
+        .iter()
+        .map(|&(p, k)| {
+            if k == 1 {
+                p.to_string()
+            } else {
+                format!("{}^{}", p, k)
+            }
+        })
+        .collect();
+    parts.join(" * ")
+}
+
+// 子命令入口: fibmod <n> [m], n 可以超出 u64, m 默认 1e9+7
+pub fn fib_mod_main(args: &[String]) {
+    let n = args.first().and_then(|arg| arg.parse::<BigUint>().ok());
+    let m = match args.get(1) {
+        Some(arg) => arg
+            .parse::<u64>()
+            .ok()
+            .filter(|&m| m > 0 && m <= u64::MAX / 6),
+        None => Some(MOD),
+    };
+    let (Some(n), Some(m)) = (n, m) else {
+        eprintln!("用法: fibmod <n> [m], 0 < m <= {}", u64::MAX / 6);
+        return;
+    };
+    let value = match n.to_u64() {
+        Some(n) => fib_mod(n, m),
+        None => fib_mod_big(&n, m),
+    };
+    println!("F({}) mod {} = {}", n, m, value);
+}
+
+// 子命令入口: pisano <m>
+pub fn pisano_main(args: &[String]) {
+    let Some(m) = args
+        .first()
+        .and_then(|arg| arg.parse::<u64>().ok())
+        .filter(|&m| m > 0 && m <= u64::MAX / 6)
+    else {
+        eprintln!("用法: pisano <m>, 0 < m <= {}", u64::MAX / 6);
+        return;
+    };
+    println!("m = {}", format_factors(&factorize(m)));
+    println!("π({}) = {}", m, pisano(m));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sequences::fast;
+
+    // 逐项计算直到 (0, 1) 再次出现, 用来验证 pisano
+    fn pisano_naive(m: u64) -> u64 {
+        if m == 1 {
+            return 1;
+        }
+        let (mut a, mut b) = (0, 1);
+        for period in 1.. {
+            (a, b) = (b, (a + b) % m);
+            if (a, b) == (0, 1) {
+                return period;
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn known_periods() {
+        assert_eq!(
+            [1, 2, 3, 5, 10, 100, 1000].map(pisano),
+            [1, 3, 8, 20, 60, 300, 1500]
+        );
+    }
+
+    // 按素因子组合出的周期和逐项找到的一致
+    #[test]
+    fn against_naive() {
+        for m in 1..=2000 {
+            assert_eq!(pisano(m), pisano_naive(m), "π({})", m);
+        }
+    }
+
+    // 取模的快速倍增和大数结果取模一致, 模数接近上限时 u128 的中间结果也不溢出
+    #[test]
+    fn fib_mod_against_big() {
+        for n in 0..500 {
+            let f = fast::fibonacci(n);
+            for m in [1, 10, MOD, u64::MAX / 6] {
+                assert_eq!(BigUint::from(fib_mod(n, m)), &f % m, "F({}) mod {}", n, m);
+            }
+        }
+    }
+
+    // Carmichael 数能骗过费马测试, 骗不过 Miller-Rabin
+    #[test]
+    fn carmichael_numbers_are_composite() {
+        for n in [
+            561, 1105, 1729, 2465, 2821, 6601, 8911, 41041, 825265, 321197185,
+        ] {
+            assert!(!is_prime(n), "{}", n);
+        }
+        // 对前 9 个素数做底都是强伪素数
+        assert!(!is_prime(3825123056546413051));
+        assert_eq!(
+            factorize(3825123056546413051),
+            [(149491, 1), (747451, 1), (34233211, 1)]
+        );
+    }
+
+    #[test]
+    fn primes_near_u64_max() {
+        // u64 范围内最大的素数
+        assert!(is_prime(18446744073709551557));
+        assert!(!is_prime(u64::MAX));
+        assert!(!is_prime(u64::MAX - 1));
+        assert!(!is_prime(18446744073709551559));
+        assert_eq!(
+            factorize(u64::MAX),
+            [
+                (3, 1),
+                (5, 1),
+                (17, 1),
+                (257, 1),
+                (641, 1),
+                (65537, 1),
+                (6700417, 1)
+            ]
+        );
+        assert_eq!(factorize(18446744073709551557), [(18446744073709551557, 1)]);
+    }
+
+    // 两个接近 2^32 的素数之积, 只能靠 Pollard rho 分开
+    #[test]
+    fn factorize_semiprime() {
+        let (p, q) = (4294967279, 4294967291);
+        assert!(is_prime(p) && is_prime(q));
+        assert_eq!(factorize(p * q), [(p, 1), (q, 1)]);
+        assert_eq!(factorize(MOD * (MOD + 2)), [(MOD, 1), (MOD + 2, 1)]);
+        assert_eq!(factorize(1), []);
+    }
+
+    // 2^64 + 5 ≡ 21 (mod 60), F(21) = 10946
+    #[test]
+    fn fib_mod_beyond_u64() {
+        let n = (BigUint::from(1u32) << 64u32) + 5u32;
+        assert_eq!(fib_mod_big(&n, 10), 6);
+        // 周期用逐项找到的, 余数那一项用大数算
+        for m in [7, 97, 1000, 65536] {
+            let n = BigUint::from(u64::MAX) * 3u32 + m;
+            let period = pisano_naive(m);
+            let reduced = (&n % period).to_u64().unwrap();
+            assert_eq!(
+                BigUint::from(fib_mod_big(&n, m)),
+                fast::fibonacci(reduced) % m,
+                "m = {}",
+                m
+            );
+        }
+    }
+}
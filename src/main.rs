@@ -92,7 +92,60 @@ async fn main() {
     // modules::ownership::main_ownership();
     // println!("计算结果为: {:?}", modules::climb_stairs::climb_stairs(2));
     // modules::reference::reference::reference_fn();
-    modules::expression::expression_main().await;
+    // 子命令, 不带参数时仍然进入当前的学习入口
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        // 把 modules 下的注释整理成 Markdown 教材
+        Some("book") => modules::lesson_book::book_main(&args[1..]),
+        // 斐波那契、卢卡斯、卡特兰等整数数列
+        Some("sequence") => modules::sequences::sequence_main(&args[1..]),
+        // 快速倍增计算大下标的斐波那契数
+        Some("fib") => modules::sequences::fast::fib_main(&args[1..]),
+        // 斐波那契取模和皮萨诺周期
+        Some("fibmod") => modules::sequences::pisano::fib_mod_main(&args[1..]),
+        Some("pisano") => modules::sequences::pisano::pisano_main(&args[1..]),
+        // 任意步长、禁止台阶和代价的爬楼梯
+        Some("stairs") => modules::climb_stairs::general::stairs_main(&args[1..]),
+        // 各种定宽整数在四种溢出策略下的斐波那契和阶乘
+        Some("overflow") => modules::sequences::overflow::overflow_main(&args[1..]),
+        // 朴素递归和记忆化递归的调用次数对比
+        Some("memo") => modules::sequences::memo::memo_main(&args[1..]),
+        // 以 Prometheus 格式暴露后台采样的系统指标
+        Some("metrics") => {
+            modules::expression::system_info::metrics::metrics_main(&args[1..]).await
+        }
+        // 按 JSON 规则文件做阈值告警
+        Some("alert") => modules::expression::system_info::alert::alert_main(&args[1..]).await,
+        // 常驻采样进程, 通过 Unix socket 回答查询
+        #[cfg(unix)]
+        Some("daemon") => modules::expression::system_info::daemon::daemon_main(&args[1..]).await,
+        #[cfg(unix)]
+        Some("query") => modules::expression::system_info::daemon::query_main(&args[1..]).await,
+        // ? 其他平台没有 Unix domain socket, 明确告诉用户, 而不是落到学习入口
+        #[cfg(not(unix))]
+        Some(command @ ("daemon" | "query")) => {
+            eprintln!("{} 依赖 Unix domain socket, 当前平台不支持", command)
+        }
+        // 终端实时面板
+        Some("monitor") => {
+            modules::expression::system_info::monitor::monitor_main(&args[1..]).await
+        }
+        // 把采样写入 JSON Lines 或 CSV 文件
+        Some("record") => {
+            modules::expression::system_info::recorder::record_main(&args[1..]).await
+        }
+        // 汇总记录文件中的一段时间
+        Some("report") => modules::expression::system_info::report::report_main(&args[1..]),
+        // 保存快照, 对比基准测试前后的机器状态
+        Some("snapshot") => {
+            modules::expression::system_info::snapshot::snapshot_main(&args[1..]).await
+        }
+        // 磁盘、网络吞吐和文件系统用量
+        Some("io") => modules::expression::system_info::throughput::io_main(&args[1..]).await,
+        // 按 CPU 和内存列出占用最多的进程
+        Some("top") => modules::expression::system_info::process::top_main(&args[1..]).await,
+        _ => modules::expression::expression_main().await,
+    }
 }
 
 